@@ -8,7 +8,9 @@ use swash::{Attributes, CacheKey, FontRef};
 
 fn main() {
     let mut builder = LibraryBuilder::default();
-    builder.add_system_path("/usr/share/fonts").unwrap();
+    // Fonts in additional directories participate in fallback along with the
+    // fonts located by the platform.
+    let _ = builder.add_font_dir("/usr/share/fonts");
     let library = builder.build();
     let fcx = FontContext::new(&library);
     let mut cache = FontCache::default();
@@ -0,0 +1,153 @@
+//! Build-time embedding of application fonts for `fount`.
+//!
+//! The [`fount_embed!`] macro scans font files when the crate using it is
+//! compiled and expands to a static collection that contains their data:
+//!
+//! ```ignore
+//! use fount::{embed::StaticCollectionData, Library};
+//!
+//! static FONTS: &StaticCollectionData = fount_embed::fount_embed!("assets/fonts/*.ttf");
+//!
+//! let library = Library::from_embedded(FONTS);
+//! ```
+
+use proc_macro::{TokenStream, TokenTree};
+use std::path::{Path, PathBuf};
+
+/// Expands to a `&'static fount::embed::StaticCollectionData` containing the
+/// fonts in the files matched by a path pattern.
+///
+/// The pattern is a string literal relative to the directory of the
+/// manifest of the crate being compiled. Path components may contain `*`,
+/// which matches any sequence of characters, and `?`, which matches a single
+/// character. Matched files are embedded in sorted order, which determines
+/// the order of the default families.
+///
+/// Changes to matched files trigger recompilation, but files added later
+/// are only picked up when the invoking crate is rebuilt for another reason.
+#[proc_macro]
+pub fn fount_embed(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(source) => source.parse().unwrap(),
+        Err(message) => format!("compile_error!({:?})", message).parse().unwrap(),
+    }
+}
+
+fn expand(input: TokenStream) -> Result<String, String> {
+    let pattern = parse_literal(input)?;
+    let base = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .ok_or("CARGO_MANIFEST_DIR is not set")?;
+    let pattern_path = base.join(&pattern);
+    let mut paths = Vec::new();
+    let mut root = PathBuf::new();
+    let mut components = Vec::new();
+    for component in pattern_path.components() {
+        let component = component.as_os_str();
+        if components.is_empty() && !has_wildcard(&component.to_string_lossy()) {
+            root.push(component);
+        } else {
+            components.push(component.to_string_lossy().into_owned());
+        }
+    }
+    if components.is_empty() {
+        paths.push(root);
+    } else {
+        matches(&root, &components, &mut paths).map_err(|e| e.to_string())?;
+    }
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("no files match \"{}\"", pattern));
+    }
+    fount::embed::collection_source(&paths).map_err(|e| e.to_string())
+}
+
+/// Returns the value of a single string literal.
+fn parse_literal(input: TokenStream) -> Result<String, String> {
+    const USAGE: &str = "expected a string literal, as in fount_embed!(\"fonts/*.ttf\")";
+    let mut tokens = input.into_iter();
+    let literal = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => literal.to_string(),
+        _ => return Err(USAGE.into()),
+    };
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return raw[hashes..raw.len() - hashes]
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .map(|s| s.to_owned())
+            .ok_or_else(|| USAGE.into());
+    }
+    let quoted = literal
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(USAGE)?;
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            value.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some(ch @ ('\\' | '"' | '\'')) => value.push(ch),
+            _ => return Err("unsupported escape in font path pattern".into()),
+        }
+    }
+    Ok(value)
+}
+
+/// Adds the files below `dir` that match the remaining pattern components.
+fn matches(dir: &Path, components: &[String], paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    if !has_wildcard(component) {
+        let path = dir.join(component);
+        if rest.is_empty() {
+            if path.is_file() {
+                paths.push(path);
+            }
+            return Ok(());
+        }
+        return matches(&path, rest, paths);
+    }
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let pattern = component.chars().collect::<Vec<_>>();
+        let name = name.chars().collect::<Vec<_>>();
+        if !wildcard_match(&pattern, &name) {
+            continue;
+        }
+        let path = entry.path();
+        if rest.is_empty() {
+            if path.is_file() {
+                paths.push(path);
+            }
+        } else if path.is_dir() {
+            matches(&path, rest, paths)?;
+        }
+    }
+    Ok(())
+}
+
+fn has_wildcard(component: &str) -> bool {
+    component.contains(|ch| ch == '*' || ch == '?')
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| wildcard_match(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+        Some((ch, rest)) => name.first() == Some(ch) && wildcard_match(rest, &name[1..]),
+    }
+}
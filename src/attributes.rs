@@ -0,0 +1,287 @@
+//! Conversion between font attributes and CSS style strings.
+
+use swash::{Attributes, ObliqueAngle, Stretch, Style, Weight};
+
+/// Extension methods for building font attributes and converting them to and
+/// from CSS-like strings such as "italic 600 condensed".
+pub trait AttributesExt: Sized {
+    /// Parses attributes from a whitespace separated list of CSS
+    /// `font-style`, `font-weight` and `font-stretch` values in any order.
+    ///
+    /// Weights may be numbers or the keywords "bold", "thin", "extra-light",
+    /// "light", "medium", "semi-bold", "extra-bold" and "black". Stretches
+    /// may be keywords or percentages. The keyword "normal" may appear in
+    /// place of any value. Returns `None` if a value is not recognized or
+    /// more than one value is given for an attribute.
+    ///
+    /// # Example
+    /// ```
+    /// use fount::AttributesExt;
+    /// use swash::{Attributes, Stretch, Style, Weight};
+    ///
+    /// let attributes = Attributes::parse_css("italic 600 condensed").unwrap();
+    /// assert_eq!(
+    ///     attributes,
+    ///     Attributes::new(Stretch::CONDENSED, Weight::SEMI_BOLD, Style::Italic)
+    /// );
+    /// assert_eq!(attributes.to_css(), "italic 600 condensed");
+    /// ```
+    fn parse_css(s: &str) -> Option<Self>;
+
+    /// Formats the attributes as a CSS-like string that can be parsed with
+    /// [`parse_css`](Self::parse_css).
+    ///
+    /// Values are written in the order of the CSS `font` shorthand with
+    /// weights as numbers. Normal values are omitted and attributes that
+    /// are all normal produce "normal".
+    fn to_css(&self) -> String;
+
+    /// Returns the attributes with the specified stretch.
+    fn with_stretch(self, stretch: Stretch) -> Self;
+
+    /// Returns the attributes with the specified weight.
+    fn with_weight(self, weight: Weight) -> Self;
+
+    /// Returns the attributes with the specified style.
+    fn with_style(self, style: Style) -> Self;
+}
+
+impl AttributesExt for Attributes {
+    fn parse_css(s: &str) -> Option<Self> {
+        let mut stretch = None;
+        let mut weight = None;
+        let mut style = None;
+        let mut tokens = s.split_whitespace().peekable();
+        tokens.peek()?;
+        while let Some(token) = tokens.next() {
+            let token = token.to_ascii_lowercase();
+            if token == "normal" {
+                continue;
+            }
+            let (slot, value) = if token == "oblique" {
+                let angle = tokens.peek().and_then(|next| parse_angle(next));
+                if angle.is_some() {
+                    tokens.next();
+                }
+                let angle = angle.unwrap_or_else(|| ObliqueAngle::from_degrees(14.));
+                (&mut style, Value::Style(Style::Oblique(angle)))
+            } else if token == "italic" {
+                (&mut style, Value::Style(Style::Italic))
+            } else if let Some(value) = parse_weight(&token) {
+                (&mut weight, Value::Weight(value))
+            } else if let Some(value) = parse_stretch(&token) {
+                (&mut stretch, Value::Stretch(value))
+            } else {
+                return None;
+            };
+            if slot.is_some() {
+                return None;
+            }
+            *slot = Some(value);
+        }
+        let mut attributes = Attributes::default();
+        for value in [stretch, weight, style].iter().flatten() {
+            attributes = match *value {
+                Value::Stretch(stretch) => attributes.with_stretch(stretch),
+                Value::Weight(weight) => attributes.with_weight(weight),
+                Value::Style(style) => attributes.with_style(style),
+            };
+        }
+        Some(attributes)
+    }
+
+    fn to_css(&self) -> String {
+        let (stretch, weight, style) = self.parts();
+        let mut parts = Vec::new();
+        match style {
+            Style::Normal => {}
+            Style::Italic => parts.push("italic".to_owned()),
+            Style::Oblique(angle) => {
+                let degrees = angle.to_degrees();
+                if degrees == 14. {
+                    parts.push("oblique".to_owned());
+                } else {
+                    parts.push(format!("oblique {}deg", degrees));
+                }
+            }
+        }
+        if weight != Weight::NORMAL {
+            parts.push(weight.0.to_string());
+        }
+        if stretch != Stretch::NORMAL {
+            parts.push(match STRETCH_KEYWORDS.iter().find(|(_, s)| *s == stretch) {
+                Some((keyword, _)) => keyword.to_string(),
+                None => format!("{}%", stretch.to_percentage()),
+            });
+        }
+        if parts.is_empty() {
+            "normal".to_owned()
+        } else {
+            parts.join(" ")
+        }
+    }
+
+    fn with_stretch(self, stretch: Stretch) -> Self {
+        let (_, weight, style) = self.parts();
+        Attributes::new(stretch, weight, style)
+    }
+
+    fn with_weight(self, weight: Weight) -> Self {
+        let (stretch, _, style) = self.parts();
+        Attributes::new(stretch, weight, style)
+    }
+
+    fn with_style(self, style: Style) -> Self {
+        let (stretch, weight, _) = self.parts();
+        Attributes::new(stretch, weight, style)
+    }
+}
+
+enum Value {
+    Stretch(Stretch),
+    Weight(Weight),
+    Style(Style),
+}
+
+const STRETCH_KEYWORDS: [(&str, Stretch); 8] = [
+    ("ultra-condensed", Stretch::ULTRA_CONDENSED),
+    ("extra-condensed", Stretch::EXTRA_CONDENSED),
+    ("condensed", Stretch::CONDENSED),
+    ("semi-condensed", Stretch::SEMI_CONDENSED),
+    ("semi-expanded", Stretch::SEMI_EXPANDED),
+    ("expanded", Stretch::EXPANDED),
+    ("extra-expanded", Stretch::EXTRA_EXPANDED),
+    ("ultra-expanded", Stretch::ULTRA_EXPANDED),
+];
+
+const WEIGHT_KEYWORDS: [(&str, Weight); 8] = [
+    ("thin", Weight::THIN),
+    ("extra-light", Weight::EXTRA_LIGHT),
+    ("light", Weight::LIGHT),
+    ("medium", Weight::MEDIUM),
+    ("semi-bold", Weight::SEMI_BOLD),
+    ("bold", Weight::BOLD),
+    ("extra-bold", Weight::EXTRA_BOLD),
+    ("black", Weight::BLACK),
+];
+
+fn parse_weight(token: &str) -> Option<Weight> {
+    if let Some((_, weight)) = WEIGHT_KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == token)
+    {
+        return Some(*weight);
+    }
+    let value = token.parse::<f32>().ok()?;
+    if (1. ..=1000.).contains(&value) {
+        Some(Weight(value.round() as u16))
+    } else {
+        None
+    }
+}
+
+fn parse_stretch(token: &str) -> Option<Stretch> {
+    if let Some((_, stretch)) = STRETCH_KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == token)
+    {
+        return Some(*stretch);
+    }
+    let percentage = token.strip_suffix('%')?.parse::<f32>().ok()?;
+    if percentage >= 0. {
+        Some(Stretch::from_percentage(percentage))
+    } else {
+        None
+    }
+}
+
+fn parse_angle(token: &str) -> Option<ObliqueAngle> {
+    let token = token.to_ascii_lowercase();
+    let (value, unit) = token
+        .find(|ch: char| ch.is_ascii_alphabetic())
+        .map(|pos| token.split_at(pos))?;
+    let value = value.parse::<f32>().ok()?;
+    Some(match unit {
+        "deg" => ObliqueAngle::from_degrees(value),
+        "grad" => ObliqueAngle::from_gradians(value),
+        "rad" => ObliqueAngle::from_radians(value),
+        "turn" => ObliqueAngle::from_turns(value),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values_in_any_order() {
+        let expected = Attributes::new(Stretch::CONDENSED, Weight::BOLD, Style::Italic);
+        assert_eq!(
+            Attributes::parse_css("bold italic condensed"),
+            Some(expected)
+        );
+        assert_eq!(
+            Attributes::parse_css("Condensed ITALIC 700"),
+            Some(expected)
+        );
+        assert_eq!(
+            Attributes::parse_css("normal 350 87.5%"),
+            Some(Attributes::new(
+                Stretch::from_percentage(87.5),
+                Weight(350),
+                Style::Normal
+            ))
+        );
+        assert_eq!(Attributes::parse_css("normal"), Some(Attributes::default()));
+    }
+
+    #[test]
+    fn parses_oblique_angles() {
+        let oblique = |degrees| {
+            Attributes::default().with_style(Style::Oblique(ObliqueAngle::from_degrees(degrees)))
+        };
+        assert_eq!(Attributes::parse_css("oblique"), Some(oblique(14.)));
+        assert_eq!(Attributes::parse_css("oblique 10deg"), Some(oblique(10.)));
+        assert_eq!(
+            Attributes::parse_css("oblique bold"),
+            Some(oblique(14.).with_weight(Weight::BOLD))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert_eq!(Attributes::parse_css(""), None);
+        assert_eq!(Attributes::parse_css("   "), None);
+        assert_eq!(Attributes::parse_css("heavy"), None);
+        assert_eq!(Attributes::parse_css("bold 600"), None);
+        assert_eq!(Attributes::parse_css("italic oblique"), None);
+        assert_eq!(Attributes::parse_css("0"), None);
+        assert_eq!(Attributes::parse_css("-50%"), None);
+    }
+
+    #[test]
+    fn round_trips_through_css() {
+        for css in [
+            "normal",
+            "italic",
+            "oblique 20deg",
+            "300",
+            "italic 600 condensed",
+            "expanded",
+            "bold 90%",
+        ] {
+            let attributes = Attributes::parse_css(css).unwrap();
+            assert_eq!(
+                Attributes::parse_css(&attributes.to_css()),
+                Some(attributes),
+                "{}",
+                css
+            );
+        }
+        assert_eq!(
+            Attributes::parse_css("bold 90%").unwrap().to_css(),
+            "700 90%"
+        );
+    }
+}
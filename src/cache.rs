@@ -0,0 +1,475 @@
+//! Persistent cache of scanned font metadata.
+//!
+//! The cache maps font file paths to the results of scanning them. Each entry
+//! records the modification time and size of the file so that entries are
+//! discarded automatically when a file changes.
+
+use super::data::fold_name;
+use super::metrics::{FaceMetrics, LineMetrics};
+use super::scan::ScannedFont;
+use super::script_tags::script_from_index;
+use super::vfs::FileSystem;
+use super::{
+    Capabilities, ColorFormat, FaceRejection, NamedInstance, SourcePriority, VariationAxis,
+};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use swash::text::Cjk;
+use swash::{Attributes, CacheKey};
+
+const MAGIC: [u8; 4] = *b"FNTC";
+
+/// Version of the cache format. This must be incremented whenever the
+/// format or the data recorded by the scanner changes.
+const CACHE_VERSION: u32 = 12;
+
+/// Modification time and size of a font file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FileStamp {
+    modified: (u64, u32),
+    len: u64,
+}
+
+impl FileStamp {
+    pub fn of(fs: &dyn FileSystem, path: &Path) -> Option<Self> {
+        let metadata = fs.metadata(path).ok()?;
+        let modified = metadata.modified?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+            len: metadata.len,
+        })
+    }
+}
+
+/// Fonts and indices of rejected faces recorded for a file.
+pub type CachedScan<'a> = (&'a [ScannedFont], &'a [(u32, FaceRejection)]);
+
+struct CacheEntry {
+    stamp: FileStamp,
+    fonts: Vec<ScannedFont>,
+    /// Indices of the faces that were rejected as malformed.
+    rejected: Vec<(u32, FaceRejection)>,
+}
+
+/// Scanned fonts keyed by file path, backed by a file on disk.
+pub struct ScanCache {
+    path: PathBuf,
+    entries: HashMap<PathBuf, CacheEntry>,
+    dirty: bool,
+}
+
+impl ScanCache {
+    /// Loads the cache from the specified file. A missing, corrupt or
+    /// outdated file produces an empty cache that will replace it when saved.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|data| read_entries(&data))
+            .unwrap_or_default();
+        Self {
+            path,
+            entries,
+            dirty: false,
+        }
+    }
+
+    /// Returns the fonts and rejected faces recorded for the file if it has
+    /// not changed since it was scanned.
+    pub fn get(&self, path: &Path, stamp: FileStamp) -> Option<CachedScan<'_>> {
+        let entry = self.entries.get(path)?;
+        if entry.stamp == stamp {
+            Some((&entry.fonts, &entry.rejected))
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        path: &Path,
+        stamp: FileStamp,
+        fonts: Vec<ScannedFont>,
+        rejected: Vec<(u32, FaceRejection)>,
+    ) {
+        self.entries.insert(
+            path.to_owned(),
+            CacheEntry {
+                stamp,
+                fonts,
+                rejected,
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Writes the cache to disk if it has been modified, dropping entries
+    /// for files in the file system that have since been changed or
+    /// removed.
+    pub fn save(&mut self, file_system: &dyn FileSystem) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        self.entries
+            .retain(|path, entry| FileStamp::of(file_system, path) == Some(entry.stamp));
+        let mut w = Writer::default();
+        w.0.extend_from_slice(&MAGIC);
+        w.u32(CACHE_VERSION);
+        // Paths that are not valid UTF-8 are not cached.
+        let entries = self
+            .entries
+            .iter()
+            .filter_map(|(path, entry)| Some((path.to_str()?, entry)))
+            .collect::<Vec<_>>();
+        w.u32(entries.len() as u32);
+        for (path, entry) in entries {
+            w.str(path);
+            w.u64(entry.stamp.modified.0);
+            w.u32(entry.stamp.modified.1);
+            w.u64(entry.stamp.len);
+            w.u32(entry.fonts.len() as u32);
+            for font in &entry.fonts {
+                write_font(&mut w, font);
+            }
+            w.u32(entry.rejected.len() as u32);
+            for (index, reason) in &entry.rejected {
+                w.u32(*index);
+                write_rejection(&mut w, reason);
+            }
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write to a temporary file first so that a concurrent reader never
+        // observes a partially written cache.
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, &w.0)?;
+        fs::rename(&tmp, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+fn write_font(w: &mut Writer, font: &ScannedFont) {
+    w.str(&font.name);
+    w.u32(font.localized_names.len() as u32);
+    for name in &font.localized_names {
+        w.str(name);
+    }
+    w.u32(font.index);
+    w.u32(font.attributes.0);
+    w.u32(font.scripts.len() as u32);
+    for (script, cjk) in &font.scripts {
+        w.u8(*script as u8);
+        w.u8(*cjk as u8);
+    }
+    w.u32(font.verified_scripts.len() as u32);
+    for script in &font.verified_scripts {
+        w.u8(*script as u8);
+    }
+    w.u8(font.capabilities.0);
+    match &font.metrics {
+        Some(metrics) => {
+            w.u8(1);
+            w.u8(metrics.use_typographic as u8);
+            w.line_metrics(Some(&metrics.legacy));
+            w.line_metrics(metrics.typographic.as_ref());
+            w.line_metrics(metrics.windows.as_ref());
+        }
+        None => w.u8(0),
+    }
+    w.u16(font.glyph_count);
+    w.u64(font.file_size);
+    w.u32(font.instances.len() as u32);
+    for instance in font.instances.iter() {
+        w.str(&instance.name);
+        w.u32(instance.coords.len() as u32);
+        for (tag, value) in &instance.coords {
+            w.u32(*tag);
+            w.u32(value.to_bits());
+        }
+        w.u32(instance.attributes.0);
+    }
+    w.u32(font.axes.len() as u32);
+    for axis in font.axes.iter() {
+        w.u32(axis.tag);
+        w.u32(axis.min.to_bits());
+        w.u32(axis.default.to_bits());
+        w.u32(axis.max.to_bits());
+    }
+    match font.optical_size {
+        Some((min, max)) => {
+            w.u8(1);
+            w.u32(min.to_bits());
+            w.u32(max.to_bits());
+        }
+        None => w.u8(0),
+    }
+    w.u8(font.cjk as u8);
+    w.u8(match font.color_format {
+        None => 0,
+        Some(ColorFormat::Colr) => 1,
+        Some(ColorFormat::Cbdt) => 2,
+        Some(ColorFormat::Sbix) => 3,
+        Some(ColorFormat::Svg) => 4,
+    });
+}
+
+fn read_entries(data: &[u8]) -> Option<HashMap<PathBuf, CacheEntry>> {
+    let mut r = Reader { data, pos: 0 };
+    if r.bytes(4)? != MAGIC || r.u32()? != CACHE_VERSION {
+        return None;
+    }
+    let count = r.u32()?;
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let path = PathBuf::from(r.str()?);
+        let stamp = FileStamp {
+            modified: (r.u64()?, r.u32()?),
+            len: r.u64()?,
+        };
+        let font_count = r.u32()?;
+        let mut fonts = Vec::new();
+        for _ in 0..font_count {
+            fonts.push(read_font(&mut r)?);
+        }
+        let mut rejected = Vec::new();
+        for _ in 0..r.u32()? {
+            rejected.push((r.u32()?, read_rejection(&mut r)?));
+        }
+        entries.insert(
+            path,
+            CacheEntry {
+                stamp,
+                fonts,
+                rejected,
+            },
+        );
+    }
+    Some(entries)
+}
+
+fn read_font(r: &mut Reader) -> Option<ScannedFont> {
+    let name = r.str()?.to_owned();
+    let mut localized_names = Vec::new();
+    for _ in 0..r.u32()? {
+        localized_names.push(r.str()?.to_owned());
+    }
+    let index = r.u32()?;
+    let attributes = Attributes(r.u32()?);
+    let mut scripts = HashSet::new();
+    for _ in 0..r.u32()? {
+        let script = script_from_index(r.u8()? as usize)?;
+        scripts.insert((script, read_cjk(r)?));
+    }
+    let mut verified_scripts = HashSet::new();
+    for _ in 0..r.u32()? {
+        verified_scripts.insert(script_from_index(r.u8()? as usize)?);
+    }
+    let capabilities = Capabilities(r.u8()?);
+    let metrics = if r.u8()? != 0 {
+        let use_typographic = r.u8()? != 0;
+        Some(FaceMetrics {
+            legacy: r.line_metrics()??,
+            typographic: r.line_metrics()?,
+            windows: r.line_metrics()?,
+            use_typographic,
+        })
+    } else {
+        None
+    };
+    let glyph_count = r.u16()?;
+    let file_size = r.u64()?;
+    let mut instances = Vec::new();
+    for _ in 0..r.u32()? {
+        let name = r.str()?.to_owned();
+        let mut coords = Vec::new();
+        for _ in 0..r.u32()? {
+            coords.push((r.u32()?, f32::from_bits(r.u32()?)));
+        }
+        instances.push(NamedInstance {
+            name,
+            coords,
+            attributes: Attributes(r.u32()?),
+        });
+    }
+    let mut axes = Vec::new();
+    for _ in 0..r.u32()? {
+        axes.push(VariationAxis {
+            tag: r.u32()?,
+            min: f32::from_bits(r.u32()?),
+            default: f32::from_bits(r.u32()?),
+            max: f32::from_bits(r.u32()?),
+        });
+    }
+    let optical_size = if r.u8()? != 0 {
+        Some((f32::from_bits(r.u32()?), f32::from_bits(r.u32()?)))
+    } else {
+        None
+    };
+    let cjk = read_cjk(r)?;
+    let color_format = match r.u8()? {
+        0 => None,
+        1 => Some(ColorFormat::Colr),
+        2 => Some(ColorFormat::Cbdt),
+        3 => Some(ColorFormat::Sbix),
+        4 => Some(ColorFormat::Svg),
+        _ => return None,
+    };
+    Some(ScannedFont {
+        lowercase_name: fold_name(&name).collect(),
+        name,
+        localized_names,
+        index,
+        attributes,
+        // Cache keys are only meaningful within a single process.
+        cache_key: CacheKey::new(),
+        scripts,
+        verified_scripts,
+        capabilities,
+        metrics,
+        glyph_count,
+        file_size,
+        // Priorities depend on the root being scanned and are assigned by
+        // the caller.
+        priority: SourcePriority::System,
+        instances: instances.into(),
+        axes: axes.into(),
+        optical_size,
+        cjk,
+        color_format,
+        // Only fonts scanned from system directories are cached.
+        namespaced: false,
+    })
+}
+
+// Files that cannot be read are not cached, so the error kind of an
+// unreadable file is not recorded.
+fn write_rejection(w: &mut Writer, reason: &FaceRejection) {
+    let (kind, tag) = match reason {
+        FaceRejection::Truncated => (0, [0; 4]),
+        FaceRejection::TableOutOfBounds(tag) => (1, *tag),
+        FaceRejection::MissingTable(tag) => (2, *tag),
+        FaceRejection::NoGlyphData => (3, [0; 4]),
+        FaceRejection::InvalidHead => (4, [0; 4]),
+        FaceRejection::InvalidCmap => (5, [0; 4]),
+        FaceRejection::Unreadable(_) => (6, [0; 4]),
+    };
+    w.u8(kind);
+    w.0.extend_from_slice(&tag);
+}
+
+fn read_rejection(r: &mut Reader) -> Option<FaceRejection> {
+    let kind = r.u8()?;
+    let tag = r.bytes(4)?.try_into().ok()?;
+    Some(match kind {
+        0 => FaceRejection::Truncated,
+        1 => FaceRejection::TableOutOfBounds(tag),
+        2 => FaceRejection::MissingTable(tag),
+        3 => FaceRejection::NoGlyphData,
+        4 => FaceRejection::InvalidHead,
+        5 => FaceRejection::InvalidCmap,
+        6 => FaceRejection::Unreadable(io::ErrorKind::Other),
+        _ => return None,
+    })
+}
+
+fn read_cjk(r: &mut Reader) -> Option<Cjk> {
+    Some(match r.u8()? {
+        0 => Cjk::None,
+        1 => Cjk::Traditional,
+        2 => Cjk::Simplified,
+        3 => Cjk::Japanese,
+        4 => Cjk::Korean,
+        _ => return None,
+    })
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn line_metrics(&mut self, metrics: Option<&LineMetrics>) {
+        match metrics {
+            Some(metrics) => {
+                self.u8(1);
+                self.u16(metrics.units_per_em);
+                self.u32(metrics.ascent as u32);
+                self.u32(metrics.descent as u32);
+                self.u32(metrics.line_gap as u32);
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        core::str::from_utf8(self.bytes(len)?).ok()
+    }
+
+    fn line_metrics(&mut self) -> Option<Option<LineMetrics>> {
+        if self.u8()? == 0 {
+            return Some(None);
+        }
+        Some(Some(LineMetrics {
+            units_per_em: self.u16()?,
+            ascent: self.u32()? as i32,
+            descent: self.u32()? as i32,
+            line_gap: self.u32()? as i32,
+        }))
+    }
+}
@@ -0,0 +1,155 @@
+//! Full Unicode case folding.
+//!
+//! Folding matches lowercasing for most characters, so only the characters
+//! from `CaseFolding.txt` whose full folding differs from
+//! [`char::to_lowercase`] are listed here.
+
+use std::char::ToLowercase;
+use std::str::Chars;
+
+/// Returns the full case folding of a character.
+pub fn fold(ch: char) -> CaseFold {
+    let c = ch as u32;
+    match c {
+        // Greek letters with ypogegrammeni and prosgegrammeni fold to the
+        // base letter followed by iota.
+        0x1F80..=0x1FAF => {
+            let base = [0x1F00, 0x1F20, 0x1F60][(c as usize - 0x1F80) / 16] + (c & 7);
+            CaseFold::Pair(char::from_u32(base), Some('\u{3b9}'))
+        }
+        // Cherokee folds to the uppercase letters.
+        0x13A0..=0x13F5 => CaseFold::Pair(Some(ch), None),
+        0x13F8..=0x13FD => CaseFold::Pair(char::from_u32(c - 8), None),
+        0xAB70..=0xABBF => CaseFold::Pair(char::from_u32(c - 0xAB70 + 0x13A0), None),
+        _ => match FOLDS.binary_search_by_key(&ch, |(ch, _)| *ch) {
+            Ok(index) => CaseFold::Table(FOLDS[index].1.chars()),
+            Err(_) => CaseFold::Lower(ch.to_lowercase()),
+        },
+    }
+}
+
+/// Iterator over the characters of a case folded character.
+pub enum CaseFold {
+    Lower(ToLowercase),
+    Table(Chars<'static>),
+    Pair(Option<char>, Option<char>),
+}
+
+impl Iterator for CaseFold {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match self {
+            Self::Lower(chars) => chars.next(),
+            Self::Table(chars) => chars.next(),
+            Self::Pair(first, second) => first.take().or_else(|| second.take()),
+        }
+    }
+}
+
+/// Folds that differ from lowercasing, sorted by character.
+const FOLDS: &[(char, &str)] = &[
+    ('\u{b5}', "\u{3bc}"),
+    ('\u{df}', "ss"),
+    ('\u{149}', "\u{2bc}n"),
+    ('\u{17f}', "s"),
+    ('\u{1f0}', "j\u{30c}"),
+    ('\u{345}', "\u{3b9}"),
+    ('\u{390}', "\u{3b9}\u{308}\u{301}"),
+    ('\u{3b0}', "\u{3c5}\u{308}\u{301}"),
+    ('\u{3c2}', "\u{3c3}"),
+    ('\u{3d0}', "\u{3b2}"),
+    ('\u{3d1}', "\u{3b8}"),
+    ('\u{3d5}', "\u{3c6}"),
+    ('\u{3d6}', "\u{3c0}"),
+    ('\u{3f0}', "\u{3ba}"),
+    ('\u{3f1}', "\u{3c1}"),
+    ('\u{3f5}', "\u{3b5}"),
+    ('\u{587}', "\u{565}\u{582}"),
+    ('\u{1c80}', "\u{432}"),
+    ('\u{1c81}', "\u{434}"),
+    ('\u{1c82}', "\u{43e}"),
+    ('\u{1c83}', "\u{441}"),
+    ('\u{1c84}', "\u{442}"),
+    ('\u{1c85}', "\u{442}"),
+    ('\u{1c86}', "\u{44a}"),
+    ('\u{1c87}', "\u{463}"),
+    ('\u{1c88}', "\u{a64b}"),
+    ('\u{1e96}', "h\u{331}"),
+    ('\u{1e97}', "t\u{308}"),
+    ('\u{1e98}', "w\u{30a}"),
+    ('\u{1e99}', "y\u{30a}"),
+    ('\u{1e9a}', "a\u{2be}"),
+    ('\u{1e9b}', "\u{1e61}"),
+    ('\u{1e9e}', "ss"),
+    ('\u{1f50}', "\u{3c5}\u{313}"),
+    ('\u{1f52}', "\u{3c5}\u{313}\u{300}"),
+    ('\u{1f54}', "\u{3c5}\u{313}\u{301}"),
+    ('\u{1f56}', "\u{3c5}\u{313}\u{342}"),
+    ('\u{1fb2}', "\u{1f70}\u{3b9}"),
+    ('\u{1fb3}', "\u{3b1}\u{3b9}"),
+    ('\u{1fb4}', "\u{3ac}\u{3b9}"),
+    ('\u{1fb6}', "\u{3b1}\u{342}"),
+    ('\u{1fb7}', "\u{3b1}\u{342}\u{3b9}"),
+    ('\u{1fbc}', "\u{3b1}\u{3b9}"),
+    ('\u{1fbe}', "\u{3b9}"),
+    ('\u{1fc2}', "\u{1f74}\u{3b9}"),
+    ('\u{1fc3}', "\u{3b7}\u{3b9}"),
+    ('\u{1fc4}', "\u{3ae}\u{3b9}"),
+    ('\u{1fc6}', "\u{3b7}\u{342}"),
+    ('\u{1fc7}', "\u{3b7}\u{342}\u{3b9}"),
+    ('\u{1fcc}', "\u{3b7}\u{3b9}"),
+    ('\u{1fd2}', "\u{3b9}\u{308}\u{300}"),
+    ('\u{1fd3}', "\u{3b9}\u{308}\u{301}"),
+    ('\u{1fd6}', "\u{3b9}\u{342}"),
+    ('\u{1fd7}', "\u{3b9}\u{308}\u{342}"),
+    ('\u{1fe2}', "\u{3c5}\u{308}\u{300}"),
+    ('\u{1fe3}', "\u{3c5}\u{308}\u{301}"),
+    ('\u{1fe4}', "\u{3c1}\u{313}"),
+    ('\u{1fe6}', "\u{3c5}\u{342}"),
+    ('\u{1fe7}', "\u{3c5}\u{308}\u{342}"),
+    ('\u{1ff2}', "\u{1f7c}\u{3b9}"),
+    ('\u{1ff3}', "\u{3c9}\u{3b9}"),
+    ('\u{1ff4}', "\u{3ce}\u{3b9}"),
+    ('\u{1ff6}', "\u{3c9}\u{342}"),
+    ('\u{1ff7}', "\u{3c9}\u{342}\u{3b9}"),
+    ('\u{1ffc}', "\u{3c9}\u{3b9}"),
+    ('\u{fb00}', "ff"),
+    ('\u{fb01}', "fi"),
+    ('\u{fb02}', "fl"),
+    ('\u{fb03}', "ffi"),
+    ('\u{fb04}', "ffl"),
+    ('\u{fb05}', "st"),
+    ('\u{fb06}', "st"),
+    ('\u{fb13}', "\u{574}\u{576}"),
+    ('\u{fb14}', "\u{574}\u{565}"),
+    ('\u{fb15}', "\u{574}\u{56b}"),
+    ('\u{fb16}', "\u{57e}\u{576}"),
+    ('\u{fb17}', "\u{574}\u{56d}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold_str(s: &str) -> String {
+        s.chars().flat_map(fold).collect()
+    }
+
+    #[test]
+    fn table_is_sorted() {
+        assert!(FOLDS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn folds_special_cases() {
+        assert_eq!(fold_str("Straße"), "strasse");
+        assert_eq!(fold_str("STRAẞE"), "strasse");
+        assert_eq!(fold_str("ΟΔΟΣ"), fold_str("οδος"));
+        assert_eq!(fold_str("ὀδός"), "ὀδόσ");
+        assert_eq!(fold_str("\u{fb01}ne"), "fine");
+        assert_eq!(fold_str("\u{1f88}"), "\u{1f00}\u{3b9}");
+        assert_eq!(fold_str("\u{ab70}"), "\u{13a0}");
+        assert_eq!(fold_str("\u{13a0}"), "\u{13a0}");
+    }
+}
@@ -0,0 +1,261 @@
+//! Font configuration files describing default, generic and fallback
+//! families.
+
+use super::script_tags::script_from_name;
+use super::{GenericFamily, LanguageTag, LibraryBuilder};
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use swash::text::{Cjk, Script};
+
+/// Family configuration that can be read from a TOML or JSON file and
+/// applied to a [`LibraryBuilder`].
+///
+/// This lets end users adjust font selection without rebuilding the
+/// application. Every field is optional. In TOML:
+///
+/// ```toml
+/// default-families = ["Inter", "Noto Sans"]
+///
+/// [generic-families]
+/// monospace = ["JetBrains Mono", "DejaVu Sans Mono"]
+///
+/// [aliases]
+/// Helvetica = ["Liberation Sans"]
+///
+/// [fallbacks]
+/// Arab = ["Vazirmatn", "Noto Sans Arabic"]
+///
+/// [cjk]
+/// ja = ["Noto Sans CJK JP"]
+/// ```
+#[derive(Clone, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct FontConfig {
+    /// Default families, in order of preference. See
+    /// [`LibraryBuilder::default_families`].
+    pub default_families: Vec<String>,
+    /// Families keyed by generic family keyword, such as "monospace". See
+    /// [`LibraryBuilder::generic`].
+    pub generic_families: BTreeMap<String, Vec<String>>,
+    /// Families keyed by the name that resolves to them. See
+    /// [`LibraryBuilder::alias`].
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Fallback chains keyed by ISO 15924 script tag, such as "Arab", or
+    /// script name. See [`LibraryBuilder::fallback`].
+    pub fallbacks: BTreeMap<String, Vec<String>>,
+    /// Fallback chains for Han text keyed by a language tag that selects a
+    /// CJK locale, such as "ja", "ko", "zh-Hans" or "zh-Hant". See
+    /// [`LibraryBuilder::cjk`].
+    pub cjk: BTreeMap<String, Vec<String>>,
+}
+
+impl FontConfig {
+    /// Reads a configuration file. Files with a `json` extension are parsed
+    /// as JSON and all others as TOML.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    /// Parses a configuration in TOML format.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|e| ConfigError::Syntax(e.to_string()))
+    }
+
+    /// Parses a configuration in JSON format.
+    pub fn from_json(text: &str) -> Result<Self, ConfigError> {
+        serde_json::from_str(text).map_err(|e| ConfigError::Syntax(e.to_string()))
+    }
+}
+
+/// Error returned when a font configuration cannot be read or applied.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file is not valid TOML or JSON, or does not match the format of
+    /// [`FontConfig`].
+    Syntax(String),
+    /// A key of the generic families is not a generic family keyword.
+    UnknownGeneric(String),
+    /// A key of the fallbacks does not name a script.
+    UnknownScript(String),
+    /// A key of the CJK fallbacks does not select a CJK locale.
+    UnknownLocale(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "failed to read the font configuration: {}", error),
+            Self::Syntax(message) => write!(f, "invalid font configuration: {}", message),
+            Self::UnknownGeneric(key) => write!(f, "unknown generic family \"{}\"", key),
+            Self::UnknownScript(key) => write!(f, "unknown script \"{}\"", key),
+            Self::UnknownLocale(key) => write!(f, "\"{}\" does not select a CJK locale", key),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl LibraryBuilder {
+    /// Applies a font configuration.
+    ///
+    /// Entries replace those set earlier for the same generic family, alias,
+    /// script or locale, and the default families are replaced if the
+    /// configuration lists any. The configuration is validated before any
+    /// of it is applied, so the builder is unchanged on error.
+    pub fn config(&mut self, config: &FontConfig) -> Result<&mut Self, ConfigError> {
+        let generics = config
+            .generic_families
+            .iter()
+            .map(|(key, names)| {
+                let generic = key
+                    .parse::<GenericFamily>()
+                    .map_err(|_| ConfigError::UnknownGeneric(key.clone()))?;
+                Ok((generic, names))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut fallbacks = config
+            .fallbacks
+            .iter()
+            .map(|(key, names)| {
+                let script = script_from_name(key.trim())
+                    .ok_or_else(|| ConfigError::UnknownScript(key.clone()))?;
+                Ok((script, Cjk::None, names))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, names) in &config.cjk {
+            let cjk = LanguageTag::parse(key)
+                .map(|tag| tag.cjk())
+                .filter(|cjk| *cjk != Cjk::None)
+                .ok_or_else(|| ConfigError::UnknownLocale(key.clone()))?;
+            fallbacks.push((Script::Han, cjk, names));
+        }
+        if !config.default_families.is_empty() {
+            self.default_families(&name_refs(&config.default_families));
+        }
+        for (generic, names) in generics {
+            self.generic(generic, &name_refs(names));
+        }
+        for (alias, names) in &config.aliases {
+            self.alias(alias, &name_refs(names));
+        }
+        for (script, cjk, names) in fallbacks {
+            if script == Script::Han {
+                self.cjk(cjk, &name_refs(names));
+            } else {
+                self.fallback(script, &name_refs(names));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Reads a configuration file with [`FontConfig::load`] and applies it
+    /// with [`config`](Self::config).
+    pub fn load_config(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, ConfigError> {
+        self.config(&FontConfig::load(path)?)
+    }
+}
+
+fn name_refs(names: &[String]) -> Vec<&str> {
+    names.iter().map(|name| name.as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+default-families = ["Inter", "Noto Sans"]
+
+[generic-families]
+monospace = ["JetBrains Mono"]
+
+[fallbacks]
+Arab = ["Vazirmatn"]
+
+[cjk]
+ja = ["Noto Sans CJK JP"]
+"#;
+
+    const JSON: &str = r#"{
+        "default-families": ["Inter", "Noto Sans"],
+        "generic-families": { "monospace": ["JetBrains Mono"] },
+        "fallbacks": { "Arab": ["Vazirmatn"] },
+        "cjk": { "ja": ["Noto Sans CJK JP"] }
+    }"#;
+
+    #[test]
+    fn parses_toml_and_json() {
+        let config = FontConfig::from_toml(TOML).unwrap();
+        assert_eq!(config.default_families, ["Inter", "Noto Sans"]);
+        assert_eq!(config.generic_families["monospace"], ["JetBrains Mono"]);
+        assert!(config.aliases.is_empty());
+        assert_eq!(FontConfig::from_json(JSON).unwrap(), config);
+        assert!(matches!(
+            FontConfig::from_toml("default-families = 1"),
+            Err(ConfigError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn loads_by_extension() {
+        let dir = std::env::temp_dir().join(format!("fount-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("fonts.JSON");
+        let toml = dir.join("fonts.toml");
+        std::fs::write(&json, JSON).unwrap();
+        std::fs::write(&toml, TOML).unwrap();
+        let loaded = (FontConfig::load(&json), FontConfig::load(&toml));
+        let missing = FontConfig::load(dir.join("missing.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.0.unwrap(), loaded.1.unwrap());
+        assert!(matches!(missing, Err(ConfigError::Io(_))));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let mut builder = LibraryBuilder::headless();
+        let mut config = FontConfig::default();
+        config
+            .generic_families
+            .insert("sans".into(), vec!["Inter".into()]);
+        assert!(matches!(
+            builder.config(&config),
+            Err(ConfigError::UnknownGeneric(key)) if key == "sans"
+        ));
+        let mut config = FontConfig::default();
+        config.fallbacks.insert("Xyzw".into(), vec!["Inter".into()]);
+        assert!(matches!(
+            builder.config(&config),
+            Err(ConfigError::UnknownScript(key)) if key == "Xyzw"
+        ));
+        let mut config = FontConfig::default();
+        config.cjk.insert("fr".into(), vec!["Inter".into()]);
+        assert!(matches!(
+            builder.config(&config),
+            Err(ConfigError::UnknownLocale(key)) if key == "fr"
+        ));
+        assert!(builder
+            .config(&FontConfig::from_toml(TOML).unwrap())
+            .is_ok());
+    }
+}
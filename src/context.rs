@@ -2,29 +2,63 @@ use super::data::*;
 use super::font::FontData;
 use super::id::*;
 use super::library::*;
+use super::scan::{collect_files, scan_files, ScannedFont};
 use super::*;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::RwLock;
-use swash::text::Script;
+use swash::text::{Cjk, Codepoint as _, Script};
+use swash::{CacheKey, FontRef, StringId};
 
 /// Interface to a font library providing enumeration, queries and fallbacks.
+///
+/// Contexts are cheap to create and clone. The user font collection is
+/// shared with the library through a reference counted snapshot, so cloning a
+/// context or synchronizing it with newly registered fonts never copies the
+/// collection itself. This makes it practical to create short lived contexts,
+/// for example once per frame.
 #[derive(Clone)]
 pub struct FontContext {
     library: Library,
-    user: RefCell<Arc<(u64, CollectionData)>>,
+    user: RefCell<(u64, Arc<CollectionData>)>,
+    script_preferences: HashMap<Script, FamilyId>,
+    /// Generic families that resolve to the default families in this
+    /// context, as a bit set indexed by discriminant.
+    disabled_generics: u32,
+    scorer: Option<Arc<dyn MatchScorer>>,
+    trust_policy: Option<Arc<dyn TrustPolicy>>,
+    resolvers: Vec<Arc<dyn Resolver>>,
+    scratch: RefCell<Vec<FamilyId>>,
 }
 
 impl FontContext {
     /// Creates a new font context for the associated font library.
     pub fn new(library: &Library) -> Self {
-        let library_user = library.inner.user.borrow();
         let user_version = library.inner.user_version.load(Ordering::Relaxed);
-        let user = RefCell::new(Arc::new((user_version, library_user.clone())));
+        let user = library.inner.user.read().unwrap().clone();
+        let user = RefCell::new((user_version, user));
         Self {
             library: library.clone(),
             user,
+            script_preferences: library
+                .inner
+                .script_preferences
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+            disabled_generics: 0,
+            scorer: None,
+            trust_policy: None,
+            resolvers: vec![
+                Arc::new(GenericFamilyResolver),
+                Arc::new(UserFontResolver),
+                Arc::new(SystemFontResolver),
+                Arc::new(LastResortResolver),
+            ],
+            scratch: RefCell::default(),
         }
     }
 
@@ -39,10 +73,21 @@ impl FontContext {
     //     self.library.inner.system.read().unwrap().source_paths()
     // }
 
+    /// Returns the generation of the library observed by the context.
+    ///
+    /// This is the same counter returned by
+    /// [`Library::generation`](super::Library::generation) and refreshes the
+    /// view of registered fonts held by the context.
+    pub fn generation(&self) -> u64 {
+        self.sync_user();
+        self.user.borrow().0
+    }
+
     /// Returns an iterator over the font families in the context.
     pub fn families(&self) -> Families {
+        self.sync_user();
         Families {
-            user: self.user.borrow().clone(),
+            user: self.user.borrow().1.clone(),
             library: self.library.clone(),
             pos: 0,
             stage: 0,
@@ -50,113 +95,1772 @@ impl FontContext {
     }
 
     /// Returns the font family entry for the specified identifier.
+    ///
+    /// Placeholders created with
+    /// [`Library::add_placeholder`](super::Library::add_placeholder) resolve
+    /// to their substitute family.
     pub fn family(&self, id: FamilyId) -> Option<FamilyEntry> {
-        if id.is_user_font() {
+        if id.is_placeholder() {
+            return self.placeholder_substitute(id);
+        }
+        let family = if id.is_user_font() {
             self.sync_user();
-            self.user.borrow().1.family(id)
+            self.user.borrow().1.family(id)?
         } else {
-            self.library.inner.system.borrow().family(id)
+            let family = self.library.inner.system().family(id)?;
+            self.refine(family)
+        };
+        self.allows_family(family)
+    }
+
+    /// Invokes a closure with a borrowed view of the font family for the
+    /// specified identifier and returns its result.
+    ///
+    /// This avoids the reference count update and allocation of
+    /// [`family`](Self::family) in hot paths and for large families. The
+    /// closure runs while the collection holding the family is borrowed,
+    /// under the read lock of the library unless it is
+    /// [frozen](super::Library::freeze), so it must not call back into the
+    /// context or the library.
+    pub fn with_family<R>(&self, id: FamilyId, f: impl FnOnce(FamilyRef) -> R) -> Option<R> {
+        // Filtering by the trust policy requires the font entries and
+        // placeholders resolve through the resolvers, so both use an owned
+        // entry.
+        if self.trust_policy.is_some() || id.is_placeholder() {
+            return self.family(id).map(|family| f(family.as_family_ref()));
         }
+        if id.is_user_font() {
+            self.sync_user();
+            return self.user.borrow().1.family_ref(id).map(f);
+        }
+        // Refinement writes to the library, so it happens before the lock
+        // is held for the closure.
+        if !self.library.is_frozen() && self.library.inner.system().needs_refinement(id) {
+            let family = self.library.inner.system().family(id)?;
+            self.refine(family);
+        }
+        let system = self.library.inner.system();
+        system.family_ref(id).map(f)
+    }
+
+    /// Hides families without any fonts allowed by the trust policy.
+    fn allows_family(&self, family: FamilyEntry) -> Option<FamilyEntry> {
+        if self.trust_policy.is_some() && family.fonts().all(|id| self.font(id).is_none()) {
+            return None;
+        }
+        Some(family)
     }
 
     /// Returns the font family entry for the specified name.
+    ///
+    /// The name is passed to each [resolver](Self::resolvers) in turn and
+    /// the first family that is allowed by the trust policy is returned.
+    /// By default, generic family keywords such as "monospace" resolve to
+    /// the first family of the generic family. See
+    /// [`GenericFamilyResolver`].
     pub fn family_by_name<'a>(&'a self, name: &str) -> Option<FamilyEntry> {
+        self.resolvers.iter().find_map(|resolver| {
+            let id = resolver.resolve_family(self, name)?;
+            self.family(id)
+        })
+    }
+
+    /// Resolves a placeholder to the family with the requested name if it
+    /// is available, or else to the first default family.
+    fn placeholder_substitute(&self, id: FamilyId) -> Option<FamilyEntry> {
+        let missing = self.library.missing_font(id)?;
+        // Resolvers may map the name to a placeholder, which must not
+        // resolve to itself.
+        let named = self
+            .resolvers
+            .iter()
+            .filter_map(|resolver| resolver.resolve_family(self, &missing.name))
+            .find(|id| !id.is_placeholder());
+        named
+            .into_iter()
+            .chain(self.default_families())
+            .find_map(|id| self.family(id))
+    }
+
+    /// Resolves a family name in the user collection.
+    pub(crate) fn user_family_id(&self, name: &str) -> Option<FamilyId> {
         self.sync_user();
-        self.library.inner.system.borrow_mut().family_by_name(name)
+        self.user.borrow().1.find_family_id(name)
+    }
+
+    /// Resolves a family name among the families that are already known,
+    /// without consulting the platform.
+    pub(crate) fn known_family_id(&self, name: &str) -> Option<FamilyId> {
+        self.user_family_id(name).or_else(|| {
+            self.library
+                .inner
+                .system()
+                .find_family(name)
+                .map(|family| family.id())
+        })
+    }
+
+    /// Resolves a family name in the system collection, locating the
+    /// family through the platform if it has not been used yet.
+    pub(crate) fn system_family_id(&self, name: &str) -> Option<FamilyId> {
+        // Families that have already been resolved are found under the read
+        // lock so that repeated queries do not contend with each other.
+        let known = self.library.inner.system().find_family(name);
+        match known {
+            Some(family) => Some(family.id()),
+            None => match self.library.inner.system_mut() {
+                Some(mut system) => Some(system.family_by_name(name)?.id()),
+                // Frozen libraries only resolve families that are already known.
+                None => None,
+            },
+        }
+    }
+
+    /// Returns the resolvers consulted by the context, in order.
+    pub fn resolvers(&self) -> &[Arc<dyn Resolver>] {
+        &self.resolvers
+    }
+
+    /// Inserts a resolver at the specified position in the stack for this
+    /// context only.
+    ///
+    /// Position zero places the resolver ahead of all others, which is the
+    /// usual place for an [`OverrideResolver`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of resolvers.
+    pub fn insert_resolver(&mut self, index: usize, resolver: Arc<dyn Resolver>) {
+        self.resolvers.insert(index, resolver);
+    }
+
+    /// Replaces the resolvers consulted by the context. Removing
+    /// [`SystemFontResolver`] hides system fonts from name lookups,
+    /// removing [`GenericFamilyResolver`] makes generic family keywords
+    /// plain family names and removing [`LastResortResolver`] makes matching
+    /// fail when no requested family is available.
+    pub fn set_resolvers(&mut self, resolvers: Vec<Arc<dyn Resolver>>) {
+        self.resolvers = resolvers;
+    }
+
+    /// Refines the attributes of the fonts in a scanned family the first
+    /// time it is requested.
+    ///
+    /// Scanning only records the attributes reported by the OS/2 table. Each
+    /// face is loaded here to compute more precise values for matching and
+    /// the result is cached in the library. Sources are loaded once each and
+    /// before the library is locked for writing, so other threads are only
+    /// blocked while the refined attributes are stored.
+    fn refine(&self, family: FamilyEntry) -> FamilyEntry {
+        let fonts = match &family.kind {
+            FontFamilyKind::Dynamic(data)
+                if !data.refined && !family.id.is_user_font() && !self.library.is_frozen() =>
+            {
+                data.fonts.clone()
+            }
+            _ => return family,
+        };
+        let mut loaded: Vec<(SourceId, Option<FontData>)> = Vec::new();
+        let mut refine_font = |id: FontId| {
+            let font = self.font(id)?;
+            let data = match loaded.iter().find(|(source, _)| *source == font.source()) {
+                Some((_, data)) => data.clone(),
+                None => {
+                    let data = self.load(font.source());
+                    loaded.push((font.source(), data.clone()));
+                    data
+                }
+            }?;
+            let font_ref = FontRef::from_index(&data, font.index() as usize)?;
+            Some(super::scan::refine_attributes(&font_ref))
+        };
+        let refined = fonts
+            .iter()
+            .map(|&(id, stretch, weight, style)| {
+                let (stretch, weight, style) = refine_font(id).unwrap_or((stretch, weight, style));
+                (id, stretch, weight, style)
+            })
+            .collect::<Vec<_>>();
+        let mut system = match self.library.inner.system_mut() {
+            Some(system) => system,
+            None => return family,
+        };
+        system.refine_family(family.id, &refined);
+        system.family(family.id).unwrap_or(family)
     }
 
     /// Returns the font entry for the specified identifier.
     pub fn font(&self, id: FontId) -> Option<FontEntry> {
-        if id.is_user_font() {
+        let font = if id.is_user_font() {
             self.sync_user();
-            self.user.borrow().1.font(id)
+            self.user.borrow().1.font(id)?
         } else {
-            self.library.inner.system.borrow().font(id)
+            self.library.inner.system().font(id)?
+        };
+        if !self.allows(font.source(), font.trust()) {
+            return None;
         }
+        Some(font)
+    }
+
+    /// Returns the identifier of the font with the specified cache key.
+    ///
+    /// This maps keys retained by glyph and shaping caches back to the
+    /// fonts of the library, for example to reload a font or to report
+    /// which font an entry belongs to. Fonts receive a new cache key when
+    /// their file changes on disk, after which the old key is no longer
+    /// found.
+    pub fn font_by_cache_key(&self, key: CacheKey) -> Option<FontId> {
+        self.sync_user();
+        let id = match self.user.borrow().1.font_by_cache_key(key) {
+            Some(id) => id,
+            None => self.library.inner.system().font_by_cache_key(key)?,
+        };
+        self.font(id).map(|font| font.id())
     }
 
     /// Returns the font source entry for the specified identifier.
     pub fn source(&self, id: SourceId) -> Option<SourceEntry> {
-        if id.is_user_font() {
+        let source = if id.is_user_font() {
             self.sync_user();
-            self.user.borrow().1.source(id)
+            self.user.borrow().1.source(id)?
+        } else {
+            self.library.inner.system().source(id)?
+        };
+        if !self.allows(id, source.trust()) {
+            return None;
+        }
+        Some(source)
+    }
+
+    /// Returns true if the trust policy of the context allows the source.
+    fn allows(&self, source: SourceId, trust: SourceTrust) -> bool {
+        match &self.trust_policy {
+            Some(policy) => policy.allows(source, trust),
+            None => true,
+        }
+    }
+
+    /// Returns a handle to the specified font if its data is already
+    /// resident, without performing IO or waiting for a lock.
+    ///
+    /// This is intended for realtime threads, such as those adjacent to
+    /// audio processing, that must never block. If the font has not been
+    /// loaded, its data has been released or the library is being modified
+    /// by another thread, `None` is returned and loading can be deferred to
+    /// another thread with [`load`](Self::load). The context does not
+    /// observe fonts registered since it last synchronized with the
+    /// library.
+    pub fn try_get_loaded(&self, id: FontId) -> Option<FontHandle> {
+        let (font, data) = if id.is_user_font() {
+            let user = self.user.try_borrow().ok()?;
+            let font = user.1.font(id)?;
+            let data = user.1.loaded(font.source())?;
+            (font, data)
         } else {
-            self.library.inner.system.borrow().source(id)
+            let system = self.library.inner.try_system()?;
+            let font = system.font(id)?;
+            let data = system.loaded(font.source())?;
+            (font, data)
+        };
+        if !self.allows(font.source(), font.trust()) {
+            return None;
         }
+        Some(FontHandle {
+            data,
+            index: font.index(),
+            cache_key: font.cache_key(),
+        })
     }
 
     /// Loads the font data for the specified source.
+    ///
+    /// Use [`try_load`](Self::try_load) to determine why loading failed.
     pub fn load(&self, id: SourceId) -> Option<FontData> {
+        self.try_load(id).ok()
+    }
+
+    /// Loads the font data for the specified source, returning the reason
+    /// on failure.
+    ///
+    /// Failures are remembered, so a source that could not be loaded
+    /// reports the original error until it is rescanned.
+    pub fn try_load(&self, id: SourceId) -> Result<FontData, LoadError> {
+        if self.trust_policy.is_some() {
+            let trust = if id.is_user_font() {
+                self.sync_user();
+                self.user.borrow().1.source_trust(id)
+            } else {
+                self.library.inner.system().source_trust(id)
+            };
+            if !self.allows(id, trust.ok_or(LoadError::NotFound)?) {
+                return Err(LoadError::Denied);
+            }
+        }
         if id.is_user_font() {
             self.sync_user();
             self.user.borrow().1.load(id)
         } else {
-            self.library.inner.system.borrow().load(id)
+            let (data, changed) = self.library.inner.system().load_checked(id)?;
+            if changed {
+                self.refresh_source(id, &data);
+            }
+            Ok(data)
         }
     }
 
+    /// Rescans a source whose backing file has changed and notifies caches
+    /// by advancing the library generation.
+    ///
+    /// A frozen library cannot be updated, but the generation is still
+    /// advanced so that caches holding data from the old file are discarded.
+    fn refresh_source(&self, id: SourceId, data: &FontData) {
+        let scanners = self.library.inner.system().scanners();
+        let fonts = scanners.get().scan_all(data);
+        let diff = match self.library.inner.system_mut() {
+            Some(mut system) => system.refresh_source(id, &fonts),
+            None => CollectionDiff::default(),
+        };
+        self.library.inner.names.clear();
+        self.library.inner.bump_generation(diff);
+    }
+
     /// Returns an ordered sequence of font family identifers that represent
     /// the default font families.
     pub fn default_families(&self) -> Vec<FamilyId> {
-        let system = self.library.inner.system.borrow();
+        let system = self.library.inner.system();
         system.default_families().to_vec()
     }
 
     /// Returns an ordered sequence of font family identifers that represent the
     /// specified generic font family.
     pub fn generic_families(&self, family: GenericFamily) -> Vec<FamilyId> {
-        self.library
-            .inner
-            .system
-            .borrow()
-            .generic_families(family)
-            .to_vec()
+        let system = self.library.inner.system();
+        self.generic_slice(&system, family).to_vec()
+    }
+
+    /// Returns the families for a generic family, substituting the default
+    /// families if the generic family is disabled in this context.
+    fn generic_slice<'a>(
+        &self,
+        system: &'a SystemCollectionData,
+        family: GenericFamily,
+    ) -> &'a [FamilyId] {
+        if self.is_generic_disabled(family) {
+            system.default_families()
+        } else {
+            system.generic_families(family)
+        }
+    }
+
+    /// Returns the families selected for every generic family, in the order
+    /// of [`GenericFamily::ALL`].
+    pub fn all_generic_mappings(&self) -> Vec<(GenericFamily, Vec<FamilyId>)> {
+        let system = self.library.inner.system();
+        GenericFamily::ALL
+            .iter()
+            .map(|generic| (*generic, self.generic_slice(&system, *generic).to_vec()))
+            .collect()
+    }
+
+    /// Returns families with names close to a name that could not be
+    /// resolved.
+    ///
+    /// This allows applications to respond to a configuration referencing a
+    /// missing or misspelled family with a message such as "did you mean
+    /// 'Fira Code'?". Names are compared ignoring case, spaces and hyphens.
+    /// Families whose names begin with the requested name are listed first,
+    /// followed by the others in order of increasing edit distance. Families
+    /// known to the platform that have not been loaded are considered and
+    /// loaded if suggested. At most `limit` families are returned.
+    pub fn suggest_families(&self, name: &str, limit: usize) -> Vec<ResolvedFamily> {
+        let key = suggestion_key(name);
+        if key.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+        let mut names = self
+            .families()
+            .map(|family| family.name().to_owned())
+            .collect::<Vec<_>>();
+        names.extend(self.library.inner.system().platform_family_names());
+        let max_distance = (key.chars().count() / 3).max(2);
+        let mut candidates = names
+            .into_iter()
+            .filter_map(|candidate| {
+                let candidate_key = suggestion_key(&candidate);
+                let rank = if candidate_key.starts_with(&key) {
+                    0
+                } else {
+                    match edit_distance(&key, &candidate_key) {
+                        distance if distance <= max_distance => distance,
+                        _ => return None,
+                    }
+                };
+                Some((rank, candidate_key, candidate))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.dedup_by(|a, b| a.1 == b.1);
+        let mut families = Vec::new();
+        for (_, _, candidate) in candidates {
+            if families.len() == limit {
+                break;
+            }
+            if let Some(family) = self.family_by_name(&candidate) {
+                if families
+                    .iter()
+                    .all(|f: &ResolvedFamily| f.id != family.id())
+                {
+                    families.push(ResolvedFamily {
+                        id: family.id(),
+                        name: family.name().to_owned(),
+                    });
+                }
+            }
+        }
+        families
+    }
+
+    /// Parses the value of a CSS `font-family` property and returns the
+    /// families it resolves to in order of priority.
+    ///
+    /// Named families that are not available are skipped and generic
+    /// families expand to the families selected for them. Each family
+    /// appears once. See [`FamilyKey::parse_list`].
+    pub fn resolve_family_list(&self, value: &str) -> Vec<FamilyId> {
+        let mut families = Vec::new();
+        for key in FamilyKey::parse_list(value) {
+            let ids = match key {
+                FamilyKey::Named(name) => self
+                    .family_by_name(&name)
+                    .map(|family| vec![family.id()])
+                    .unwrap_or_default(),
+                FamilyKey::Generic(generic) => self.generic_families(generic),
+                FamilyKey::Id(id) => vec![id],
+            };
+            for id in ids {
+                if !families.contains(&id) {
+                    families.push(id);
+                }
+            }
+        }
+        families
+    }
+
+    /// Returns the families selected for each generic family.
+    ///
+    /// This is intended for diagnostics, such as a page describing which
+    /// fonts the library selected on a system for inclusion in bug reports.
+    pub fn generic_table(&self) -> Vec<(GenericFamily, Vec<ResolvedFamily>)> {
+        GenericFamily::ALL
+            .iter()
+            .map(|generic| {
+                let families = self.resolve_families(&self.generic_families(*generic));
+                (*generic, families)
+            })
+            .collect()
+    }
+
+    /// Returns the families in the fallback chain for Han characters for
+    /// each CJK locale bucket. See [`generic_table`](Self::generic_table).
+    pub fn cjk_table(&self) -> Vec<(Cjk, Vec<ResolvedFamily>)> {
+        [
+            Cjk::None,
+            Cjk::Traditional,
+            Cjk::Simplified,
+            Cjk::Japanese,
+            Cjk::Korean,
+        ]
+        .iter()
+        .map(|cjk| {
+            self.library.inner.resolve_fallbacks(Script::Han, *cjk);
+            let ids = self.library.inner.system().cjk_families(*cjk).to_vec();
+            (*cjk, self.resolve_families(&ids))
+        })
+        .collect()
+    }
+
+    fn resolve_families(&self, ids: &[FamilyId]) -> Vec<ResolvedFamily> {
+        ids.iter()
+            .filter_map(|id| {
+                Some(ResolvedFamily {
+                    id: *id,
+                    name: self.with_family(*id, |family| family.name().to_owned())?,
+                })
+            })
+            .collect()
     }
 
     /// Returns an ordered sequence of font family identifers that represent the
     /// fallback chain for the specified script and locale.
+    ///
+    /// The list is empty for scripts without a fallback chain. Queries
+    /// consult the default families after the chain in either case.
     pub fn fallback_families(&self, script: Script, locale: Option<Locale>) -> Vec<FamilyId> {
+        let mut families = Vec::new();
+        self.resolve_fallbacks(script, locale);
+        self.fill_fallback(&self.library.inner.system(), script, locale, &mut families);
+        families
+    }
+
+    /// Queries the platform for the fallback chain of the script and locale
+    /// before it is first used.
+    fn resolve_fallbacks(&self, script: Script, locale: Option<Locale>) {
+        self.library
+            .inner
+            .resolve_fallbacks(script, fallback_cjk(script, locale));
+    }
+
+    /// Fills an empty buffer with the fallback chain for the script and
+    /// locale, applying the script preferences of the context.
+    fn fill_fallback(
+        &self,
+        system: &SystemCollectionData,
+        script: Script,
+        locale: Option<Locale>,
+        families: &mut Vec<FamilyId>,
+    ) {
+        families.extend_from_slice(system.fallback_families(script, locale));
+        // Fonts registered over a static collection extend its chains.
+        self.sync_user();
+        families.extend_from_slice(self.user.borrow().1.fallback_families(script, locale));
+        if let Some(preferred) = self.script_preferences.get(&script) {
+            families.retain(|id| id != preferred);
+            families.insert(0, *preferred);
+        }
+    }
+
+    /// Fills a buffer owned by the context with family identifiers and
+    /// calls the closure with the result.
+    ///
+    /// Queries are issued at a high rate by text layout, so the buffer is
+    /// reused to avoid allocating once it has grown to fit the longest
+    /// chain. The system collection is only locked while filling.
+    fn with_scratch<R>(
+        &self,
+        fill: impl FnOnce(&SystemCollectionData, &mut Vec<FamilyId>),
+        f: impl FnOnce(&mut Vec<FamilyId>) -> R,
+    ) -> R {
+        // The buffer is taken rather than borrowed so that a nested query
+        // made by the closure falls back to a fresh buffer.
+        let mut families = self.scratch.take();
+        families.clear();
+        fill(&self.library.inner.system(), &mut families);
+        let result = f(&mut families);
+        self.scratch.replace(families);
+        result
+    }
+
+    /// Calls the closure with the fallback chain for the script and locale
+    /// followed by the default families.
+    fn with_chain<R>(
+        &self,
+        script: Script,
+        locale: Option<Locale>,
+        f: impl FnOnce(&mut Vec<FamilyId>) -> R,
+    ) -> R {
+        self.resolve_fallbacks(script, locale);
+        self.with_scratch(
+            |system, families| {
+                self.fill_fallback(system, script, locale, families);
+                families.extend_from_slice(system.default_families());
+            },
+            f,
+        )
+    }
+
+    /// Calls the closure with the fallback chain for a character. This is
+    /// the chain of [`with_chain`](Self::with_chain) preceded by the emoji
+    /// generic family for characters that default to emoji presentation.
+    fn with_char_chain<R>(
+        &self,
+        ch: char,
+        script: Script,
+        locale: Option<Locale>,
+        f: impl FnOnce(&mut Vec<FamilyId>) -> R,
+    ) -> R {
+        self.resolve_fallbacks(script, locale);
+        self.with_scratch(
+            |system, families| {
+                self.fill_fallback(system, script, locale, families);
+                if is_emoji_presentation(ch) && !self.is_generic_disabled(GenericFamily::Emoji) {
+                    let emoji = system.generic_families(GenericFamily::Emoji);
+                    families.splice(0..0, emoji.iter().copied());
+                }
+                families.extend_from_slice(system.default_families());
+            },
+            f,
+        )
+    }
+
+    /// Returns the fallback chain of each script that has one.
+    ///
+    /// This exposes the effective fallback configuration, including the
+    /// script preferences of the context, so that tools can display it and
+    /// let users edit it. Han is reported with the chain used when no locale
+    /// is specified. See [`cjk_table`](Self::cjk_table) for the chains of
+    /// the individual CJK locales.
+    pub fn script_fallbacks(&self) -> impl Iterator<Item = (Script, Vec<FamilyId>)> + '_ {
+        (0..)
+            .map_while(crate::script_tags::script_from_index)
+            .filter(move |script| {
+                self.script_preferences.contains_key(script)
+                    || self.library.inner.system().has_script_fallbacks(*script)
+            })
+            .map(move |script| (script, self.fallback_families(script, None)))
+    }
+
+    /// Returns a fallback chain for the specified script that serves text in
+    /// several locales at once.
+    ///
+    /// The chains for the individual locales are interleaved so that the
+    /// preferred family of each locale appears before the secondary choices
+    /// of any locale. Earlier locales take precedence at each position and
+    /// duplicate families are removed. An empty list of locales produces the
+    /// chain for the script alone.
+    pub fn fallback_families_multi(&self, script: Script, locales: &[Locale]) -> Vec<FamilyId> {
+        if locales.is_empty() {
+            return self.fallback_families(script, None);
+        }
+        let chains = locales
+            .iter()
+            .map(|locale| self.fallback_families(script, Some(*locale)))
+            .collect::<Vec<_>>();
+        let len = chains.iter().map(|chain| chain.len()).max().unwrap_or(0);
+        let mut families = Vec::new();
+        for i in 0..len {
+            for chain in &chains {
+                if let Some(id) = chain.get(i) {
+                    if !families.contains(id) {
+                        families.push(*id);
+                    }
+                }
+            }
+        }
+        families
+    }
+
+    /// Returns the fallback chain for the specified script and locale along
+    /// with the reason each family was selected.
+    ///
+    /// This allows quality sensitive consumers to skip candidates whose
+    /// coverage of the script has not been verified.
+    pub fn fallback_candidates(
+        &self,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Vec<FallbackCandidate> {
+        self.resolve_fallbacks(script, locale);
+        let mut candidates = self
+            .library
+            .inner
+            .system()
+            .fallback_candidates(script, locale);
+        if let Some(preferred) = self.script_preferences.get(&script) {
+            candidates.retain(|candidate| candidate.family != *preferred);
+            candidates.insert(
+                0,
+                FallbackCandidate {
+                    family: *preferred,
+                    reason: FallbackReason::Preferred,
+                },
+            );
+        }
+        candidates
+    }
+
+    /// Returns the font that best matches the attributes from the first
+    /// family in the prioritized list that can be resolved.
+    ///
+    /// Named and generic families are tried in order, followed by the
+    /// fallback chain for the script and locale and finally the default
+    /// families. Coverage of individual characters is not checked.
+    ///
+    /// When the selected family provides fonts with the same attributes for
+    /// several CJK regions, as pan-CJK collections do, the font designed for
+    /// the CJK locale of `locale` is preferred so that regional glyph forms
+    /// are correct.
+    pub fn match_font(
+        &self,
+        families: &[FamilyKey],
+        attributes: Attributes,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Option<FontEntry> {
+        self.match_font_sized(families, attributes, script, locale, None)
+    }
+
+    /// Returns the font that best matches the attributes and point size from
+    /// the first family in the prioritized list that can be resolved.
+    ///
+    /// This behaves like [`match_font`](Self::match_font), except that when a
+    /// size is specified, a font in the selected family with the same
+    /// attributes whose optical size range contains the size is preferred,
+    /// or failing that the font whose range is nearest to the size. This
+    /// selects between faces such as "Caption", "Text" and "Display" cuts
+    /// that are grouped into one family.
+    pub fn match_font_sized(
+        &self,
+        families: &[FamilyKey],
+        attributes: Attributes,
+        script: Script,
+        locale: Option<Locale>,
+        size: Option<f32>,
+    ) -> Option<FontEntry> {
+        let request = MatchRequest {
+            attributes,
+            size,
+            script,
+            locale,
+        };
+        let query = |id: FamilyId| self.query_sized(&self.family(id)?, &request);
+        for key in families {
+            let found = match key {
+                FamilyKey::Named(name) => self
+                    .family_by_name(name)
+                    .and_then(|family| self.query_sized(&family, &request)),
+                FamilyKey::Generic(generic) => self.with_scratch(
+                    |system, families| {
+                        families.extend_from_slice(self.generic_slice(system, *generic))
+                    },
+                    |families| families.iter().copied().find_map(query),
+                ),
+                FamilyKey::Id(id) => query(*id),
+            };
+            if found.is_some() {
+                return found;
+            }
+        }
+        self.resolvers
+            .iter()
+            .find_map(|resolver| resolver.resolve_fallback(self, &request))
+    }
+
+    /// Selects a font for the request from the fallback chain for its script
+    /// followed by the default families.
+    pub(crate) fn last_resort(&self, request: &MatchRequest) -> Option<FontEntry> {
+        let query = |id: FamilyId| self.query_sized(&self.family(id)?, request);
+        self.with_chain(request.script, request.locale, |chain| {
+            if let Some(scorer) = &self.scorer {
+                // Scoring looks up the family, so each family is scored once.
+                // The sort is stable so families with equal scores keep their
+                // order in the chain.
+                chain.sort_by_cached_key(|id| {
+                    let score = self
+                        .family(*id)
+                        .map(|family| scorer.score_family(&family, request))
+                        .unwrap_or(0.);
+                    core::cmp::Reverse(Score(score))
+                });
+            }
+            chain.iter().copied().find_map(query)
+        })
+    }
+
+    /// Returns the font that best matches a fontconfig pattern string such as
+    /// "DejaVu Sans:style=Bold:size=12".
+    ///
+    /// The families, attributes and size of the pattern are passed to
+    /// [`match_font_sized`](Self::match_font_sized). Returns `None` if the
+    /// pattern cannot be parsed. See [`FontPattern::parse`] for the
+    /// supported syntax.
+    pub fn match_pattern(
+        &self,
+        pattern: &str,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Option<FontEntry> {
+        let pattern = FontPattern::parse(pattern)?;
+        self.match_font_sized(
+            &pattern.family_keys(),
+            pattern.attributes,
+            script,
+            locale,
+            pattern.size,
+        )
+    }
+
+    fn query_sized(&self, family: &FamilyEntry, request: &MatchRequest) -> Option<FontEntry> {
+        let mut font = self.font(family.query(request.attributes)?)?;
+        // Among fonts with the same attributes, prefer the regional forms of
+        // the requested locale and then the nearest optical size.
+        let cjk = request
+            .locale
+            .map(|locale| locale.cjk())
+            .unwrap_or(Cjk::None);
+        let fitness = |font: &FontEntry| {
+            let regional = cjk == Cjk::None || font.cjk() == cjk;
+            let distance = request
+                .size
+                .map_or(0., |size| font.optical_size_distance(size));
+            (regional, distance)
+        };
+        let is_better = |(regional, distance): (bool, f32),
+                         (best_regional, best_distance): (bool, f32)| {
+            (regional && !best_regional) || (regional == best_regional && distance < best_distance)
+        };
+        let mut best_fitness = fitness(&font);
+        // Other fonts are only considered when this one is for another
+        // region or optical size.
+        let (regional, distance) = best_fitness;
+        if !(regional && distance == 0.) {
+            let attributes = font.attributes();
+            for candidate in family
+                .fonts()
+                .filter_map(|id| self.font(id))
+                .filter(|candidate| candidate.attributes() == attributes)
+            {
+                let candidate_fitness = fitness(&candidate);
+                if is_better(candidate_fitness, best_fitness) {
+                    font = candidate;
+                    best_fitness = candidate_fitness;
+                }
+            }
+        }
+        if let Some(scorer) = &self.scorer {
+            // The standard match wins ties.
+            let mut best_score = scorer.score_font(&font, request);
+            for candidate in family.fonts().filter_map(|id| self.font(id)) {
+                let score = scorer.score_font(&candidate, request);
+                if score > best_score {
+                    font = candidate;
+                    best_score = score;
+                }
+            }
+        }
+        Some(font)
+    }
+
+    /// Returns the first font in the fallback chain for the script and locale
+    /// that maps the specified character to a glyph.
+    ///
+    /// Unlike [`fallback_families`](Self::fallback_families), this checks the
+    /// character map of each candidate, loading font data as necessary. The
+    /// default families are consulted if no fallback covers the character.
+    pub fn fallback_for_char(
+        &self,
+        ch: char,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Option<FontEntry> {
+        if let Some(font) = self.char_override(ch, Attributes::default()) {
+            return Some(font);
+        }
+        self.with_char_chain(ch, script, locale, |chain| {
+            chain
+                .iter()
+                .filter_map(|id| {
+                    self.font(self.with_family(*id, |family| family.query(Attributes::default()))??)
+                })
+                .find(|font| self.has_char(font.id(), ch))
+        })
+        .or_else(|| self.platform_fallback(ch, Attributes::default()))
+    }
+
+    /// Returns the font that should be used to render a single character with
+    /// the specified attributes.
+    ///
+    /// The script of the character is used to select the fallback chain,
+    /// followed by the default families. The first family containing a font
+    /// that maps the character is selected and the font within the family
+    /// that best matches the attributes is returned along with any synthesis
+    /// needed to approximate them.
+    pub fn font_for(
+        &self,
+        ch: char,
+        attributes: Attributes,
+        locale: Option<Locale>,
+    ) -> Option<MatchedFont> {
+        let script = ch.script();
+        self.char_override(ch, attributes)
+            .or_else(|| {
+                self.with_char_chain(ch, script, locale, |chain| {
+                    chain
+                        .iter()
+                        .filter_map(|id| {
+                            self.font(self.with_family(*id, |family| family.query(attributes))??)
+                        })
+                        .find(|font| self.has_char(font.id(), ch))
+                })
+            })
+            .or_else(|| self.platform_fallback(ch, attributes))
+            .map(|font| MatchedFont {
+                font,
+                script,
+                synthesis: font.attributes().synthesize(attributes),
+            })
+    }
+
+    /// Returns the font selected for a character by
+    /// [`Library::add_char_override`] if it maps the character.
+    pub(crate) fn char_override(&self, ch: char, attributes: Attributes) -> Option<FontEntry> {
+        let family = {
+            let overrides = self.library.inner.char_overrides.read();
+            *overrides.unwrap_or_else(|e| e.into_inner()).get(&ch)?
+        };
+        let font = self.font(self.with_family(family, |family| family.query(attributes))??)?;
+        if self.has_char(font.id(), ch) {
+            Some(font)
+        } else {
+            None
+        }
+    }
+
+    /// Asks the platform for a font that maps a character missing from the
+    /// fallback chain. This requires the `coretext` feature on macOS.
+    fn platform_fallback(&self, ch: char, attributes: Attributes) -> Option<FontEntry> {
+        #[cfg(all(feature = "coretext", target_os = "macos"))]
+        {
+            let name = super::system::coretext_family_for_char(ch)?;
+            let font = self.font(self.family_by_name(&name)?.query(attributes)?)?;
+            if self.has_char(font.id(), ch) {
+                return Some(font);
+            }
+        }
+        let _ = (ch, attributes);
+        None
+    }
+
+    /// Returns the localized string with the specified identifier from the
+    /// name table of a font.
+    ///
+    /// A string in the requested language is preferred, falling back to the
+    /// string without a language and then to the first string with the
+    /// identifier. Results are retained in the cache configured with
+    /// [`LibraryBuilder::name_cache_capacity`].
+    pub fn localized_name(
+        &self,
+        font: FontId,
+        id: StringId,
+        language: Option<&str>,
+    ) -> Option<Arc<str>> {
+        let language = language.unwrap_or("");
         self.library
             .inner
-            .system
-            .borrow_mut()
-            .fallback_families(script, locale)
-            .to_vec()
+            .names
+            .get_or_insert(font, id.to_raw(), language, || {
+                self.with_font_ref(font, |font_ref| {
+                    let strings = font_ref.localized_strings();
+                    let string = Some(language)
+                        .filter(|language| !language.is_empty())
+                        .and_then(|language| strings.find_by_id(id, Some(language)))
+                        .or_else(|| strings.find_by_id(id, None))?;
+                    Some(string.chars().collect())
+                })?
+            })
+    }
+
+    /// Returns the set of characters mapped by the specified font.
+    ///
+    /// The set is computed from the character map the first time it is
+    /// requested and cached in the library, so subsequent calls for the same
+    /// font do not load or parse the font.
+    pub fn coverage(&self, id: FontId) -> Option<Arc<Coverage>> {
+        let cache = if id.is_user_font() {
+            self.sync_user();
+            self.user.borrow().1.coverage_cache(id)?.clone()
+        } else {
+            self.library.inner.system().coverage_cache(id)?.clone()
+        };
+        cache.get_or_insert_with(|| self.with_font_ref(id, Coverage::from_font))
+    }
+
+    /// Returns the named instances of the specified variable font.
+    ///
+    /// The instances are recorded when the font is scanned, so this does not
+    /// load the font. The result is empty for fonts that are not variable.
+    pub fn named_instances(&self, id: FontId) -> Vec<NamedInstance> {
+        let instances = if id.is_user_font() {
+            self.sync_user();
+            self.user.borrow().1.instances(id).cloned()
+        } else {
+            self.library.inner.system().instances(id).cloned()
+        };
+        instances
+            .map(|instances| instances.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Returns the variation axes of the specified font.
+    ///
+    /// The axes are recorded when the font is scanned, so layout engines can
+    /// clamp requested coordinates without loading the font. The result is
+    /// empty for fonts that are not variable.
+    pub fn variation_axes(&self, id: FontId) -> Vec<VariationAxis> {
+        let axes = if id.is_user_font() {
+            self.sync_user();
+            self.user.borrow().1.axes(id).cloned()
+        } else {
+            self.library.inner.system().axes(id).cloned()
+        };
+        axes.map(|axes| axes.to_vec()).unwrap_or_default()
+    }
+
+    /// Returns the named instances of every font in the specified family
+    /// along with the font that provides each one.
+    ///
+    /// This allows the styles of variable families to be presented and
+    /// selected like the fonts of a family with separate files per style.
+    pub fn family_instances(&self, id: FamilyId) -> Vec<(FontId, NamedInstance)> {
+        let family = match self.family(id) {
+            Some(family) => family,
+            None => return Vec::new(),
+        };
+        family
+            .fonts()
+            .flat_map(|font| {
+                self.named_instances(font)
+                    .into_iter()
+                    .map(move |instance| (font, instance))
+            })
+            .collect()
+    }
+
+    /// Returns true if the specified font maps the character to a glyph.
+    fn has_char(&self, font: FontId, ch: char) -> bool {
+        self.coverage(font)
+            .map(|coverage| coverage.contains(ch))
+            .unwrap_or(false)
+    }
+
+    /// Prefers the specified family for the script in this context only.
+    ///
+    /// The family is placed at the front of the fallback chain returned by
+    /// [`fallback_families`](Self::fallback_families) for this context. Other
+    /// contexts sharing the same library are unaffected and the preference
+    /// is not persisted. Use [`Library::prefer_for_script`] for a preference
+    /// that applies to new contexts and is reported to the
+    /// [persistence hook](super::LibraryBuilder::persistence).
+    pub fn prefer_for_script(&mut self, script: Script, family: FamilyId) {
+        self.script_preferences.insert(script, family);
+    }
+
+    /// Removes a preference previously set with
+    /// [`prefer_for_script`](Self::prefer_for_script), or inherited from the
+    /// library, from this context.
+    pub fn clear_script_preference(&mut self, script: Script) {
+        self.script_preferences.remove(&script);
+    }
+
+    /// Moves a family to the front of the fallback chain for the script in
+    /// the library, adding it to the chain if necessary.
+    ///
+    /// Unlike [`prefer_for_script`](Self::prefer_for_script), this edits the
+    /// chain shared by all contexts, as a settings page would. The change
+    /// takes effect immediately and the library
+    /// [generation](Self::generation) is advanced so that cached fallback
+    /// results are discarded. The edited chain is no longer extended with
+    /// families registered later. Editing Han affects the chains of all CJK
+    /// locales. Returns false if the family is unknown, the chain is
+    /// unchanged or the library is frozen or static.
+    pub fn prepend_fallback(&self, script: Script, family: FamilyId) -> bool {
+        if self.with_family(family, |_| ()).is_none() {
+            return false;
+        }
+        self.edit_fallbacks(|system| system.prepend_fallback(script, family))
+    }
+
+    /// Removes a family from the fallback chain for the script in the
+    /// library. See [`prepend_fallback`](Self::prepend_fallback).
+    pub fn remove_fallback(&self, script: Script, family: FamilyId) -> bool {
+        self.edit_fallbacks(|system| system.remove_fallback(script, family))
+    }
+
+    fn edit_fallbacks(&self, edit: impl FnOnce(&mut SystemCollectionData) -> bool) -> bool {
+        let changed = match self.library.inner.system_mut() {
+            Some(mut system) => edit(&mut system),
+            None => false,
+        };
+        if changed {
+            self.library
+                .inner
+                .bump_generation(CollectionDiff::default());
+        }
+        changed
+    }
+
+    /// Disables a generic family in this context only, so that it resolves
+    /// to the default families.
+    ///
+    /// This lets a context such as a code preview refuse `cursive` or
+    /// `fantasy` while other contexts sharing the library keep the full
+    /// CSS semantics. Disabling the emoji generic family also stops it from
+    /// being tried ahead of the fallback chain for emoji characters.
+    pub fn disable_generic(&mut self, generic: GenericFamily) {
+        self.disabled_generics |= 1 << generic as u32;
+    }
+
+    /// Enables a generic family previously disabled with
+    /// [`disable_generic`](Self::disable_generic).
+    pub fn enable_generic(&mut self, generic: GenericFamily) {
+        self.disabled_generics &= !(1 << generic as u32);
+    }
+
+    /// Returns true if the generic family is disabled in this context.
+    pub fn is_generic_disabled(&self, generic: GenericFamily) -> bool {
+        self.disabled_generics & (1 << generic as u32) != 0
+    }
+
+    /// Sets a scorer that biases family and font selection in
+    /// [`match_font`](Self::match_font) and
+    /// [`match_font_sized`](Self::match_font_sized) for this context only.
+    pub fn set_match_scorer(&mut self, scorer: Option<Arc<dyn MatchScorer>>) {
+        self.scorer = scorer;
+    }
+
+    /// Sets a policy that excludes font sources from this context based on
+    /// their trust level.
+    ///
+    /// Fonts from sources that are not allowed are hidden from lookups,
+    /// matching and fallback, and their data is never loaded by the
+    /// context. Families without any allowed fonts are hidden as well,
+    /// although they are still listed by [`families`](Self::families).
+    /// This allows a process rendering untrusted documents to avoid loading
+    /// fonts registered as [`SourceTrust::Remote`], for example with
+    /// `Some(Arc::new(SourceTrust::Registered))`.
+    pub fn set_trust_policy(&mut self, policy: Option<Arc<dyn TrustPolicy>>) {
+        self.trust_policy = policy;
+    }
+
+    /// Returns the byte offset of the first character in the text that the
+    /// specified font cannot map to a glyph, or `None` if every character
+    /// is supported.
+    ///
+    /// This only consults the character map of the font and does not perform
+    /// shaping. Control characters, zero width formatting characters and
+    /// variation selectors are ignored. If the font cannot be loaded, the
+    /// first remaining character is reported as missing.
+    pub fn first_missing(&self, text: &str, font: FontId) -> Option<usize> {
+        let mut chars = text
+            .char_indices()
+            .filter(|(_, ch)| !is_ignorable(*ch))
+            .peekable();
+        chars.peek()?;
+        let first = chars.peek().map(|(offset, _)| *offset);
+        match self.coverage(font) {
+            Some(coverage) => chars
+                .find(|(_, ch)| !coverage.contains(*ch))
+                .map(|(offset, _)| offset),
+            None => first,
+        }
+    }
+
+    /// Returns suggestions for freely available fonts covering the scripts
+    /// in the text that have no verified coverage in this context.
+    ///
+    /// This allows applications to tell users which font to install rather
+    /// than rendering missing glyph boxes.
+    pub fn missing_script_suggestions(
+        &self,
+        text: &str,
+        locale: Option<Locale>,
+    ) -> Vec<FontSuggestion> {
+        let mut scripts: Vec<Script> = Vec::new();
+        for script in text.chars().map(|ch| ch.script()) {
+            if !matches!(script, Script::Common | Script::Inherited | Script::Unknown)
+                && !scripts.contains(&script)
+            {
+                scripts.push(script);
+            }
+        }
+        scripts
+            .into_iter()
+            .filter(|script| {
+                !self
+                    .fallback_candidates(*script, locale)
+                    .iter()
+                    .any(|candidate| {
+                        matches!(
+                            candidate.reason,
+                            FallbackReason::Preferred | FallbackReason::CoverageVerified
+                        )
+                    })
+            })
+            .filter_map(|script| {
+                let family = match (script, locale.map(|l| l.cjk())) {
+                    (Script::Han, Some(Cjk::Traditional)) => "Noto Sans CJK TC",
+                    (Script::Han, Some(Cjk::Japanese)) => "Noto Sans CJK JP",
+                    (Script::Han, Some(Cjk::Korean)) => "Noto Sans CJK KR",
+                    _ => crate::script_tags::script_suggestion(script)?,
+                };
+                Some(FontSuggestion { script, family })
+            })
+            .collect()
+    }
+
+    /// Loads the specified font and invokes the closure with a reference to
+    /// it.
+    fn with_font_ref<R>(&self, id: FontId, f: impl FnOnce(&FontRef) -> R) -> Option<R> {
+        let font = self.font(id)?;
+        let data = self.load(font.source())?;
+        let font_ref = FontRef::from_index(&data, font.index() as usize)?;
+        Some(f(&font_ref))
     }
 
     /// Registers the fonts contained in the specified data. Returns identifiers for
     /// the families and fonts added to the context.
+    ///
+    /// This may be called concurrently from multiple threads. The font data is
+    /// parsed before acquiring the library lock, so registrations only
+    /// serialize while committing the results.
+    ///
+    /// With the `woff` feature enabled, WOFF and WOFF2 data is accepted and
+    /// decompressed before scanning.
     pub fn register_fonts(&self, data: Vec<u8>) -> Option<Registration> {
-        let mut collection = self.library.inner.system.borrow_mut();
+        self.try_register_fonts(data).ok()
+    }
+
+    /// Registers the fonts contained in the specified data, returning an
+    /// error describing why no fonts were added on failure.
+    pub fn try_register_fonts(&self, data: Vec<u8>) -> Result<Registration, RegisterError> {
+        self.try_register_fonts_with_trust(data, SourceTrust::Registered)
+    }
+
+    /// Registers the fonts contained in the specified data with a trust
+    /// level, such as [`SourceTrust::Remote`] for fonts embedded in a
+    /// document. See [`set_trust_policy`](Self::set_trust_policy).
+    pub fn try_register_fonts_with_trust(
+        &self,
+        data: Vec<u8>,
+        trust: SourceTrust,
+    ) -> Result<Registration, RegisterError> {
+        self.try_register_fonts_in(data, trust, None)
+    }
+
+    /// Registers the fonts contained in the specified data under family
+    /// names qualified by a namespace.
+    ///
+    /// This prevents a document that embeds a font named after an installed
+    /// family, such as "Segoe UI", from changing the fonts used by other
+    /// parts of an application sharing the library. The families are only
+    /// found by their qualified names, as produced by
+    /// [`NamespaceResolver::family_name`]. Inserting a
+    /// [`NamespaceResolver`] for the namespace at the front of the
+    /// [resolvers](Self::resolvers) of the context that renders the document
+    /// resolves the names used by the document to its fonts.
+    pub fn try_register_fonts_namespaced(
+        &self,
+        data: Vec<u8>,
+        trust: SourceTrust,
+        namespace: &str,
+    ) -> Result<Registration, RegisterError> {
+        self.try_register_fonts_in(data, trust, Some(namespace))
+    }
+
+    fn try_register_fonts_in(
+        &self,
+        data: Vec<u8>,
+        trust: SourceTrust,
+        namespace: Option<&str>,
+    ) -> Result<Registration, RegisterError> {
+        if self.library.is_frozen() {
+            return Err(RegisterError::Frozen);
+        }
         let mut reg = Registration::default();
+        self.register_data(FontData::new(data), trust, namespace, &mut reg);
+        if reg.fonts.is_empty() {
+            // The library may have been frozen concurrently.
+            if self.library.is_frozen() {
+                Err(RegisterError::Frozen)
+            } else if let Some(face) = reg.rejected.first() {
+                Err(RegisterError::Rejected(face.reason))
+            } else {
+                Err(RegisterError::NoFonts)
+            }
+        } else {
+            Ok(reg)
+        }
+    }
+
+    /// Registers fonts that were scanned from the specified data with a
+    /// [`FontScanner`](super::FontScanner), avoiding parsing the data
+    /// again. This lets tools that scan many fonts in parallel with a
+    /// [`ScannerPool`](super::ScannerPool) register the results in bulk.
+    ///
+    /// The fonts must have been produced by scanning `data`.
+    pub fn register_scanned_fonts(
+        &self,
+        data: Vec<u8>,
+        fonts: &[ScannedFont],
+    ) -> Option<Registration> {
+        if self.library.is_frozen() {
+            return None;
+        }
         let data = FontData::new(data);
+        let source = SourceData {
+            kind: SourceDataKind::Data(data),
+            status: RwLock::new(SourceDataStatus::Vacant),
+            trust: SourceTrust::Registered,
+        };
+        let mut reg = Registration::default();
+        self.commit_fonts(fonts, source, &mut reg);
+        Some(reg).filter(|reg| !reg.fonts.is_empty())
+    }
+
+    /// Registers the fonts contained in the data produced by a reader, such
+    /// as standard input in a pipeline. Returns `None` if the data could not
+    /// be read, is larger than `limit` bytes or contains no new fonts. See
+    /// [`FontData::from_reader`].
+    pub fn register_fonts_from_reader(
+        &self,
+        reader: impl std::io::Read,
+        limit: usize,
+    ) -> Option<Registration> {
+        if self.library.is_frozen() {
+            return None;
+        }
+        let data = FontData::from_reader(reader, limit).ok()?;
+        let mut reg = Registration::default();
+        self.register_data(data, SourceTrust::Registered, None, &mut reg);
+        Some(reg).filter(|reg| !reg.fonts.is_empty())
+    }
+
+    /// Registers the fonts contained in the specified file. Returns identifiers
+    /// for the families and fonts added to the context, or `None` if the file
+    /// could not be read or contains no new fonts.
+    ///
+    /// Unlike [`register_fonts`](Self::register_fonts), the fonts are backed
+    /// by the file so the data is loaded lazily and may be released when no
+    /// longer in use. The path is reported to the
+    /// [persistence hook](super::LibraryBuilder::persistence) of the library,
+    /// if any.
+    pub fn register_font_file(&self, path: impl AsRef<Path>) -> Option<Registration> {
+        let file_system = self.library.inner.system().file_system();
+        if file_system.metadata(path.as_ref()).ok()?.is_dir {
+            return None;
+        }
+        self.register_paths_persisted(path.as_ref())
+    }
+
+    /// Registers the fonts contained in all files in the specified directory
+    /// and its subdirectories. The files are parsed in parallel and backed by
+    /// their paths as with [`register_font_file`](Self::register_font_file).
+    pub fn register_font_dir(&self, path: impl AsRef<Path>) -> Option<Registration> {
+        let file_system = self.library.inner.system().file_system();
+        if !file_system.metadata(path.as_ref()).ok()?.is_dir {
+            return None;
+        }
+        self.register_paths_persisted(path.as_ref())
+    }
+
+    /// Registers the fonts at a path and reports it to the persistence hook.
+    fn register_paths_persisted(&self, path: &Path) -> Option<Registration> {
+        let reg = self.register_paths(path)?;
+        if let Some(persistence) = &self.library.inner.persistence {
+            persistence.fonts_registered(path);
+        }
+        Some(reg)
+    }
+
+    pub(crate) fn register_paths(&self, path: &Path) -> Option<Registration> {
+        let (file_system, scanners) = {
+            let system = self.library.inner.system();
+            (system.file_system(), system.scanners())
+        };
+        let path = file_system.canonicalize(path).ok()?;
+        let mut files = Vec::new();
+        let mut unreadable = Vec::new();
+        collect_files(&*file_system, &path, &mut files, &mut unreadable).ok()?;
+        let mut reg = Registration::default();
+        self.record_rejected(&unreadable, &mut reg);
+        for (file, fonts, rejected) in scan_files(&*file_system, &scanners, files, None) {
+            self.record_rejected(&rejected, &mut reg);
+            if let Ok(source) = SourceData::from_path(&file) {
+                self.commit_fonts(&fonts, source, &mut reg);
+            }
+        }
+        if reg.fonts.is_empty() {
+            None
+        } else {
+            Some(reg)
+        }
+    }
+
+    /// Removes the fonts described by a registration from the library.
+    ///
+    /// Families left without fonts are removed from name resolution and
+    /// fallback, and the font data is released once no fonts from the
+    /// source remain. Returns true if any fonts were removed.
+    pub fn unregister(&self, reg: &Registration) -> bool {
+        unregister(&self.library, reg)
+    }
+
+    /// Registers the fonts contained in the specified data, returning a guard
+    /// that unregisters them when dropped.
+    pub fn register_fonts_scoped(&self, data: Vec<u8>) -> Option<ScopedRegistration> {
+        Some(ScopedRegistration {
+            library: self.library.clone(),
+            reg: self.register_fonts(data)?,
+        })
+    }
+
+    /// Registers only the faces at the specified indices of a font collection.
+    ///
+    /// Each selected face is copied into a standalone buffer so the remainder
+    /// of the collection data is released once this function returns.
+    pub fn register_collection(&self, data: Vec<u8>, indices: &[u32]) -> Option<Registration> {
+        let mut reg = Registration::default();
+        for index in indices {
+            if let Some(face) = crate::tables::extract_face(&data, *index) {
+                let data = FontData::new(face);
+                self.register_data(data, SourceTrust::Registered, None, &mut reg);
+            }
+        }
+        if reg.fonts.is_empty() {
+            None
+        } else {
+            Some(reg)
+        }
+    }
+
+    /// Returns the sources in the context that contain more than one face,
+    /// such as TrueType collections.
+    pub fn collections(&self) -> Vec<CollectionEntry> {
+        self.sync_user();
+        let mut collections = self.user.borrow().1.collections();
+        collections.extend(self.library.inner.system().collections());
+        collections
+    }
+
+    fn register_data(
+        &self,
+        data: FontData,
+        trust: SourceTrust,
+        namespace: Option<&str>,
+        reg: &mut Registration,
+    ) -> u32 {
         let source = SourceData {
             kind: SourceDataKind::Data(data.clone()),
             status: RwLock::new(SourceDataStatus::Vacant),
+            trust,
+        };
+        let scanners = self.library.inner.system().scanners();
+        let mut scanner = scanners.get();
+        let mut fonts = scanner.scan_all(&data);
+        self.record_rejected(&scanner.rejected_faces(None), reg);
+        drop(scanner);
+        if let Some(namespace) = namespace {
+            for font in &mut fonts {
+                font.set_namespace(namespace);
+            }
+        }
+        self.commit_fonts(&fonts, source, reg)
+    }
+
+    /// Adds faces rejected while scanning to a registration and the library.
+    fn record_rejected(&self, rejected: &[RejectedFace], reg: &mut Registration) {
+        if rejected.is_empty() {
+            return;
+        }
+        reg.rejected.extend_from_slice(rejected);
+        if let Some(mut system) = self.library.inner.system_mut() {
+            system.add_rejected_faces(rejected);
+        }
+    }
+
+    /// Adds previously scanned fonts to the library under the write lock.
+    pub(crate) fn commit_fonts(
+        &self,
+        fonts: &[ScannedFont],
+        source: SourceData,
+        reg: &mut Registration,
+    ) -> u32 {
+        if fonts.is_empty() || self.library.is_frozen() {
+            return 0;
+        }
+        let duplicates = self.library.inner.system().static_duplicates(&source);
+        if let Some(duplicates) = duplicates {
+            return self.commit_overlay_fonts(fonts, source, &duplicates, reg);
+        }
+        let mut collection = match self.library.inner.system_mut() {
+            Some(collection) => collection,
+            None => return 0,
         };
+        let family_count = collection.family_count();
+        let (first_family, first_font) = (reg.families.len(), reg.fonts.len());
         let count = collection
-            .add_fonts(data, source, Some(&mut reg))
+            .add_scanned_fonts(fonts, source, Some(reg))
             .unwrap_or(0);
+        drop(collection);
         if count != 0 {
-            self.library
-                .inner
-                .user_version
-                .fetch_add(1, Ordering::Relaxed);
-            Some(reg)
-        } else {
-            None
+            let diff = added_diff(reg, first_family, first_font, family_count);
+            self.library.inner.bump_generation(diff);
+        }
+        count
+    }
+
+    /// Adds scanned fonts to the user collection of a library created from
+    /// a static collection.
+    ///
+    /// Faces that the static collection already provides are added to the
+    /// registration with their static identifiers instead, so that
+    /// enumeration and fallback do not present the same face twice.
+    /// Namespaced fonts are always added since they only resolve through
+    /// their namespace.
+    fn commit_overlay_fonts(
+        &self,
+        fonts: &[ScannedFont],
+        source: SourceData,
+        duplicates: &[(u32, FamilyId, FontId)],
+        reg: &mut Registration,
+    ) -> u32 {
+        let mut added = Vec::new();
+        for font in fonts {
+            let duplicate = duplicates
+                .iter()
+                .find(|(index, ..)| !font.namespaced && *index == font.index());
+            match duplicate {
+                Some((_, family, id)) => {
+                    if !reg.families.contains(family) {
+                        reg.families.push(*family);
+                    }
+                    reg.fonts.push(*id);
+                }
+                None => added.push(font.clone()),
+            }
+        }
+        if added.is_empty() {
+            return 0;
+        }
+        let mut guard = self.library.inner.user.write().unwrap();
+        let user = Arc::make_mut(&mut guard);
+        let family_count = user.families.len();
+        let (first_family, first_font) = (reg.families.len(), reg.fonts.len());
+        let count = user
+            .add_scanned_fonts(&added, source, Some(reg))
+            .unwrap_or(0);
+        user.rank_fallbacks();
+        drop(guard);
+        if count != 0 {
+            let diff = added_diff(reg, first_family, first_font, family_count);
+            self.library.inner.bump_generation(diff);
         }
+        count
     }
 
+    /// Refreshes the user collection snapshot if the library has changed. This
+    /// only replaces the shared reference and never copies the collection.
     fn sync_user(&self) {
         let user_version = self.library.inner.user_version.load(Ordering::Relaxed);
         if self.user.borrow().0 != user_version {
-            let mut arc_user = self.user.borrow().clone();
-            let mut user = Arc::make_mut(&mut arc_user);
-            let library_user = self.library.inner.user.borrow();
-            library_user.clone_into(&mut user.1);
-            user.0 = self.library.inner.user_version.load(Ordering::Relaxed);
-            *self.user.borrow_mut() = arc_user;
+            let user = self.library.inner.user.read().unwrap().clone();
+            *self.user.borrow_mut() = (user_version, user);
+        }
+    }
+}
+
+/// Family score ordered with [`f32::total_cmp`] for use as a sort key.
+struct Score(f32);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == core::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Returns the families and fonts added to a registration after the
+/// specified positions, excluding families that the collection already had.
+fn added_diff(
+    reg: &Registration,
+    first_family: usize,
+    first_font: usize,
+    family_count: usize,
+) -> CollectionDiff {
+    CollectionDiff {
+        added_families: reg.families[first_family..]
+            .iter()
+            .copied()
+            .filter(|id| id.to_usize() >= family_count)
+            .collect(),
+        added_fonts: reg.fonts[first_font..].to_vec(),
+        ..Default::default()
+    }
+}
+
+fn unregister(library: &Library, reg: &Registration) -> bool {
+    // Fonts registered with a library created from a static collection are
+    // held by the user collection.
+    let (user_fonts, fonts): (Vec<FontId>, Vec<FontId>) =
+        reg.fonts.iter().copied().partition(|id| id.is_user_font());
+    let mut diff = match library.inner.system_mut() {
+        Some(mut system) => system.unregister(&fonts),
+        None => return false,
+    };
+    if !user_fonts.is_empty() {
+        let mut user = library.inner.user.write().unwrap();
+        diff.merge(&Arc::make_mut(&mut user).unregister(&user_fonts));
+    }
+    if diff.is_empty() {
+        return false;
+    }
+    library.inner.bump_generation(diff);
+    true
+}
+
+/// Registration that removes its fonts from the library when dropped.
+///
+/// This is returned by
+/// [`FontContext::register_fonts_scoped`](FontContext::register_fonts_scoped).
+pub struct ScopedRegistration {
+    library: Library,
+    reg: Registration,
+}
+
+impl ScopedRegistration {
+    /// Returns the families and fonts that were registered.
+    pub fn registration(&self) -> &Registration {
+        &self.reg
+    }
+
+    /// Keeps the fonts registered for the lifetime of the library and
+    /// returns the registration.
+    pub fn keep(mut self) -> Registration {
+        // Dropping the guard with an empty registration removes nothing.
+        core::mem::take(&mut self.reg)
+    }
+}
+
+impl Drop for ScopedRegistration {
+    fn drop(&mut self) {
+        unregister(&self.library, &self.reg);
+    }
+}
+
+/// Returns a family name folded for comparison by
+/// [`FontContext::suggest_families`].
+fn suggestion_key(name: &str) -> String {
+    fold_name(name)
+        .filter(|ch| !ch.is_whitespace() && *ch != '-' && *ch != '_')
+        .collect()
+}
+
+/// Returns the number of character insertions, deletions, substitutions and
+/// transpositions of adjacent characters needed to turn one string into
+/// another.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in rows[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Returns true for characters that fonts are not expected to map.
+pub(crate) fn is_ignorable(ch: char) -> bool {
+    ch.is_control()
+        || matches!(ch, '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FE00}'..='\u{FE0F}')
+}
+
+/// Returns true for characters with the Unicode `Emoji_Presentation`
+/// property, which default to emoji presentation. Symbols such as U+00A9
+/// and U+2764 default to text presentation instead.
+fn is_emoji_presentation(ch: char) -> bool {
+    let c = ch as u32;
+    EMOJI_PRESENTATION
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                core::cmp::Ordering::Less
+            } else if start > c {
+                core::cmp::Ordering::Greater
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Inclusive ranges of `Emoji_Presentation` characters from the Unicode 15.1
+/// `emoji-data.txt`, sorted by code point.
+#[rustfmt::skip]
+const EMOJI_PRESENTATION: &[(u32, u32)] = &[
+    (0x231A, 0x231B), (0x23E9, 0x23EC), (0x23F0, 0x23F0), (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE), (0x2614, 0x2615), (0x2648, 0x2653), (0x267F, 0x267F),
+    (0x2693, 0x2693), (0x26A1, 0x26A1), (0x26AA, 0x26AB), (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5), (0x26CE, 0x26CE), (0x26D4, 0x26D4), (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3), (0x26F5, 0x26F5), (0x26FA, 0x26FA), (0x26FD, 0x26FD),
+    (0x2705, 0x2705), (0x270A, 0x270B), (0x2728, 0x2728), (0x274C, 0x274C),
+    (0x274E, 0x274E), (0x2753, 0x2755), (0x2757, 0x2757), (0x2795, 0x2797),
+    (0x27B0, 0x27B0), (0x27BF, 0x27BF), (0x2B1B, 0x2B1C), (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55), (0x1F004, 0x1F004), (0x1F0CF, 0x1F0CF), (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A), (0x1F1E6, 0x1F1FF), (0x1F201, 0x1F201), (0x1F21A, 0x1F21A),
+    (0x1F22F, 0x1F22F), (0x1F232, 0x1F236), (0x1F238, 0x1F23A), (0x1F250, 0x1F251),
+    (0x1F300, 0x1F320), (0x1F32D, 0x1F335), (0x1F337, 0x1F37C), (0x1F37E, 0x1F393),
+    (0x1F3A0, 0x1F3CA), (0x1F3CF, 0x1F3D3), (0x1F3E0, 0x1F3F0), (0x1F3F4, 0x1F3F4),
+    (0x1F3F8, 0x1F43E), (0x1F440, 0x1F440), (0x1F442, 0x1F4FC), (0x1F4FF, 0x1F53D),
+    (0x1F54B, 0x1F54E), (0x1F550, 0x1F567), (0x1F57A, 0x1F57A), (0x1F595, 0x1F596),
+    (0x1F5A4, 0x1F5A4), (0x1F5FB, 0x1F64F), (0x1F680, 0x1F6C5), (0x1F6CC, 0x1F6CC),
+    (0x1F6D0, 0x1F6D2), (0x1F6D5, 0x1F6D7), (0x1F6DC, 0x1F6DF), (0x1F6EB, 0x1F6EC),
+    (0x1F6F4, 0x1F6FC), (0x1F7E0, 0x1F7EB), (0x1F7F0, 0x1F7F0), (0x1F90C, 0x1F93A),
+    (0x1F93C, 0x1F945), (0x1F947, 0x1F9FF), (0x1FA70, 0x1FA7C), (0x1FA80, 0x1FA88),
+    (0x1FA90, 0x1FABD), (0x1FABF, 0x1FAC5), (0x1FACE, 0x1FADB), (0x1FAE0, 0x1FAE8),
+    (0x1FAF0, 0x1FAF8),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_presentation_table_is_sorted() {
+        assert!(EMOJI_PRESENTATION
+            .windows(2)
+            .all(|w| w[0].0 <= w[0].1 && w[0].1 < w[1].0));
+    }
+
+    #[test]
+    fn emoji_presentation() {
+        for ch in [
+            '\u{231A}',
+            '\u{26A1}',
+            '\u{26BD}',
+            '\u{2705}',
+            '\u{1F004}',
+            '\u{1F600}',
+        ] {
+            assert!(is_emoji_presentation(ch), "{:?}", ch);
+        }
+        // Text presentation symbols, including the mahjong tiles and playing
+        // cards other than the red dragon and the joker.
+        for ch in [
+            '\u{A9}',
+            '\u{2764}',
+            '\u{1F000}',
+            '\u{1F0A1}',
+            '\u{1F321}',
+            'a',
+        ] {
+            assert!(!is_emoji_presentation(ch), "{:?}", ch);
         }
     }
 }
@@ -0,0 +1,91 @@
+//! Cached character coverage for fonts.
+
+use std::sync::{Arc, RwLock};
+use swash::FontRef;
+
+/// Set of characters mapped by the character map of a font.
+///
+/// The set is stored as a sorted list of inclusive codepoint ranges which is
+/// compact for the mostly contiguous blocks found in real fonts.
+#[derive(Clone, Default, Debug)]
+pub struct Coverage {
+    ranges: Vec<(u32, u32)>,
+    len: usize,
+}
+
+impl Coverage {
+    /// Builds the coverage set from the character map of the font.
+    pub fn from_font(font: &FontRef) -> Self {
+        let mut codepoints = Vec::new();
+        font.charmap().enumerate(|codepoint, glyph_id| {
+            if glyph_id != 0 {
+                codepoints.push(codepoint);
+            }
+        });
+        codepoints.sort_unstable();
+        codepoints.dedup();
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        for codepoint in &codepoints {
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == *codepoint => range.1 = *codepoint,
+                _ => ranges.push((*codepoint, *codepoint)),
+            }
+        }
+        Self {
+            ranges,
+            len: codepoints.len(),
+        }
+    }
+
+    /// Returns true if the character is mapped to a glyph.
+    pub fn contains(&self, ch: char) -> bool {
+        let codepoint = ch as u32;
+        self.ranges
+            .binary_search_by(|range| {
+                if range.1 < codepoint {
+                    std::cmp::Ordering::Less
+                } else if range.0 > codepoint {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    /// Returns the number of characters in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the inclusive codepoint ranges in the set.
+    pub fn ranges(&self) -> &[(u32, u32)] {
+        &self.ranges
+    }
+}
+
+/// Slot holding the lazily computed coverage of a font. Clones share the
+/// same slot.
+#[derive(Clone, Default)]
+pub struct CoverageCache(Arc<RwLock<Option<Arc<Coverage>>>>);
+
+impl CoverageCache {
+    /// Returns the cached coverage, computing it with the closure on first
+    /// use.
+    pub fn get_or_insert_with(
+        &self,
+        f: impl FnOnce() -> Option<Coverage>,
+    ) -> Option<Arc<Coverage>> {
+        if let Some(coverage) = &*self.0.read().unwrap() {
+            return Some(coverage.clone());
+        }
+        let coverage = Arc::new(f()?);
+        let mut slot = self.0.write().unwrap();
+        Some(slot.get_or_insert(coverage).clone())
+    }
+}
@@ -0,0 +1,187 @@
+//! Parsing of CSS font family lists.
+
+use super::{FamilyKey, GenericFamily};
+use std::borrow::Cow;
+use std::iter::Peekable;
+
+/// Parses the value of a CSS `font-family` property into a prioritized list
+/// of family keys.
+///
+/// Quoted names are always named families, while unquoted names are
+/// sequences of identifiers that are matched against the generic family
+/// keywords without regard to case. Malformed entries are skipped.
+pub fn parse_family_list(value: &str) -> Vec<FamilyKey<'_>> {
+    let mut families = Vec::new();
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start();
+        let quote = match rest.chars().next() {
+            None => break,
+            Some(',') => {
+                rest = &rest[1..];
+                continue;
+            }
+            Some(quote @ '"') | Some(quote @ '\'') => quote,
+            Some(_) => {
+                let end = rest.find(',').unwrap_or(rest.len());
+                if let Some(key) = parse_identifiers(&rest[..end]) {
+                    families.push(key);
+                }
+                rest = &rest[end..];
+                continue;
+            }
+        };
+        let (name, len) = match parse_string(&rest[1..], quote) {
+            Some(string) => string,
+            None => break,
+        };
+        rest = rest[1 + len..].trim_start();
+        // Anything other than a separator after a string makes the entry
+        // invalid.
+        if rest.is_empty() || rest.starts_with(',') {
+            if !name.is_empty() {
+                families.push(FamilyKey::Named(name));
+            }
+        } else {
+            rest = &rest[rest.find(',').unwrap_or(rest.len())..];
+        }
+    }
+    families
+}
+
+/// Parses a string following the opening quote. Returns the unescaped
+/// string and the length of the input including the closing quote, or
+/// `None` if the string is not terminated.
+fn parse_string(s: &str, quote: char) -> Option<(Cow<'_, str>, usize)> {
+    let end = s.find([quote, '\\'])?;
+    if s[end..].starts_with(quote) {
+        return Some((Cow::Borrowed(&s[..end]), end + 1));
+    }
+    let mut string = String::from(&s[..end]);
+    let mut chars = s[end..].char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '\\' => {
+                // An escaped newline continues the string.
+                if chars.next_if(|&(_, ch)| ch == '\n').is_none() {
+                    unescape(&mut chars, &mut string)?;
+                }
+            }
+            _ if ch == quote => return Some((Cow::Owned(string), end + i + 1)),
+            _ => string.push(ch),
+        }
+    }
+    None
+}
+
+/// Parses an unquoted entry consisting of identifiers separated by
+/// whitespace.
+fn parse_identifiers(s: &str) -> Option<FamilyKey<'_>> {
+    let s = s.trim();
+    if s.is_empty() || s.contains(['"', '\'']) {
+        return None;
+    }
+    if s.contains('\\') {
+        // Escaped identifiers never match the generic family keywords.
+        let mut unescaped = String::new();
+        let mut chars = s.char_indices().peekable();
+        while let Some((_, ch)) = chars.next() {
+            match ch {
+                '\\' => unescape(&mut chars, &mut unescaped)?,
+                _ => unescaped.push(ch),
+            }
+        }
+        let name = unescaped.split_whitespace().collect::<Vec<_>>().join(" ");
+        return Some(FamilyKey::Named(Cow::Owned(name)));
+    }
+    if let Some(generic) = GenericFamily::parse(&s.to_ascii_lowercase()) {
+        return Some(FamilyKey::Generic(generic));
+    }
+    // Identifiers are separated by a single space in the family name.
+    let name = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name == s {
+        Some(FamilyKey::Named(Cow::Borrowed(s)))
+    } else {
+        Some(FamilyKey::Named(Cow::Owned(name)))
+    }
+}
+
+/// Decodes the escape following a backslash and appends it to a string.
+/// Returns `None` if the input ends.
+///
+/// Up to six hex digits give a code point, optionally followed by a single
+/// whitespace character, and invalid code points are replaced with U+FFFD.
+/// Any other character stands for itself.
+fn unescape<I>(chars: &mut Peekable<I>, string: &mut String) -> Option<()>
+where
+    I: Iterator<Item = (usize, char)>,
+{
+    let (_, ch) = chars.next()?;
+    let mut value = match ch.to_digit(16) {
+        Some(digit) => digit,
+        None => {
+            string.push(ch);
+            return Some(());
+        }
+    };
+    for _ in 1..6 {
+        match chars.next_if(|(_, ch)| ch.is_ascii_hexdigit()) {
+            Some((_, digit)) => value = value * 16 + digit.to_digit(16).unwrap_or(0),
+            None => break,
+        }
+    }
+    if let Some((_, '\r')) = chars.next_if(|(_, ch)| matches!(ch, ' ' | '\t' | '\n' | '\r')) {
+        chars.next_if(|&(_, ch)| ch == '\n');
+    }
+    match char::from_u32(value) {
+        Some(ch) if value != 0 => string.push(ch),
+        _ => string.push('\u{fffd}'),
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> FamilyKey<'_> {
+        FamilyKey::Named(Cow::Borrowed(name))
+    }
+
+    #[test]
+    fn parses_lists() {
+        assert_eq!(
+            parse_family_list("\"Fira Sans\", Noto  Sans ,serif"),
+            [
+                named("Fira Sans"),
+                named("Noto Sans"),
+                FamilyKey::Generic(GenericFamily::Serif)
+            ]
+        );
+        assert_eq!(parse_family_list("'Sans-Serif'"), [named("Sans-Serif")]);
+        assert_eq!(
+            parse_family_list("MONOSPACE"),
+            [FamilyKey::Generic(GenericFamily::Monospace)]
+        );
+    }
+
+    #[test]
+    fn skips_malformed_entries() {
+        assert_eq!(parse_family_list("\"A\" B, C"), [named("C")]);
+        assert_eq!(parse_family_list("A\"B, C"), [named("C")]);
+        assert_eq!(parse_family_list("C, \"unterminated"), [named("C")]);
+        assert!(parse_family_list(", , ''").is_empty());
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(parse_family_list("\"A\\26 B\""), [named("A&B")]);
+        assert_eq!(parse_family_list("\"A\\000026B\""), [named("A&B")]);
+        assert_eq!(parse_family_list("\"\\\"Q\\\"\""), [named("\"Q\"")]);
+        assert_eq!(parse_family_list("\"A\\\nB\""), [named("AB")]);
+        assert_eq!(parse_family_list("\"\\0\""), [named("\u{fffd}")]);
+        assert_eq!(parse_family_list("\"\\D800\""), [named("\u{fffd}")]);
+        assert_eq!(parse_family_list("Font\\ Name"), [named("Font Name")]);
+        assert_eq!(parse_family_list("\\73 erif"), [named("serif")]);
+    }
+}
@@ -1,23 +1,40 @@
-use crate::scan::scan_path;
+use crate::scan::{scan_paths, ScannerPool};
 
+use super::cache::{FileStamp, ScanCache};
+use super::coverage::CoverageCache;
 use super::font::*;
 use super::id::*;
+use super::metrics::FaceMetrics;
+#[cfg(not(feature = "font-kit"))]
+use super::native::{Handle, SystemSource};
+use super::vfs::{FileSystem, OsFileSystem};
 use super::*;
-use font_kit::handle::Handle;
-use font_kit::source::SystemSource;
-use std::collections::HashMap;
+#[cfg(feature = "font-kit")]
+use font_kit::{handle::Handle, source::SystemSource};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use swash::text::Cjk;
 use swash::text::Script;
 use swash::{Attributes, CacheKey, Stretch, Style, Weight};
+use unicode_normalization::UnicodeNormalization;
 
 #[derive(Clone)]
 pub struct FamilyData {
     pub name: String,
     pub has_stretch: bool,
+    pub capabilities: Capabilities,
+    pub glyph_count: u16,
+    pub file_size: u64,
+    /// True once the attributes of the fonts have been refined by loading
+    /// each face.
+    pub refined: bool,
+    /// Priority of the root that the fonts were discovered in.
+    pub priority: SourcePriority,
     pub fonts: Vec<(FontId, Stretch, Weight, Style)>,
 }
 
@@ -28,24 +45,48 @@ pub struct FontData {
     pub index: u32,
     pub attributes: Attributes,
     pub cache_key: CacheKey,
+    pub metrics: Option<FaceMetrics>,
+    pub glyph_count: u16,
+    pub file_size: u64,
+    pub coverage: CoverageCache,
+    pub instances: Arc<[NamedInstance]>,
+    pub axes: Arc<[VariationAxis]>,
+    /// Position of the font among fonts with the same attributes in the
+    /// family.
+    pub variant: u16,
+    /// Range of point sizes the font is designed for.
+    pub optical_size: Option<(f32, f32)>,
+    /// CJK locale the glyphs of the font are designed for.
+    pub cjk: Cjk,
+    /// Format of the color glyphs in the font.
+    pub color_format: Option<ColorFormat>,
 }
 
 #[derive(Clone)]
 pub enum SourceDataKind {
     Path(Arc<PathBuf>),
     Data(super::font::FontData),
+    /// Source of fonts that have been unregistered.
+    Removed,
 }
 
 #[derive(Clone)]
 pub enum SourceDataStatus {
     Vacant,
-    Present(WeakFontData),
-    Error,
+    /// Data that has been loaded along with the modification time and size
+    /// of the file when it was loaded.
+    Present(WeakFontData, Option<FileStamp>),
+    /// Loading failed. The error is retained so that it can be reported by
+    /// later attempts, along with the modification time and size of the file
+    /// when it failed so that a changed file is retried when sources are
+    /// verified.
+    Error(LoadError, Option<FileStamp>),
 }
 
 pub struct SourceData {
     pub kind: SourceDataKind,
     pub status: RwLock<SourceDataStatus>,
+    pub trust: SourceTrust,
 }
 
 impl SourceData {
@@ -57,6 +98,7 @@ impl SourceData {
         Ok(SourceData {
             kind: SourceDataKind::Path(Arc::new(path.into())),
             status: RwLock::new(SourceDataStatus::Vacant),
+            trust: SourceTrust::Registered,
         })
     }
 }
@@ -66,22 +108,152 @@ impl Clone for SourceData {
         Self {
             kind: self.kind.clone(),
             status: RwLock::new(self.status.read().unwrap().clone()),
+            trust: self.trust,
         }
     }
 }
 
+/// Platform font source that can be shared between threads.
+///
+/// Some backends (fontconfig in particular) hand out raw handles that are
+/// not marked thread safe, so all access is serialized through a mutex. The
+/// source is created on first use so that collections which never consult
+/// the platform do not initialize it.
+pub struct SharedSystemSource(Mutex<SourceState>);
+
+enum SourceState {
+    Uninitialized,
+    Ready(SystemSource),
+    Failed(String),
+}
+
+// The wrapped source is only ever accessed while holding the mutex.
+unsafe impl Send for SharedSystemSource {}
+unsafe impl Sync for SharedSystemSource {}
+
+impl SharedSystemSource {
+    pub fn new() -> Self {
+        Self(Mutex::new(SourceState::Uninitialized))
+    }
+
+    /// Invokes the closure with the platform source, creating it if
+    /// necessary. Returns `None` if the source failed to initialize.
+    pub fn with<R>(&self, f: impl FnOnce(&mut SystemSource) -> R) -> Option<R> {
+        let mut state = self.lock();
+        match &mut *state {
+            SourceState::Ready(source) => Some(f(source)),
+            _ => None,
+        }
+    }
+
+    /// Returns the status of the platform source, creating it if necessary.
+    pub fn status(&self) -> BackendStatus {
+        match &*self.lock() {
+            SourceState::Failed(message) => BackendStatus::Failed(message.clone()),
+            _ => BackendStatus::Available,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SourceState> {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let SourceState::Uninitialized = *state {
+            *state = init_system_source();
+        }
+        state
+    }
+}
+
+/// Creates the platform source and verifies that it is able to enumerate
+/// fonts. Backends that fail to load, such as a fontconfig with a broken
+/// configuration, otherwise behave as if no fonts were installed.
+fn init_system_source() -> SourceState {
+    let source = match std::panic::catch_unwind(SystemSource::new) {
+        Ok(source) => source,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown error".into());
+            return SourceState::Failed(format!(
+                "failed to initialize the platform font source: {}",
+                message
+            ));
+        }
+    };
+    match source.all_families() {
+        Ok(families) if !families.is_empty() => SourceState::Ready(source),
+        Ok(_) => SourceState::Failed("the platform font source reported no families".into()),
+        Err(err) => SourceState::Failed(format!("failed to enumerate font families: {}", err)),
+    }
+}
+
 #[derive(Clone)]
 pub struct CollectionData {
-    pub system_source: Arc<SystemSource>,
+    pub system_source: Arc<SharedSystemSource>,
     pub is_user: bool,
     pub families: Vec<Arc<FamilyData>>,
     pub fonts: Vec<FontData>,
     pub sources: Vec<SourceData>,
     pub family_map: HashMap<Arc<str>, Option<FamilyId>>,
+    /// All families keyed by lowercase name and root priority, including
+    /// those shadowed in the family map.
+    pub family_roots: HashMap<(Arc<str>, SourcePriority), FamilyId>,
     pub default_families: Vec<FamilyId>,
     pub generic_families: [Vec<FamilyId>; GENERIC_FAMILY_COUNT],
     pub cjk_families: [Vec<FamilyId>; CJK_FAMILY_COUNT],
     pub script_fallbacks: HashMap<[u8; 4], Vec<FamilyId>>,
+    pub verified_fallbacks: HashSet<(FamilyId, Script)>,
+    /// Positions of families in the fallback chains preferred by the
+    /// platform configuration. Ranked families precede all others.
+    pub fallback_ranks: HashMap<(FamilyId, Script, Cjk), usize>,
+    /// Families listed for fallback by the platform defaults. These keep
+    /// their listed order ahead of families discovered while scanning.
+    pub curated_fallbacks: Vec<FamilyId>,
+    /// Fallback chains extended with discovered families since they were
+    /// last ranked.
+    pub unranked_fallbacks: HashSet<(Script, Cjk)>,
+    /// Fallback chains configured when building the library, which are not
+    /// extended with families discovered later.
+    pub fixed_fallbacks: HashSet<(Script, Cjk)>,
+    /// Query for the families the platform prefers for a language.
+    pub platform_fallback_query: Option<fn(&str) -> Vec<String>>,
+    /// Fallback chains whose platform preferences have not been queried
+    /// yet. Each chain is queried when it is first needed.
+    pub pending_platform_fallbacks: HashSet<(Script, Cjk)>,
+    /// Fallback chains configured for scripts in particular languages and
+    /// regions, which take precedence over the chains for the scripts.
+    pub locale_fallbacks: Vec<(LanguageTag, Script, Vec<FamilyId>)>,
+    pub metrics_policy: MetricsPolicy,
+    pub scan_cache: Option<Arc<Mutex<ScanCache>>>,
+    /// Check that files backing sources are unchanged before reusing
+    /// previously loaded data.
+    pub verify_sources: bool,
+    /// Consult the platform for families that have not been added
+    /// explicitly.
+    pub system_fonts: bool,
+    /// True if the platform font directories have been scanned because the
+    /// platform source is unavailable.
+    pub scanned_font_dirs: bool,
+    /// True if the directories listed by the environment have been scanned.
+    pub scanned_extra_font_dirs: bool,
+    /// Merge families named with a weight suffix, such as "Roboto Thin",
+    /// into the base family.
+    pub merge_weight_families: bool,
+    pub duplicate_policy: DuplicatePolicy,
+    pub emoji_preference: EmojiPreference,
+    /// True if the default families were synthesized because none of the
+    /// platform defaults were found.
+    pub synthesized_default: bool,
+    /// Generic families that were synthesized because none of the platform
+    /// families for them were found.
+    pub synthesized_generics: [bool; GENERIC_FAMILY_COUNT],
+    /// File system from which path based sources are scanned and loaded.
+    pub file_system: Arc<dyn FileSystem>,
+    /// Scanners reused when fonts are added.
+    pub scanners: Arc<ScannerPool>,
+    /// Faces rejected as malformed while scanning.
+    pub rejected_faces: Vec<RejectedFace>,
 }
 
 impl Default for CollectionData {
@@ -93,16 +265,39 @@ impl Default for CollectionData {
 impl CollectionData {
     pub fn new() -> Self {
         Self {
-            system_source: Arc::new(SystemSource::new()),
+            system_source: Arc::new(SharedSystemSource::new()),
             is_user: false,
             families: Vec::new(),
             fonts: Vec::new(),
             sources: Vec::new(),
             family_map: HashMap::new(),
+            family_roots: HashMap::new(),
             default_families: Vec::new(),
             generic_families: Default::default(),
             cjk_families: Default::default(),
             script_fallbacks: HashMap::new(),
+            verified_fallbacks: HashSet::new(),
+            fallback_ranks: HashMap::new(),
+            curated_fallbacks: Vec::new(),
+            unranked_fallbacks: HashSet::new(),
+            fixed_fallbacks: HashSet::new(),
+            platform_fallback_query: None,
+            pending_platform_fallbacks: HashSet::new(),
+            locale_fallbacks: Vec::new(),
+            metrics_policy: MetricsPolicy::default(),
+            scan_cache: None,
+            verify_sources: false,
+            system_fonts: true,
+            scanned_font_dirs: false,
+            scanned_extra_font_dirs: false,
+            merge_weight_families: false,
+            duplicate_policy: DuplicatePolicy::default(),
+            emoji_preference: EmojiPreference::default(),
+            synthesized_default: false,
+            synthesized_generics: [false; GENERIC_FAMILY_COUNT],
+            file_system: Arc::new(OsFileSystem),
+            scanners: Arc::new(ScannerPool::new()),
+            rejected_faces: Vec::new(),
         }
     }
 
@@ -110,22 +305,59 @@ impl CollectionData {
         let mut lowercase_buf = LowercaseString::new();
         let lowercase_name = lowercase_buf.get(name)?;
 
-        if !self.family_map.contains_key(lowercase_name) {
-            if let Ok(handle) = self.system_source.select_family_by_name(name) {
-                for font in handle.fonts() {
-                    match font {
-                        Handle::Path { path, font_index } => {
-                            scan_path(path, self);
+        if self.system_fonts && !self.family_map.contains_key(lowercase_name) {
+            let handle = self
+                .system_source
+                .with(|source| source.select_family_by_name(name));
+            match handle {
+                Some(Ok(handle)) => {
+                    let mut paths = Vec::new();
+                    let mut buffers: Vec<&Arc<Vec<u8>>> = Vec::new();
+                    for font in handle.fonts() {
+                        match font {
+                            Handle::Path { path, .. } => paths.push(path),
+                            // Faces of a collection share the same buffer,
+                            // which is scanned once for all of them.
+                            Handle::Memory { bytes, .. } => {
+                                if !buffers.iter().any(|buffer| Arc::ptr_eq(buffer, bytes)) {
+                                    buffers.push(bytes);
+                                }
+                            }
                         }
-                        Handle::Memory {
-                            bytes: _,
-                            font_index: _,
-                        } => {}
+                    }
+                    let (user, system): (Vec<_>, Vec<_>) = paths.into_iter().partition(|path| {
+                        super::system::dir_priority(path) == SourcePriority::User
+                    });
+                    let _ = scan_paths(&system, SourcePriority::System, self);
+                    let _ = scan_paths(&user, SourcePriority::User, self);
+                    for bytes in buffers {
+                        let data = super::font::FontData::new(bytes.as_ref().clone());
+                        let source = SourceData {
+                            kind: SourceDataKind::Data(data.clone()),
+                            status: RwLock::new(SourceDataStatus::Vacant),
+                            trust: SourceTrust::System,
+                        };
+                        self.add_fonts(data, source, None);
+                    }
+                }
+                Some(Err(_)) => {
+                    // The family may be installed in a directory that the
+                    // platform does not know about.
+                    self.scan_extra_font_dirs();
+                    if !self.family_map.contains_key(lowercase_name) {
+                        self.family_map.insert(lowercase_name.into(), None);
+                    }
+                }
+                None => {
+                    // The platform source is unavailable, so locate fonts by
+                    // scanning the platform font directories instead.
+                    self.scan_font_dirs();
+                    if !self.family_map.contains_key(lowercase_name) {
+                        self.family_map.insert(lowercase_name.into(), None);
                     }
                 }
-            } else {
-                self.family_map.insert(name.into(), None);
             }
+            self.rank_fallbacks();
         }
 
         if let Some(family_id) = self.family_map.get(lowercase_name) {
@@ -135,15 +367,88 @@ impl CollectionData {
         }
     }
 
+    /// Scans every font in the platform font directories and the directories
+    /// listed by the environment. This only happens once per collection.
+    ///
+    /// iOS does not allow enumerating the font directories, so the files of
+    /// the fonts known to Core Text are scanned instead.
+    fn scan_font_dirs(&mut self) {
+        if self.scanned_font_dirs {
+            return;
+        }
+        self.scanned_font_dirs = true;
+        #[cfg(target_os = "ios")]
+        {
+            let _ = scan_paths(
+                &super::system::coretext_font_paths(),
+                SourcePriority::System,
+                self,
+            );
+        }
+        for dir in super::system::font_dirs() {
+            if self.file_system.metadata(&dir).is_ok_and(|m| m.is_dir) {
+                let priority = super::system::dir_priority(&dir);
+                let _ = scan_paths(&[dir], priority, self);
+            }
+        }
+        self.scan_extra_font_dirs();
+    }
+
+    /// Scans the fonts in the directories listed by the environment. See
+    /// [`extra_font_dirs`](super::system::extra_font_dirs).
+    ///
+    /// This happens once per collection, when the platform fails to locate
+    /// a family, rather than while building since the directories may hold
+    /// many fonts.
+    fn scan_extra_font_dirs(&mut self) {
+        if self.scanned_extra_font_dirs || !self.system_fonts {
+            return;
+        }
+        self.scanned_extra_font_dirs = true;
+        for dir in super::system::extra_font_dirs() {
+            if self.file_system.metadata(&dir).is_ok_and(|m| m.is_dir) {
+                let _ = scan_paths(&[dir], SourcePriority::System, self);
+            }
+        }
+    }
+
+    /// Returns the identifier of a family that has already been resolved
+    /// without consulting the platform.
+    pub fn find_family_id(&self, name: &str) -> Option<FamilyId> {
+        let mut lowercase_buf = LowercaseString::new();
+        let lowercase_name = lowercase_buf.get(name)?;
+        self.family_map.get(lowercase_name).copied().flatten()
+    }
+
     pub fn family(&self, id: FamilyId) -> Option<FamilyEntry> {
         let family = self.families.get(id.to_usize())?;
         Some(FamilyEntry {
             id,
             has_stretch: family.has_stretch,
+            capabilities: family.capabilities,
             kind: FontFamilyKind::Dynamic(family.clone()),
         })
     }
 
+    pub fn family_ref(&self, id: FamilyId) -> Option<FamilyRef<'_>> {
+        let family = self.families.get(id.to_usize())?;
+        Some(FamilyRef {
+            id,
+            name: &family.name,
+            has_stretch: family.has_stretch,
+            capabilities: family.capabilities,
+            fonts: &family.fonts,
+        })
+    }
+
+    /// Returns true if the attributes of the fonts in a family have not
+    /// been refined yet.
+    pub fn needs_refinement(&self, id: FamilyId) -> bool {
+        self.families
+            .get(id.to_usize())
+            .is_some_and(|family| !family.refined)
+    }
+
     pub fn family_by_name(&mut self, name: &str) -> Option<FamilyEntry> {
         let family_id = self.family_id(name)?;
         self.family(family_id)
@@ -160,21 +465,93 @@ impl CollectionData {
         &self.default_families
     }
 
-    pub fn fallback_families(&mut self, script: Script, locale: Option<Locale>) -> &[FamilyId] {
+    pub fn cjk_families(&self, cjk: Cjk) -> &[FamilyId] {
+        &self.cjk_families[cjk as usize]
+    }
+
+    /// Returns the fallback chain for the script. This is empty for scripts
+    /// without a chain since queries append the default families anyway.
+    pub fn fallback_families(&self, script: Script, locale: Option<Locale>) -> &[FamilyId] {
+        if let Some(families) = self.locale_fallbacks(script, locale) {
+            return families;
+        }
         if script == Script::Han {
             let cjk = locale.map(|l| l.cjk()).unwrap_or(Cjk::None);
             return &self.cjk_families[cjk as usize];
         }
 
         let tag = super::script_tags::script_tag(script);
-        let entry = self.script_fallbacks.entry(tag).or_default();
-        match self.script_fallbacks.get(&tag) {
-            Some(families) => {
-                // println!("families for {script:?} {families:?}");
-                families
+        self.script_fallbacks
+            .get(&tag)
+            .map(|families| families.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the most specific chain configured for a language tag that
+    /// matches the locale.
+    fn locale_fallbacks(&self, script: Script, locale: Option<Locale>) -> Option<&[FamilyId]> {
+        if self.locale_fallbacks.is_empty() {
+            return None;
+        }
+        let locale = locale?;
+        let mut best: Option<(usize, &[FamilyId])> = None;
+        for (tag, chain_script, families) in &self.locale_fallbacks {
+            if *chain_script != script || families.is_empty() {
+                continue;
+            }
+            if let Some(specificity) = tag.match_locale(&locale) {
+                if best.is_none_or(|(best, _)| specificity > best) {
+                    best = Some((specificity, families));
+                }
             }
-            _ => &self.default_families,
         }
+        best.map(|(_, families)| families)
+    }
+
+    /// Returns true if the script has a fallback chain rather than falling
+    /// back to the default families.
+    pub fn has_script_fallbacks(&self, script: Script) -> bool {
+        if self.needs_platform_fallbacks(script, Cjk::None) {
+            return true;
+        }
+        if script == Script::Han {
+            return !self.cjk_families[Cjk::None as usize].is_empty();
+        }
+        let tag = super::script_tags::script_tag(script);
+        self.script_fallbacks.contains_key(&tag)
+    }
+
+    pub fn fallback_candidates(
+        &self,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Vec<FallbackCandidate> {
+        let declared = if let Some(families) = self.locale_fallbacks(script, locale) {
+            families
+        } else if script == Script::Han {
+            let cjk = locale.map(|l| l.cjk()).unwrap_or(Cjk::None);
+            &self.cjk_families[cjk as usize][..]
+        } else {
+            let tag = super::script_tags::script_tag(script);
+            self.script_fallbacks
+                .get(&tag)
+                .map(|families| families.as_slice())
+                .unwrap_or(&[])
+        };
+        if declared.is_empty() {
+            return FallbackCandidate::defaults(&self.default_families);
+        }
+        declared
+            .iter()
+            .map(|&family| FallbackCandidate {
+                family,
+                reason: if self.verified_fallbacks.contains(&(family, script)) {
+                    FallbackReason::CoverageVerified
+                } else {
+                    FallbackReason::Declared
+                },
+            })
+            .collect()
     }
 
     fn find_family(&mut self, families: &[&str]) -> Vec<FamilyId> {
@@ -187,11 +564,27 @@ impl CollectionData {
         family_ids
     }
 
-    pub fn setup_default(&mut self) {
+    /// Resolves the default families, preferring the specified names and
+    /// using the platform defaults if none of them are found.
+    pub fn setup_default(&mut self, names: &[String]) {
         use super::system::*;
+        let mut families = Vec::new();
+        for name in names {
+            if let Some(id) = self.family_id(name) {
+                if !families.contains(&id) {
+                    families.push(id);
+                }
+            }
+        }
+        if !families.is_empty() {
+            self.default_families = families;
+            return;
+        }
         let families = match OS {
             Os::Windows => self.find_family(&["segoe ui"]),
             Os::MacOs => self.find_family(&["helvetica"]),
+            Os::Ios => self.find_family(&["helvetica neue", "helvetica"]),
+            Os::Bsd => self.find_family(&["dejavu sans", "liberation sans", "noto sans"]),
             _ => self.find_family(&["Cantarell Regular", "liberation serif", "dejavu serif"]),
         };
         self.default_families = families;
@@ -208,6 +601,11 @@ impl CollectionData {
                 self.generic_families[Cursive as usize] = self.find_family(&["comic sans ms"]);
                 self.generic_families[SystemUi as usize] = self.find_family(&["segoe ui"]);
                 self.generic_families[Emoji as usize] = self.find_family(&["segoe ui emoji"]);
+                self.generic_families[Fantasy as usize] = self.find_family(&["impact"]);
+                self.generic_families[UiSerif as usize] = self.find_family(&["cambria"]);
+                self.generic_families[UiSansSerif as usize] = self.find_family(&["segoe ui"]);
+                self.generic_families[UiMonospace as usize] =
+                    self.find_family(&["cascadia mono", "consolas"]);
             }
             Os::MacOs => {
                 self.generic_families[SansSerif as usize] = self.find_family(&["helvetica"]);
@@ -216,6 +614,34 @@ impl CollectionData {
                 self.generic_families[Cursive as usize] = self.find_family(&["apple chancery"]);
                 self.generic_families[SystemUi as usize] = self.find_family(&["helvetica"]);
                 self.generic_families[Emoji as usize] = self.find_family(&["apple color emoji"]);
+                self.generic_families[Fantasy as usize] = self.find_family(&["papyrus"]);
+                self.generic_families[UiSerif as usize] = self.find_family(&["new york"]);
+                self.generic_families[UiSansSerif as usize] =
+                    self.find_family(&["sf pro", "helvetica neue"]);
+                self.generic_families[UiMonospace as usize] =
+                    self.find_family(&["sf mono", "menlo"]);
+                self.generic_families[UiRounded as usize] = self.find_family(&["sf pro rounded"]);
+            }
+            Os::Ios => {
+                self.generic_families[SansSerif as usize] =
+                    self.find_family(&["helvetica neue", "helvetica"]);
+                self.generic_families[Serif as usize] =
+                    self.find_family(&["times new roman", "georgia"]);
+                self.generic_families[Monospace as usize] =
+                    self.find_family(&["menlo", "courier new", "courier"]);
+                self.generic_families[Cursive as usize] =
+                    self.find_family(&["snell roundhand", "noteworthy"]);
+                self.generic_families[SystemUi as usize] =
+                    self.find_family(&["helvetica neue", "helvetica"]);
+                self.generic_families[Emoji as usize] = self.find_family(&["apple color emoji"]);
+                self.generic_families[Fantasy as usize] = self.find_family(&["papyrus"]);
+                self.generic_families[UiSerif as usize] =
+                    self.find_family(&["new york", "georgia"]);
+                self.generic_families[UiSansSerif as usize] =
+                    self.find_family(&["sf pro text", "helvetica neue"]);
+                self.generic_families[UiMonospace as usize] =
+                    self.find_family(&["sf mono", "menlo"]);
+                self.generic_families[UiRounded as usize] = self.find_family(&["sf pro rounded"]);
             }
             _ => {
                 self.generic_families[SansSerif as usize] = self.find_family(&["sans-serif"]);
@@ -230,48 +656,448 @@ impl CollectionData {
                 ]);
                 self.generic_families[Emoji as usize] =
                     self.find_family(&["noto color emoji", "emoji one"]);
+                self.generic_families[Fantasy as usize] = self.find_family(&["fantasy"]);
+            }
+        }
+        // The interface generics fall back to the generics they refine, as
+        // most platforms only provide some of them.
+        for (generic, base) in [
+            (UiSerif, Serif),
+            (UiSansSerif, SystemUi),
+            (UiMonospace, Monospace),
+            (UiRounded, SystemUi),
+        ] {
+            if self.generic_families[generic as usize].is_empty() {
+                self.generic_families[generic as usize] =
+                    self.generic_families[base as usize].clone();
+            }
+        }
+        // Only fonts with a math table can lay out formulas.
+        let mut math = match OS {
+            Os::Windows => self.find_family(&["cambria math"]),
+            Os::MacOs | Os::Ios => self.find_family(&["stix two math"]),
+            _ => self.find_family(&[
+                "stix two math",
+                "latin modern math",
+                "libertinus math",
+                "dejavu math tex gyre",
+                "noto sans math",
+                "xits math",
+            ]),
+        };
+        math.retain(|id| {
+            self.families
+                .get(id.to_usize())
+                .is_some_and(|family| family.capabilities.has_math())
+        });
+        self.generic_families[Math as usize] = math;
+        if self.emoji_preference == EmojiPreference::Monochrome {
+            let mut families = match OS {
+                Os::Windows => self.find_family(&["segoe ui symbol"]),
+                Os::MacOs | Os::Ios => self.find_family(&["apple symbols"]),
+                _ => self.find_family(&["noto emoji", "symbola"]),
+            };
+            // Color fonts follow for emoji missing from the symbol fonts.
+            for id in &self.generic_families[Emoji as usize] {
+                if !families.contains(id) {
+                    families.push(*id);
+                }
+            }
+            self.generic_families[Emoji as usize] = families;
+        }
+    }
+
+    /// Places the interface and monospace fonts configured by the desktop
+    /// environment at the front of the system-ui, ui-sans-serif, monospace
+    /// and ui-monospace generic families.
+    #[cfg(feature = "desktop-settings")]
+    pub fn setup_desktop_generic(&mut self) {
+        use super::system::*;
+        if !matches!(OS, Os::Unix | Os::Bsd) || !self.system_fonts {
+            return;
+        }
+        let fonts = desktop_fonts();
+        let settings = [
+            (GenericFamily::SystemUi, fonts.interface.clone()),
+            (GenericFamily::UiSansSerif, fonts.interface),
+            (GenericFamily::Monospace, fonts.monospace.clone()),
+            (GenericFamily::UiMonospace, fonts.monospace),
+        ];
+        for (generic, name) in settings {
+            let id = match name.and_then(|name| self.family_id(&name)) {
+                Some(id) => id,
+                None => continue,
+            };
+            let families = &mut self.generic_families[generic as usize];
+            families.retain(|family| *family != id);
+            families.insert(0, id);
+        }
+    }
+
+    /// Resolves names that do not name an installed family to the first
+    /// family found with one of the target names. As with localized names,
+    /// an alias resolves to the family with its name once one is added.
+    pub fn setup_aliases(&mut self, aliases: &[(String, Vec<String>)]) {
+        for (alias, names) in aliases {
+            if self.family_id(alias).is_some() {
+                continue;
+            }
+            let target = names.iter().find_map(|name| self.family_id(name));
+            let mut lowercase_buf = LowercaseString::new();
+            if let (Some(id), Some(alias)) = (target, lowercase_buf.get(alias)) {
+                self.family_map.insert(alias.into(), Some(id));
+            }
+        }
+    }
+
+    /// Replaces the families of generic families with the specified names.
+    /// Generic families for which none of the names are found keep the
+    /// platform families.
+    pub fn setup_custom_generic(&mut self, generics: &[(GenericFamily, Vec<String>)]) {
+        for (generic, names) in generics {
+            let mut families = Vec::new();
+            for name in names {
+                if let Some(id) = self.family_id(name) {
+                    if !families.contains(&id) {
+                        families.push(id);
+                    }
+                }
+            }
+            if !families.is_empty() {
+                self.generic_families[*generic as usize] = families;
+            }
+        }
+    }
+
+    /// Replaces the fallback chains of scripts with the families with the
+    /// specified names. Chains for which none of the names are found keep
+    /// the families discovered during setup.
+    pub fn setup_custom_fallbacks(&mut self, fallbacks: &[(Script, Cjk, Vec<String>)]) {
+        for (script, cjk, names) in fallbacks {
+            let mut families = Vec::new();
+            for name in names {
+                if let Some(id) = self.family_id(name) {
+                    if !families.contains(&id) {
+                        families.push(id);
+                    }
+                }
+            }
+            if families.is_empty() {
+                continue;
+            }
+            if *script == Script::Han {
+                self.cjk_families[*cjk as usize] = families;
+            } else {
+                let tag = super::script_tags::script_tag(*script);
+                self.script_fallbacks.insert(tag, families);
+            }
+            self.fixed_fallbacks.insert((*script, *cjk));
+        }
+    }
+
+    /// Resolves the fallback chains configured for language tags. Chains for
+    /// which none of the names are found are ignored.
+    pub fn setup_locale_fallbacks(&mut self, fallbacks: &[(LanguageTag, Script, Vec<String>)]) {
+        for (tag, script, names) in fallbacks {
+            let mut families = Vec::new();
+            for name in names {
+                if let Some(id) = self.family_id(name) {
+                    if !families.contains(&id) {
+                        families.push(id);
+                    }
+                }
+            }
+            if !families.is_empty() {
+                self.locale_fallbacks.push((tag.clone(), *script, families));
+            }
+        }
+    }
+
+    /// Fills the default families and any generic families for which none
+    /// of the platform families were found with installed families
+    /// classified as sans serif, serif or monospace. Only families with
+    /// color glyphs are chosen for emoji and only families with a math table
+    /// for math.
+    ///
+    /// This does nothing when the default, serif, sans serif and monospace
+    /// families were all found, since listing the installed families is
+    /// expensive and the remaining generic families are optional. Without
+    /// system fonts, only the families already loaded, such as those added
+    /// to the builder, are classified.
+    pub fn synthesize_defaults(&mut self) {
+        use GenericFamily::*;
+        let generics_found = [Serif, SansSerif, Monospace]
+            .iter()
+            .all(|generic| !self.generic_families[*generic as usize].is_empty());
+        if !self.default_families.is_empty() && generics_found {
+            return;
+        }
+        if self.system_fonts {
+            self.load_classified_families();
+        }
+        let any_text = self
+            .families
+            .iter()
+            .position(|family| !family.fonts.is_empty() && !family.capabilities.has_color())
+            .and_then(|index| FamilyId::alloc(index, self.is_user));
+        let sans = self.classified_family(FamilyClass::Sans);
+        let serif = self.classified_family(FamilyClass::Serif);
+        let mono = self.classified_family(FamilyClass::Mono);
+        if self.default_families.is_empty() {
+            if let Some(id) = sans.or(serif).or(any_text).or(mono) {
+                self.default_families.push(id);
+                self.synthesized_default = true;
+            }
+        }
+        let default = self.default_families.first().copied();
+        for generic in GenericFamily::ALL {
+            if !self.generic_families[generic as usize].is_empty() {
+                continue;
+            }
+            let id = match generic {
+                Serif | UiSerif => serif.or(default),
+                SansSerif | UiSansSerif => sans.or(default),
+                // A proportional default would misalign text that expects
+                // fixed pitch, so monospace is left missing instead.
+                Monospace | UiMonospace => mono,
+                Emoji => self.family_with(|caps| caps.has_color()),
+                Math => self.family_with(|caps| caps.has_math()),
+                SystemUi | Cursive | Fantasy | UiRounded => default,
+            };
+            if let Some(id) = id {
+                self.generic_families[generic as usize].push(id);
+                self.synthesized_generics[generic as usize] = true;
+            }
+        }
+    }
+
+    /// Loads a platform family of each class that is not yet loaded so that
+    /// there is something to choose from, falling back to scanning the font
+    /// directories when the platform provides no families at all.
+    fn load_classified_families(&mut self) {
+        let names = self
+            .system_source
+            .with(|source| source.all_families().unwrap_or_default())
+            .unwrap_or_default();
+        for class in [FamilyClass::Sans, FamilyClass::Serif, FamilyClass::Mono] {
+            if self.classified_family(class).is_some() {
+                continue;
+            }
+            // Names are only a hint, so a family named as monospace must
+            // also be fixed pitch.
+            for name in names
+                .iter()
+                .filter(|name| FamilyClass::of_name(name) == Some(class))
+            {
+                if self.family_id(name).is_some() && self.classified_family(class).is_some() {
+                    break;
+                }
             }
         }
+        if !self.families.iter().any(|family| !family.fonts.is_empty()) {
+            self.scan_font_dirs();
+        }
+    }
+
+    /// Returns the first loaded family with the specified capabilities.
+    fn family_with(&self, f: impl Fn(Capabilities) -> bool) -> Option<FamilyId> {
+        let index = self
+            .families
+            .iter()
+            .position(|family| !family.fonts.is_empty() && f(family.capabilities))?;
+        FamilyId::alloc(index, self.is_user)
+    }
+
+    /// Returns the first loaded family of the specified class.
+    fn classified_family(&self, class: FamilyClass) -> Option<FamilyId> {
+        let index = self.families.iter().position(|family| {
+            !family.fonts.is_empty()
+                && !family.capabilities.has_color()
+                && FamilyClass::of_family(family) == Some(class)
+        })?;
+        FamilyId::alloc(index, self.is_user)
+    }
+
+    /// Returns whether the default families were found on the platform,
+    /// synthesized or are missing.
+    pub fn default_status(&self) -> SetupStatus {
+        setup_status(&self.default_families, self.synthesized_default)
+    }
+
+    /// Returns whether the specified generic family was found on the
+    /// platform, synthesized or is missing.
+    pub fn generic_status(&self, generic: GenericFamily) -> SetupStatus {
+        setup_status(
+            &self.generic_families[generic as usize],
+            self.synthesized_generics[generic as usize],
+        )
     }
 
     /// When we do find_family, these fonts will be added to fallbacks in scan_font
+    ///
+    /// The families found are curated and keep the listed order at the
+    /// front of the chains of the scripts they support.
     pub fn setup_fallbacks(&mut self) {
         use super::system::*;
-        match OS {
-            Os::Windows => {
-                let _ = self.find_family(&[
-                    "microsoft yahei",
-                    "simsun",
-                    "simsun-extb",
-                    "meiryo",
-                    "yu gothic",
-                    "microsoft jhenghei",
-                    "pmingliu",
-                    "pmingliu-extb",
-                    "malgun gothic",
-                    "gulim",
-                ]);
+        self.curated_fallbacks = match OS {
+            Os::Windows => self.find_family(&[
+                "microsoft yahei",
+                "simsun",
+                "simsun-extb",
+                "meiryo",
+                "yu gothic",
+                "microsoft jhenghei",
+                "pmingliu",
+                "pmingliu-extb",
+                "malgun gothic",
+                "gulim",
+            ]),
+            Os::MacOs => self.find_family(&[
+                "pingfang sc",
+                "geeza pro",
+                "hiragino maru gothic pron w4",
+                "hiragino kaku gothic pron w3",
+                "apple sd gothic neo",
+                "Menlo",
+                "STIXGeneral",
+            ]),
+            Os::Ios => self.find_family(&[
+                "pingfang sc",
+                "pingfang tc",
+                "pingfang hk",
+                "hiragino sans",
+                "apple sd gothic neo",
+                "geeza pro",
+                "thonburi",
+                "kohinoor devanagari",
+            ]),
+            Os::Bsd => self.find_family(&[
+                "Noto Sans CJK SC",
+                "Noto Sans CJK TC",
+                "Noto Sans CJK JP",
+                "Noto Sans CJK KR",
+                "wenquanyi zen hei",
+                "ipagothic",
+                "ipaexgothic",
+                "undotum",
+                "droid sans fallback",
+            ]),
+            _ => self.find_family(&[
+                "Noto Sans CJK SC",
+                "Noto Sans CJK TC",
+                "Noto Sans CJK JP",
+                "Noto Sans CJK KR",
+            ]),
+        };
+        // Chains that gained curated families were ranked as they were
+        // found, so rank them again now that the families are known.
+        for script in (0..).map_while(super::script_tags::script_from_index) {
+            if script == Script::Han {
+                for cjk in CJK_LOCALES {
+                    self.unranked_fallbacks.insert((script, cjk));
+                }
+            } else {
+                self.unranked_fallbacks.insert((script, Cjk::None));
             }
-            Os::MacOs => {
-                let _ = self.find_family(&[
-                    "pingfang sc",
-                    "geeza pro",
-                    "hiragino maru gothic pron w4",
-                    "hiragino kaku gothic pron w3",
-                    "apple sd gothic neo",
-                    "Menlo",
-                    "STIXGeneral",
-                ]);
+        }
+        self.rank_fallbacks();
+    }
+
+    /// Ranks the families that fontconfig prefers for each script at the
+    /// front of the fallback chains so that fallback honors the user's
+    /// fontconfig configuration.
+    #[cfg(feature = "fontconfig")]
+    pub fn setup_fontconfig_fallbacks(&mut self) {
+        use super::system::*;
+        if !matches!(OS, Os::Unix | Os::Bsd) || !self.system_fonts {
+            return;
+        }
+        self.defer_platform_fallbacks(fontconfig_families);
+    }
+
+    /// Ranks the families in the Core Text cascade lists for each script at
+    /// the front of the fallback chains so that fallback matches the rest of
+    /// the system.
+    #[cfg(all(feature = "coretext", target_os = "macos"))]
+    pub fn setup_coretext_fallbacks(&mut self) {
+        if !self.system_fonts {
+            return;
+        }
+        self.defer_platform_fallbacks(super::system::coretext_cascade);
+    }
+
+    /// Registers a query for the families that the platform prefers for the
+    /// language of each script in [`PLATFORM_FALLBACK_LANGUAGES`].
+    ///
+    /// Queries spawn processes or call into the platform and resolving the
+    /// families they return loads fonts, so each chain is only queried when
+    /// fallback for it is first requested.
+    #[cfg(any(feature = "fontconfig", all(feature = "coretext", target_os = "macos")))]
+    fn defer_platform_fallbacks(&mut self, query: fn(&str) -> Vec<String>) {
+        self.platform_fallback_query = Some(query);
+        for &(script, cjk, _) in PLATFORM_FALLBACK_LANGUAGES {
+            if !self.fixed_fallbacks.contains(&(script, cjk)) {
+                self.pending_platform_fallbacks.insert((script, cjk));
             }
-            _ => {
-                let _ = self.find_family(&[
-                    "Noto Sans CJK SC",
-                    "Noto Sans CJK TC",
-                    "Noto Sans CJK JP",
-                    "Noto Sans CJK KR",
-                ]);
+        }
+    }
+
+    /// Returns true if the platform has not been queried yet for the
+    /// fallback chain of the script and CJK locale.
+    pub fn needs_platform_fallbacks(&self, script: Script, cjk: Cjk) -> bool {
+        !self.pending_platform_fallbacks.is_empty()
+            && self.pending_platform_fallbacks.contains(&(script, cjk))
+    }
+
+    /// Ranks the families returned by the platform at the front of the
+    /// fallback chain of the script and CJK locale if it has not been
+    /// queried.
+    pub fn resolve_platform_fallbacks(&mut self, script: Script, cjk: Cjk) {
+        if self.pending_platform_fallbacks.remove(&(script, cjk)) {
+            self.query_platform_fallbacks((script, cjk));
+            self.rank_fallbacks();
+        }
+    }
+
+    /// Queries the platform for every chain that has not been queried.
+    pub fn resolve_all_platform_fallbacks(&mut self) {
+        for key in core::mem::take(&mut self.pending_platform_fallbacks) {
+            self.query_platform_fallbacks(key);
+        }
+        self.rank_fallbacks();
+    }
+
+    fn query_platform_fallbacks(&mut self, (script, cjk): (Script, Cjk)) {
+        #[cfg(any(feature = "fontconfig", all(feature = "coretext", target_os = "macos")))]
+        {
+            let query = match self.platform_fallback_query {
+                Some(query) => query,
+                None => return,
+            };
+            // Chains configured by the application are left as they are.
+            if self.fixed_fallbacks.contains(&(script, cjk)) {
+                return;
             }
+            let lang = match PLATFORM_FALLBACK_LANGUAGES
+                .iter()
+                .find(|entry| entry.0 == script && entry.1 == cjk)
+            {
+                Some(&(_, _, lang)) => lang,
+                None => return,
+            };
+            let mut rank = 0;
+            for name in query(lang) {
+                // Looking up the family adds it to the fallback chains of the
+                // scripts that it supports.
+                if let Some(id) = self.family_id(&name) {
+                    self.fallback_ranks.entry((id, script, cjk)).or_insert(rank);
+                    rank += 1;
+                }
+            }
+            self.unranked_fallbacks.insert((script, cjk));
         }
+        #[cfg(not(any(feature = "fontconfig", all(feature = "coretext", target_os = "macos"))))]
+        let _ = (script, cjk);
     }
 
     pub fn font(&self, id: FontId) -> Option<FontEntry> {
@@ -283,9 +1109,306 @@ impl CollectionData {
             index: font.index,
             attributes: font.attributes,
             cache_key: font.cache_key,
+            metrics: font.metrics,
+            metrics_policy: self.metrics_policy,
+            glyph_count: font.glyph_count,
+            file_size: font.file_size,
+            variant: font.variant,
+            optical_size: font.optical_size,
+            cjk: font.cjk,
+            color_format: font.color_format,
+            trust: self
+                .source_trust(font.source)
+                .unwrap_or(SourceTrust::Registered),
         })
     }
 
+    pub fn font_by_cache_key(&self, key: CacheKey) -> Option<FontId> {
+        let index = self.fonts.iter().position(|font| font.cache_key == key)?;
+        FontId::alloc(index, self.is_user)
+    }
+
+    pub fn coverage_cache(&self, id: FontId) -> Option<&CoverageCache> {
+        self.fonts.get(id.to_usize()).map(|font| &font.coverage)
+    }
+
+    pub fn instances(&self, id: FontId) -> Option<&Arc<[NamedInstance]>> {
+        self.fonts.get(id.to_usize()).map(|font| &font.instances)
+    }
+
+    pub fn axes(&self, id: FontId) -> Option<&Arc<[VariationAxis]>> {
+        self.fonts.get(id.to_usize()).map(|font| &font.axes)
+    }
+
+    /// Removes the fonts loaded from files at or below the specified path.
+    ///
+    /// Identifiers remain valid but the fonts are no longer reachable from
+    /// their families. Families left without fonts are removed from the
+    /// name map and all fallback lists so that they can be resolved again
+    /// if the fonts are reinstalled. Returns true if any fonts were removed.
+    pub fn remove_path(&mut self, path: &Path) -> CollectionDiff {
+        let removed_sources = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter(|(_, source)| match &source.kind {
+                SourceDataKind::Path(source_path) => source_path.starts_with(path),
+                _ => false,
+            })
+            .filter_map(|(index, _)| SourceId::alloc(index, self.is_user))
+            .collect::<HashSet<_>>();
+        let fonts = self
+            .fonts
+            .iter()
+            .enumerate()
+            .filter(|(_, font)| removed_sources.contains(&font.source))
+            .filter_map(|(index, _)| FontId::alloc(index, self.is_user))
+            .collect::<HashSet<_>>();
+        self.rejected_faces
+            .retain(|face| !face.path.as_ref().is_some_and(|p| p.starts_with(path)));
+        self.remove_fonts(&fonts)
+    }
+
+    /// Records faces rejected while scanning. A face of a file that was
+    /// already rejected replaces the earlier rejection, so rescanning a
+    /// file does not report it twice. Faces registered from memory have no
+    /// path and are always added.
+    pub fn add_rejected_faces(&mut self, faces: impl IntoIterator<Item = RejectedFace>) {
+        for face in faces {
+            let existing = match &face.path {
+                Some(_) => self
+                    .rejected_faces
+                    .iter()
+                    .position(|probe| probe.path == face.path && probe.index == face.index),
+                None => None,
+            };
+            match existing {
+                Some(index) => self.rejected_faces[index] = face,
+                None => self.rejected_faces.push(face),
+            }
+        }
+    }
+
+    /// Removes registered fonts and releases the memory held by their
+    /// sources once none of the fonts in a source remain.
+    pub fn unregister(&mut self, fonts: &[FontId]) -> CollectionDiff {
+        let fonts = fonts.iter().copied().collect::<HashSet<_>>();
+        let diff = self.remove_fonts(&fonts);
+        if diff.is_empty() {
+            return diff;
+        }
+        let sources = fonts
+            .iter()
+            .filter_map(|id| Some(self.fonts.get(id.to_usize())?.source))
+            .collect::<HashSet<_>>();
+        for source_id in sources {
+            let in_use = self.families.iter().any(|family| {
+                family.fonts.iter().any(|font| {
+                    self.fonts.get(font.0.to_usize()).map(|font| font.source) == Some(source_id)
+                })
+            });
+            if !in_use {
+                if let Some(source) = self.sources.get_mut(source_id.to_usize()) {
+                    source.kind = SourceDataKind::Removed;
+                    *source.status.write().unwrap() = SourceDataStatus::Vacant;
+                }
+            }
+        }
+        diff
+    }
+
+    /// Replaces a family with the family that shadows it in the default,
+    /// generic and fallback lists.
+    pub fn replace_family(&mut self, old: FamilyId, new: FamilyId) {
+        let replace = |families: &mut Vec<FamilyId>| {
+            if families.contains(&new) {
+                families.retain(|id| *id != old);
+            } else {
+                for id in families.iter_mut().filter(|id| **id == old) {
+                    *id = new;
+                }
+            }
+        };
+        replace(&mut self.default_families);
+        self.generic_families.iter_mut().for_each(replace);
+        self.cjk_families.iter_mut().for_each(replace);
+        self.script_fallbacks.values_mut().for_each(replace);
+        self.locale_fallbacks
+            .iter_mut()
+            .for_each(|(_, _, families)| replace(families));
+        let ranks = self
+            .fallback_ranks
+            .iter()
+            .filter(|((id, _, _), _)| *id == old)
+            .map(|(&(_, script, cjk), &rank)| ((new, script, cjk), rank))
+            .collect::<Vec<_>>();
+        for (key, rank) in ranks {
+            self.fallback_ranks.entry(key).or_insert(rank);
+        }
+    }
+
+    /// Removes the specified fonts from their families. Returns the fonts
+    /// that were removed and the families left empty.
+    fn remove_fonts(&mut self, removed_fonts: &HashSet<FontId>) -> CollectionDiff {
+        let mut diff = CollectionDiff::default();
+        if removed_fonts.is_empty() {
+            return diff;
+        }
+        let mut empty_families = HashSet::new();
+        for (index, family) in self.families.iter_mut().enumerate() {
+            let is_removed = |id: FontId| removed_fonts.contains(&id);
+            if !family.fonts.iter().any(|font| is_removed(font.0)) {
+                continue;
+            }
+            let family = Arc::make_mut(family);
+            diff.removed_fonts.extend(
+                family
+                    .fonts
+                    .iter()
+                    .map(|font| font.0)
+                    .filter(|id| is_removed(*id)),
+            );
+            family.fonts.retain(|font| !is_removed(font.0));
+            if family.fonts.is_empty() {
+                if let Some(id) = FamilyId::alloc(index, self.is_user) {
+                    empty_families.insert(id);
+                    diff.removed_families.push(id);
+                }
+            }
+        }
+        if !empty_families.is_empty() {
+            let is_live = |id: &FamilyId| !empty_families.contains(id);
+            self.family_roots.retain(|_, id| is_live(id));
+            let roots = &self.family_roots;
+            // Names resolved to a removed family fall back to the highest
+            // priority family with the same name that remains.
+            self.family_map.retain(|name, id| match id {
+                Some(family) if !is_live(family) => {
+                    *id = roots
+                        .iter()
+                        .filter(|((root_name, _), _)| root_name == name)
+                        .max_by_key(|((_, priority), _)| *priority)
+                        .map(|(_, id)| *id);
+                    id.is_some()
+                }
+                _ => true,
+            });
+            self.default_families.retain(is_live);
+            for families in self.generic_families.iter_mut() {
+                families.retain(is_live);
+            }
+            for families in self.cjk_families.iter_mut() {
+                families.retain(is_live);
+            }
+            for families in self.script_fallbacks.values_mut() {
+                families.retain(is_live);
+            }
+            for (_, _, families) in self.locale_fallbacks.iter_mut() {
+                families.retain(is_live);
+            }
+            self.verified_fallbacks.retain(|(id, _)| is_live(id));
+            self.fallback_ranks.retain(|(id, _, _), _| is_live(id));
+            self.curated_fallbacks.retain(is_live);
+        }
+        diff
+    }
+
+    /// Removes negative lookup results for the specified lowercase family
+    /// names so that newly installed fonts with those names can be added.
+    pub fn clear_missing(&mut self, names: impl Iterator<Item = String>) {
+        for name in names {
+            if let Some(None) = self.family_map.get(name.as_str()) {
+                self.family_map.remove(name.as_str());
+            }
+        }
+    }
+
+    /// Moves a family to the front of the fallback chain of a script, adding
+    /// it if necessary. Han edits the chains of all CJK locales. Returns true
+    /// if any chain changed.
+    pub fn prepend_fallback(&mut self, script: Script, family: FamilyId) -> bool {
+        self.edit_fallbacks(script, true, |chain| {
+            if chain.first() == Some(&family) {
+                return false;
+            }
+            chain.retain(|id| *id != family);
+            chain.insert(0, family);
+            true
+        })
+    }
+
+    /// Removes a family from the fallback chain of a script. Han edits the
+    /// chains of all CJK locales. Returns true if any chain changed.
+    pub fn remove_fallback(&mut self, script: Script, family: FamilyId) -> bool {
+        self.edit_fallbacks(script, false, |chain| {
+            let len = chain.len();
+            chain.retain(|id| *id != family);
+            chain.len() != len
+        })
+    }
+
+    /// Applies an edit to the fallback chains of a script. Edited chains are
+    /// fixed so that families discovered later do not undo the edit.
+    fn edit_fallbacks(
+        &mut self,
+        script: Script,
+        create: bool,
+        mut edit: impl FnMut(&mut Vec<FamilyId>) -> bool,
+    ) -> bool {
+        let mut changed = false;
+        if script == Script::Han {
+            for (cjk, chain) in CJK_LOCALES.iter().zip(self.cjk_families.iter_mut()) {
+                if edit(chain) {
+                    self.fixed_fallbacks.insert((script, *cjk));
+                    changed = true;
+                }
+            }
+            return changed;
+        }
+        let tag = super::script_tags::script_tag(script);
+        let chain = if create {
+            Some(self.script_fallbacks.entry(tag).or_default())
+        } else {
+            self.script_fallbacks.get_mut(&tag)
+        };
+        if let Some(chain) = chain {
+            if edit(chain) {
+                self.fixed_fallbacks.insert((script, Cjk::None));
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Replaces the attributes used for matching the fonts in a family with
+    /// the refined values and marks the family as refined.
+    pub fn refine_family(&mut self, id: FamilyId, fonts: &[(FontId, Stretch, Weight, Style)]) {
+        let family = match self.families.get_mut(id.to_usize()) {
+            Some(family) if !family.refined => Arc::make_mut(family),
+            _ => return,
+        };
+        for (font, stretch, weight, style) in fonts {
+            if let Some(entry) = family.fonts.iter_mut().find(|entry| entry.0 == *font) {
+                *entry = (*font, *stretch, *weight, *style);
+                family.capabilities.insert_attributes(*weight, *style);
+            }
+        }
+        family.fonts.sort_by_key(|font| font.2);
+        family.has_stretch = family.fonts.iter().any(|font| font.1 != Stretch::NORMAL);
+        family.refined = true;
+    }
+
+    pub fn collections(&self) -> Vec<CollectionEntry> {
+        let mut sources: HashMap<SourceId, Vec<FontEntry>> = HashMap::new();
+        for index in 0..self.fonts.len() {
+            let id = FontId::alloc(index, self.is_user);
+            if let Some(font) = id.and_then(|id| self.font(id)) {
+                sources.entry(font.source).or_default().push(font);
+            }
+        }
+        collect_multi_face(sources)
+    }
+
     pub fn source(&self, id: SourceId) -> Option<SourceEntry> {
         let source = self.sources.get(id.to_usize())?;
         Some(SourceEntry {
@@ -293,32 +1416,130 @@ impl CollectionData {
             kind: match &source.kind {
                 SourceDataKind::Path(path) => SourceKind::Path(path.clone()),
                 SourceDataKind::Data(data) => SourceKind::Data(data.clone()),
+                SourceDataKind::Removed => return None,
             },
+            trust: source.trust,
         })
     }
 
-    pub fn load(&self, id: SourceId) -> Option<super::font::FontData> {
+    pub fn source_trust(&self, id: SourceId) -> Option<SourceTrust> {
+        self.sources.get(id.to_usize()).map(|source| source.trust)
+    }
+
+    /// Returns the data for the specified source if it is resident, without
+    /// reading the backing file or waiting for a lock.
+    pub fn loaded(&self, id: SourceId) -> Option<super::font::FontData> {
+        let source_data = self.sources.get(id.to_usize())?;
+        match &source_data.kind {
+            SourceDataKind::Data(data) => Some(data.clone()),
+            SourceDataKind::Path(_) => loaded_source(&source_data.status),
+            SourceDataKind::Removed => None,
+        }
+    }
+
+    pub fn load(&self, id: SourceId) -> Result<super::font::FontData, LoadError> {
         let index = id.to_usize();
-        let source_data = self.sources.get(index)?;
+        let source_data = self.sources.get(index).ok_or(LoadError::NotFound)?;
         let path: &Path = match &source_data.kind {
-            SourceDataKind::Data(data) => return Some(data.clone()),
+            SourceDataKind::Data(data) => return Ok(data.clone()),
             SourceDataKind::Path(path) => &*path,
+            SourceDataKind::Removed => return Err(LoadError::Removed),
         };
-        let font = load_source(path, &source_data.status);
-        font
+        load_source(&*self.file_system, path, &source_data.status)
     }
 
-    pub fn clone_into(&self, other: &mut Self) {
-        other.families.clear();
-        other.fonts.clear();
-        other.sources.clear();
-        other.family_map.clear();
-        other.families.extend(self.families.iter().cloned());
-        other.fonts.extend(self.fonts.iter().cloned());
-        other.sources.extend(self.sources.iter().cloned());
-        for (name, families) in &self.family_map {
-            other.family_map.insert(name.clone(), families.clone());
+    /// Loads the data for the specified source along with a flag indicating
+    /// whether the backing file changed since it was last loaded. Files are
+    /// only checked if source verification is enabled.
+    pub fn load_checked(&self, id: SourceId) -> Result<(super::font::FontData, bool), LoadError> {
+        let source_data = self.sources.get(id.to_usize()).ok_or(LoadError::NotFound)?;
+        match &source_data.kind {
+            SourceDataKind::Data(data) => Ok((data.clone(), false)),
+            SourceDataKind::Path(path) => load_source_checked(
+                &*self.file_system,
+                path,
+                &source_data.status,
+                self.verify_sources,
+            ),
+            SourceDataKind::Removed => Err(LoadError::Removed),
+        }
+    }
+
+    /// Updates the fonts in a source after the backing file has changed.
+    ///
+    /// Each font receives a new cache key so that caches keyed by the old
+    /// data are not reused, and the recorded metrics and coverage are
+    /// replaced with those of the new data. Faces that are no longer in the
+    /// file are removed and faces that are new to the file are added.
+    /// Returns the families and fonts that were added and removed.
+    pub fn refresh_source(
+        &mut self,
+        id: SourceId,
+        fonts: &[crate::scan::ScannedFont],
+    ) -> CollectionDiff {
+        let is_user = self.is_user;
+        let families = &self.families;
+        let is_live = |index: usize, font: &FontData| {
+            let font_id = FontId::alloc(index, is_user);
+            families
+                .get(font.family.to_usize())
+                .is_some_and(|family| family.fonts.iter().any(|f| Some(f.0) == font_id))
+        };
+        let mut live_indices = Vec::new();
+        let mut removed = HashSet::new();
+        let mut priority = SourcePriority::System;
+        for (index, font) in self.fonts.iter_mut().enumerate() {
+            if font.source != id || !is_live(index, font) {
+                continue;
+            }
+            priority = families[font.family.to_usize()].priority;
+            font.cache_key = CacheKey::new();
+            font.coverage = Default::default();
+            match fonts.iter().find(|scanned| scanned.index == font.index) {
+                Some(scanned) => {
+                    font.metrics = scanned.metrics;
+                    font.glyph_count = scanned.glyph_count;
+                    font.file_size = scanned.file_size;
+                    font.color_format = scanned.color_format;
+                    live_indices.push(font.index);
+                }
+                None => removed.extend(FontId::alloc(index, is_user)),
+            }
         }
+        let mut diff = self.remove_fonts(&removed);
+        let added = fonts
+            .iter()
+            .filter(|scanned| !live_indices.contains(&scanned.index))
+            .map(|scanned| {
+                let mut scanned = scanned.clone();
+                scanned.priority = priority;
+                scanned
+            })
+            .collect::<Vec<_>>();
+        let source = match self.sources.get(id.to_usize()) {
+            Some(source) if !added.is_empty() => source,
+            _ => return diff,
+        };
+        // New faces are added as another source backed by the same file.
+        let source = SourceData {
+            kind: match &source.kind {
+                SourceDataKind::Path(path) => SourceDataKind::Path(path.clone()),
+                SourceDataKind::Data(data) => SourceDataKind::Data(data.clone()),
+                SourceDataKind::Removed => return diff,
+            },
+            status: RwLock::new(SourceDataStatus::Vacant),
+            trust: source.trust,
+        };
+        let family_count = self.families.len();
+        let mut reg = Registration::default();
+        self.add_scanned_fonts(&added, source, Some(&mut reg));
+        diff.added_families.extend(
+            reg.families
+                .into_iter()
+                .filter(|id| id.to_usize() >= family_count),
+        );
+        diff.added_fonts.extend(reg.fonts);
+        diff
     }
 }
 
@@ -327,27 +1548,101 @@ pub struct ScannedCollectionData {
     pub collection: CollectionData,
 }
 
+/// Fonts of a family with their attributes, shared with the family entries
+/// handed out by a static collection.
+pub type StaticFamilyFonts = Arc<[(FontId, Stretch, Weight, Style)]>;
+
 pub struct StaticCollection {
     pub data: &'static StaticCollectionData,
+    /// Fonts of each family with their attributes, ordered by weight.
+    pub family_fonts: Vec<StaticFamilyFonts>,
     pub cache_keys: Vec<CacheKey>,
+    pub coverage: Vec<CoverageCache>,
     pub sources: Vec<RwLock<SourceDataStatus>>,
+    /// File system from which sources that are not embedded are loaded.
+    pub file_system: Arc<dyn FileSystem>,
+    /// Length and hash of the data of each source, computed the first time
+    /// a source scanned at runtime is compared with the collection.
+    pub content_keys: OnceLock<Vec<Option<(usize, u64)>>>,
 }
 
 impl StaticCollection {
-    pub fn new(data: &'static StaticCollectionData) -> Self {
+    pub fn new(data: &'static StaticCollectionData, file_system: Arc<dyn FileSystem>) -> Self {
         let cache_keys = (0..data.fonts.len())
             .map(|_| CacheKey::new())
             .collect::<Vec<_>>();
+        let coverage = (0..data.fonts.len())
+            .map(|_| CoverageCache::default())
+            .collect::<Vec<_>>();
         let sources = (0..data.sources.len())
             .map(|_| RwLock::new(SourceDataStatus::Vacant))
             .collect::<Vec<_>>();
+        let family_fonts = data
+            .families
+            .iter()
+            .map(|family| {
+                let mut fonts = family
+                    .fonts
+                    .iter()
+                    .filter_map(|&id| {
+                        let (stretch, weight, style) =
+                            data.fonts.get(id.to_usize())?.attributes.parts();
+                        Some((id, stretch, weight, style))
+                    })
+                    .collect::<Vec<_>>();
+                fonts.sort_by_key(|font| font.2);
+                fonts.into()
+            })
+            .collect::<Vec<_>>();
         Self {
             data,
+            family_fonts,
             cache_keys,
+            coverage,
             sources,
+            file_system,
+            content_keys: OnceLock::new(),
         }
     }
 
+    /// Returns the source backed by the same file or holding the same data
+    /// as a source scanned at runtime.
+    ///
+    /// Files are compared by canonical path first. Otherwise the data is
+    /// compared by length and hash and then byte for byte, which loads the
+    /// sources that are not embedded the first time.
+    pub fn find_source(&self, source: &SourceData) -> Option<SourceId> {
+        let data = match &source.kind {
+            SourceDataKind::Path(path) => {
+                let path = self.file_system.canonicalize(path).ok()?;
+                let found = self.data.sources.iter().position(|probe| {
+                    probe.data.is_none()
+                        && self
+                            .file_system
+                            .canonicalize(Path::new(probe.file_name))
+                            .is_ok_and(|probe| probe == path)
+                });
+                if let Some(index) = found {
+                    return Some(SourceId::new(index as u32));
+                }
+                self.file_system.read(&path).ok()?
+            }
+            SourceDataKind::Data(data) => data.clone(),
+            SourceDataKind::Removed => return None,
+        };
+        let key = Some(content_key(&data));
+        let keys = self.content_keys.get_or_init(|| {
+            (0..self.data.sources.len())
+                .map(|index| Some(content_key(&self.load(SourceId::new(index as u32)).ok()?)))
+                .collect()
+        });
+        keys.iter()
+            .enumerate()
+            .filter(|(_, probe)| **probe == key)
+            .map(|(index, _)| SourceId::new(index as u32))
+            .find(|id| self.load(*id).is_ok_and(|probe| *probe == *data))
+    }
+
     pub fn family_id(&self, name: &str) -> Option<FamilyId> {
         let mut lowercase_buf = LowercaseString::new();
         let lowercase_name = lowercase_buf.get(name)?;
@@ -378,8 +1673,39 @@ impl StaticCollection {
                 .get(index)
                 .map(|x| x.families)
                 .unwrap_or(&[]),
-            _ => self.data.default_families,
+            _ => &[],
+        }
+    }
+
+    pub fn fallback_candidates(
+        &self,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Vec<FallbackCandidate> {
+        let declared = if script == Script::Han {
+            let cjk = locale.map(|l| l.cjk() as usize).unwrap_or(0);
+            self.data.cjk_families[cjk]
+        } else {
+            let tag = super::script_tags::script_tag(script);
+            match self
+                .data
+                .script_fallbacks
+                .binary_search_by(|x| x.script.cmp(&tag))
+            {
+                Ok(index) => self.data.script_fallbacks[index].families,
+                _ => &[],
+            }
+        };
+        if declared.is_empty() {
+            return FallbackCandidate::defaults(self.data.default_families);
         }
+        declared
+            .iter()
+            .map(|&family| FallbackCandidate {
+                family,
+                reason: FallbackReason::Declared,
+            })
+            .collect()
     }
 
     pub fn family_name(&self, id: FamilyId) -> Option<&'static str> {
@@ -389,45 +1715,137 @@ impl StaticCollection {
             .map(|family| family.name)
     }
 
-    pub fn load(&self, id: SourceId) -> Option<super::font::FontData> {
+    pub fn loaded(&self, id: SourceId) -> Option<super::font::FontData> {
+        let index = id.to_usize();
+        match self.data.sources.get(index)?.data {
+            Some(data) => Some(super::font::FontData::from_static(data)),
+            None => loaded_source(self.sources.get(index)?),
+        }
+    }
+
+    pub fn load(&self, id: SourceId) -> Result<super::font::FontData, LoadError> {
         let index = id.to_usize();
         let paths = SourcePaths {
             inner: SourcePathsInner::Static(self.data.search_paths),
             pos: 0,
         };
-        load_source(
-            &self.data.sources.get(index)?.file_name,
-            self.sources.get(index)?,
-        )
+        match (self.data.sources.get(index), self.sources.get(index)) {
+            (
+                Some(StaticSourceData {
+                    data: Some(data), ..
+                }),
+                _,
+            ) => Ok(super::font::FontData::from_static(data)),
+            (Some(source), Some(status)) => {
+                load_source(&*self.file_system, Path::new(source.file_name), status)
+            }
+            _ => Err(LoadError::NotFound),
+        }
     }
 }
 
-fn load_source(path: &Path, status: &RwLock<SourceDataStatus>) -> Option<super::font::FontData> {
+/// Returns the length and hash of font data, used to find identical sources.
+fn content_key(data: &[u8]) -> (usize, u64) {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    (data.len(), hasher.finish())
+}
+
+/// Converts fonts grouped by source into collection entries, keeping only
+/// sources with more than one face.
+fn collect_multi_face(sources: HashMap<SourceId, Vec<FontEntry>>) -> Vec<CollectionEntry> {
+    let mut collections = sources
+        .into_iter()
+        .filter(|(_, fonts)| fonts.len() > 1)
+        .map(|(source, mut fonts)| {
+            fonts.sort_by_key(|font| font.index);
+            CollectionEntry { source, fonts }
+        })
+        .collect::<Vec<_>>();
+    collections.sort_by_key(|collection| collection.source);
+    collections
+}
+
+/// Returns the data for a file backed source if it is still alive. Returns
+/// `None` rather than waiting if the status is being updated.
+fn loaded_source(status: &RwLock<SourceDataStatus>) -> Option<super::font::FontData> {
+    match &*status.try_read().ok()? {
+        SourceDataStatus::Present(data, _) => data.upgrade(),
+        _ => None,
+    }
+}
+
+fn load_source(
+    file_system: &dyn FileSystem,
+    path: &Path,
+    status: &RwLock<SourceDataStatus>,
+) -> Result<super::font::FontData, LoadError> {
+    load_source_checked(file_system, path, status, false).map(|(data, _)| data)
+}
+
+/// Loads the data for a file backed source, reusing the data if it is still
+/// alive.
+///
+/// If `verify` is true, the file is checked for modification before the
+/// existing data is reused. The returned flag is true if the file has changed
+/// since it was previously loaded.
+fn load_source_checked(
+    file_system: &dyn FileSystem,
+    path: &Path,
+    status: &RwLock<SourceDataStatus>,
+    verify: bool,
+) -> Result<(super::font::FontData, bool), LoadError> {
+    let stamp = if verify {
+        FileStamp::of(file_system, path)
+    } else {
+        None
+    };
+    let is_current = |loaded: &Option<FileStamp>| !verify || *loaded == stamp;
     match &*status.read().unwrap() {
-        SourceDataStatus::Present(data) => {
+        SourceDataStatus::Present(data, loaded) if is_current(loaded) => {
             if let Some(data) = data.upgrade() {
-                return Some(data);
+                return Ok((data, false));
             }
         }
-        SourceDataStatus::Error => return None,
+        SourceDataStatus::Error(error, failed) if is_current(failed) => return Err(error.clone()),
         _ => {}
     }
     let mut status = status.write().unwrap();
+    // A file that loads after failing is treated as changed since its
+    // faces may differ from those that were scanned.
+    let changed_from = |loaded: Option<FileStamp>| match &*status {
+        SourceDataStatus::Present(_, previous) => previous.is_some() && *previous != loaded,
+        SourceDataStatus::Error(..) => true,
+        SourceDataStatus::Vacant => false,
+    };
     match &*status {
-        SourceDataStatus::Present(data) => {
+        SourceDataStatus::Present(data, loaded) if is_current(loaded) => {
             if let Some(data) = data.upgrade() {
-                return Some(data);
+                return Ok((data, false));
             }
         }
-        SourceDataStatus::Error => return None,
+        SourceDataStatus::Error(error, failed) if is_current(failed) => return Err(error.clone()),
         _ => {}
     }
-    if let Ok(data) = super::font::FontData::from_file(path) {
-        *status = SourceDataStatus::Present(data.downgrade());
-        return Some(data);
+    let data = file_system
+        .read(path)
+        .map_err(|e| LoadError::Io(Arc::new(e)))
+        .and_then(|data| match swash::FontDataRef::new(&data) {
+            Some(_) => Ok(data),
+            None => Err(LoadError::InvalidData),
+        });
+    match data {
+        Ok(data) => {
+            let stamp = stamp.or_else(|| FileStamp::of(file_system, path));
+            let changed = changed_from(stamp);
+            *status = SourceDataStatus::Present(data.downgrade(), stamp);
+            Ok((data, changed))
+        }
+        Err(error) => {
+            *status = SourceDataStatus::Error(error.clone(), stamp);
+            Err(error)
+        }
     }
-    *status = SourceDataStatus::Error;
-    None
 }
 
 pub enum SystemCollectionData {
@@ -453,21 +1871,87 @@ impl SystemCollectionData {
         match self {
             Self::Static(data) => {
                 let family = data.data.families.get(id.to_usize())?;
+                let fonts = data.family_fonts.get(id.to_usize())?;
                 Some(FamilyEntry {
                     id,
                     has_stretch: family.has_stretch,
-                    kind: FontFamilyKind::Static(family.name, family.fonts),
+                    capabilities: Capabilities::from_static(family.capabilities, fonts),
+                    kind: FontFamilyKind::Static(family.name, fonts.clone()),
                 })
             }
             Self::Scanned(data) => data.collection.family(id),
         }
     }
 
+    pub fn family_ref(&self, id: FamilyId) -> Option<FamilyRef<'_>> {
+        match self {
+            Self::Static(data) => {
+                let family = data.data.families.get(id.to_usize())?;
+                let fonts = data.family_fonts.get(id.to_usize())?;
+                Some(FamilyRef {
+                    id,
+                    name: family.name,
+                    has_stretch: family.has_stretch,
+                    capabilities: Capabilities::from_static(family.capabilities, fonts),
+                    fonts,
+                })
+            }
+            Self::Scanned(data) => data.collection.family_ref(id),
+        }
+    }
+
+    pub fn needs_refinement(&self, id: FamilyId) -> bool {
+        match self {
+            Self::Static(_) => false,
+            Self::Scanned(data) => data.collection.needs_refinement(id),
+        }
+    }
+
+    pub fn needs_platform_fallbacks(&self, script: Script, cjk: Cjk) -> bool {
+        match self {
+            Self::Static(_) => false,
+            Self::Scanned(data) => data.collection.needs_platform_fallbacks(script, cjk),
+        }
+    }
+
+    pub fn resolve_platform_fallbacks(&mut self, script: Script, cjk: Cjk) {
+        if let Self::Scanned(data) = self {
+            data.collection.resolve_platform_fallbacks(script, cjk);
+        }
+    }
+
+    pub fn resolve_all_platform_fallbacks(&mut self) {
+        if let Self::Scanned(data) = self {
+            data.collection.resolve_all_platform_fallbacks();
+        }
+    }
+
     pub fn family_by_name(&mut self, name: &str) -> Option<FamilyEntry> {
         let family_id = self.family_id(name)?;
         self.family(family_id)
     }
 
+    /// Returns the names of all families known to the platform font source,
+    /// including families that have not been loaded.
+    pub fn platform_family_names(&self) -> Vec<String> {
+        match self {
+            Self::Scanned(data) if data.collection.system_fonts => data
+                .collection
+                .system_source
+                .with(|source| source.all_families().unwrap_or_default())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn find_family(&self, name: &str) -> Option<FamilyEntry> {
+        let family_id = match self {
+            Self::Static(data) => data.family_id(name)?,
+            Self::Scanned(data) => data.collection.find_family_id(name)?,
+        };
+        self.family(family_id)
+    }
+
     pub fn font(&self, id: FontId) -> Option<FontEntry> {
         match self {
             Self::Static(data) => {
@@ -481,12 +1965,30 @@ impl SystemCollectionData {
                     index: font.index,
                     attributes: font.attributes,
                     cache_key,
+                    metrics: None,
+                    metrics_policy: MetricsPolicy::default(),
+                    glyph_count: 0,
+                    file_size: 0,
+                    variant: 0,
+                    optical_size: None,
+                    cjk: Cjk::None,
+                    color_format: font.color_format,
+                    trust: SourceTrust::System,
                 })
             }
             Self::Scanned(data) => data.collection.font(id),
         }
     }
 
+    /// Returns the number of families, including those whose fonts have
+    /// all been removed.
+    pub fn family_count(&self) -> usize {
+        match self {
+            Self::Static(data) => data.data.families.len(),
+            Self::Scanned(data) => data.collection.families.len(),
+        }
+    }
+
     pub fn add_fonts(
         &mut self,
         data: super::font::FontData,
@@ -496,8 +1998,134 @@ impl SystemCollectionData {
         match self {
             SystemCollectionData::Static(_) => None,
             SystemCollectionData::Scanned(collection) => {
-                collection.collection.add_fonts(data, source, reg)
+                let count = collection.collection.add_fonts(data, source, reg);
+                collection.collection.rank_fallbacks();
+                count
+            }
+        }
+    }
+
+    /// Returns the fonts of a static collection that duplicate the faces of
+    /// a source scanned at runtime, as the face index, family and font of
+    /// each, or `None` if the collection is not static. See
+    /// [`StaticCollection::find_source`].
+    pub fn static_duplicates(&self, source: &SourceData) -> Option<Vec<(u32, FamilyId, FontId)>> {
+        let data = match self {
+            Self::Static(data) => data,
+            Self::Scanned(_) => return None,
+        };
+        let source = match data.find_source(source) {
+            Some(source) => source,
+            None => return Some(Vec::new()),
+        };
+        let fonts = data.data.fonts.iter().enumerate();
+        Some(
+            fonts
+                .filter(|(_, font)| font.source == source)
+                .map(|(index, font)| (font.index, font.family, FontId::new(index as u32)))
+                .collect(),
+        )
+    }
+
+    pub fn add_scanned_fonts(
+        &mut self,
+        fonts: &[crate::scan::ScannedFont],
+        source: SourceData,
+        reg: Option<&mut Registration>,
+    ) -> Option<u32> {
+        match self {
+            SystemCollectionData::Static(_) => None,
+            SystemCollectionData::Scanned(collection) => {
+                let count = collection.collection.add_scanned_fonts(fonts, source, reg);
+                collection.collection.rank_fallbacks();
+                count
+            }
+        }
+    }
+
+    pub fn font_by_cache_key(&self, key: CacheKey) -> Option<FontId> {
+        match self {
+            Self::Static(data) => {
+                let index = data.cache_keys.iter().position(|k| *k == key)?;
+                FontId::alloc(index, false)
+            }
+            Self::Scanned(data) => data.collection.font_by_cache_key(key),
+        }
+    }
+
+    pub fn coverage_cache(&self, id: FontId) -> Option<&CoverageCache> {
+        match self {
+            Self::Static(data) => data.coverage.get(id.to_usize()),
+            Self::Scanned(data) => data.collection.coverage_cache(id),
+        }
+    }
+
+    pub fn instances(&self, id: FontId) -> Option<&Arc<[NamedInstance]>> {
+        match self {
+            Self::Static(_) => None,
+            Self::Scanned(data) => data.collection.instances(id),
+        }
+    }
+
+    pub fn axes(&self, id: FontId) -> Option<&Arc<[VariationAxis]>> {
+        match self {
+            Self::Static(_) => None,
+            Self::Scanned(data) => data.collection.axes(id),
+        }
+    }
+
+    pub fn remove_path(&mut self, path: &Path) -> CollectionDiff {
+        match self {
+            Self::Static(_) => CollectionDiff::default(),
+            Self::Scanned(data) => data.collection.remove_path(path),
+        }
+    }
+
+    pub fn unregister(&mut self, fonts: &[FontId]) -> CollectionDiff {
+        match self {
+            Self::Static(_) => CollectionDiff::default(),
+            Self::Scanned(data) => data.collection.unregister(fonts),
+        }
+    }
+
+    pub fn clear_missing(&mut self, names: impl Iterator<Item = String>) {
+        if let Self::Scanned(data) = self {
+            data.collection.clear_missing(names);
+        }
+    }
+
+    pub fn prepend_fallback(&mut self, script: Script, family: FamilyId) -> bool {
+        match self {
+            Self::Static(_) => false,
+            Self::Scanned(data) => data.collection.prepend_fallback(script, family),
+        }
+    }
+
+    pub fn remove_fallback(&mut self, script: Script, family: FamilyId) -> bool {
+        match self {
+            Self::Static(_) => false,
+            Self::Scanned(data) => data.collection.remove_fallback(script, family),
+        }
+    }
+
+    pub fn refine_family(&mut self, id: FamilyId, fonts: &[(FontId, Stretch, Weight, Style)]) {
+        if let Self::Scanned(data) = self {
+            data.collection.refine_family(id, fonts);
+        }
+    }
+
+    pub fn collections(&self) -> Vec<CollectionEntry> {
+        match self {
+            Self::Static(_) => {
+                let mut sources: HashMap<SourceId, Vec<FontEntry>> = HashMap::new();
+                let mut index = 0;
+                while let Some(font) = self.font(FontId::new(index)) {
+                    sources.entry(font.source).or_default().push(font);
+                    index += 1;
+                }
+                collect_multi_face(sources)
             }
+            Self::Scanned(data) => data.collection.collections(),
         }
     }
 
@@ -507,20 +2135,95 @@ impl SystemCollectionData {
                 let source = data.data.sources.get(id.to_usize())?;
                 Some(SourceEntry {
                     id,
-                    kind: SourceKind::FileName(source.file_name.clone()),
+                    kind: match source.data {
+                        Some(data) => SourceKind::Data(super::font::FontData::from_static(data)),
+                        None => SourceKind::FileName(source.file_name.into()),
+                    },
+                    trust: SourceTrust::System,
                 })
             }
             Self::Scanned(data) => data.collection.source(id),
         }
     }
 
-    pub fn load(&self, id: SourceId) -> Option<super::font::FontData> {
+    pub fn loaded(&self, id: SourceId) -> Option<super::font::FontData> {
+        match self {
+            Self::Static(data) => data.loaded(id),
+            Self::Scanned(data) => data.collection.loaded(id),
+        }
+    }
+
+    pub fn load(&self, id: SourceId) -> Result<super::font::FontData, LoadError> {
         match self {
             Self::Static(data) => data.load(id),
             Self::Scanned(data) => data.collection.load(id),
         }
     }
 
+    /// Returns the file system from which path based sources are loaded.
+    pub fn file_system(&self) -> Arc<dyn FileSystem> {
+        match self {
+            Self::Static(data) => data.file_system.clone(),
+            Self::Scanned(data) => data.collection.file_system.clone(),
+        }
+    }
+
+    pub fn rejected_faces(&self) -> &[RejectedFace] {
+        match self {
+            Self::Static(_) => &[],
+            Self::Scanned(data) => &data.collection.rejected_faces,
+        }
+    }
+
+    pub fn add_rejected_faces(&mut self, faces: &[RejectedFace]) {
+        if let Self::Scanned(data) = self {
+            data.collection.add_rejected_faces(faces.iter().cloned());
+        }
+    }
+
+    pub fn scan_cache(&self) -> Option<Arc<Mutex<ScanCache>>> {
+        match self {
+            Self::Static(_) => None,
+            Self::Scanned(data) => data.collection.scan_cache.clone(),
+        }
+    }
+
+    pub fn scanners(&self) -> Arc<ScannerPool> {
+        match self {
+            Self::Static(_) => Arc::new(ScannerPool::new()),
+            Self::Scanned(data) => data.collection.scanners.clone(),
+        }
+    }
+
+    pub fn source_trust(&self, id: SourceId) -> Option<SourceTrust> {
+        match self {
+            Self::Static(_) => Some(SourceTrust::System),
+            Self::Scanned(data) => data.collection.source_trust(id),
+        }
+    }
+
+    pub fn load_checked(&self, id: SourceId) -> Result<(super::font::FontData, bool), LoadError> {
+        match self {
+            Self::Static(data) => Ok((data.load(id)?, false)),
+            Self::Scanned(data) => data.collection.load_checked(id),
+        }
+    }
+
+    pub fn refresh_source(
+        &mut self,
+        id: SourceId,
+        fonts: &[crate::scan::ScannedFont],
+    ) -> CollectionDiff {
+        match self {
+            Self::Static(_) => CollectionDiff::default(),
+            Self::Scanned(data) => {
+                let diff = data.collection.refresh_source(id, fonts);
+                data.collection.rank_fallbacks();
+                diff
+            }
+        }
+    }
+
     pub fn default_families(&self) -> &[FamilyId] {
         match self {
             Self::Static(data) => data.data.default_families,
@@ -540,13 +2243,61 @@ impl SystemCollectionData {
         }
     }
 
-    pub fn fallback_families(&mut self, script: Script, locale: Option<Locale>) -> &[FamilyId] {
+    pub fn default_status(&self) -> SetupStatus {
+        match self {
+            Self::Static(data) => setup_status(data.data.default_families, false),
+            Self::Scanned(data) => data.collection.default_status(),
+        }
+    }
+
+    pub fn generic_status(&self, family: GenericFamily) -> SetupStatus {
+        match self {
+            Self::Static(_) => setup_status(self.generic_families(family), false),
+            Self::Scanned(data) => data.collection.generic_status(family),
+        }
+    }
+
+    pub fn cjk_families(&self, cjk: Cjk) -> &[FamilyId] {
+        match self {
+            Self::Static(data) => data.data.cjk_families[cjk as usize],
+            Self::Scanned(data) => data.collection.cjk_families(cjk),
+        }
+    }
+
+    pub fn fallback_families(&self, script: Script, locale: Option<Locale>) -> &[FamilyId] {
         match self {
             Self::Static(data) => data.fallback_families(script, locale),
             Self::Scanned(data) => data.collection.fallback_families(script, locale),
         }
     }
 
+    pub fn has_script_fallbacks(&self, script: Script) -> bool {
+        match self {
+            Self::Static(data) if script == Script::Han => {
+                !data.data.cjk_families[Cjk::None as usize].is_empty()
+            }
+            Self::Static(data) => {
+                let tag = super::script_tags::script_tag(script);
+                data.data
+                    .script_fallbacks
+                    .binary_search_by(|x| x.script.cmp(&tag))
+                    .is_ok()
+            }
+            Self::Scanned(data) => data.collection.has_script_fallbacks(script),
+        }
+    }
+
+    pub fn fallback_candidates(
+        &self,
+        script: Script,
+        locale: Option<Locale>,
+    ) -> Vec<FallbackCandidate> {
+        match self {
+            Self::Static(data) => data.fallback_candidates(script, locale),
+            Self::Scanned(data) => data.collection.fallback_candidates(script, locale),
+        }
+    }
+
     pub fn family_id(&mut self, name: &str) -> Option<FamilyId> {
         match self {
             Self::Static(data) => data.family_id(name),
@@ -555,40 +2306,155 @@ impl SystemCollectionData {
     }
 }
 
+/// Family in a [`StaticCollectionData`].
 pub struct StaticFamilyData {
+    /// Name of the family.
     pub name: &'static str,
+    /// Name of the family folded to lowercase. Families are sorted by this
+    /// name.
     pub lowercase_name: &'static str,
+    /// True if any font in the family has a stretch other than normal.
     pub has_stretch: bool,
-    pub fonts: &'static [(FontId, Stretch, Weight, Style)],
+    /// Capability flags of the fonts that cannot be derived from their
+    /// attributes, such as color glyphs and fixed pitch.
+    pub capabilities: u8,
+    /// Fonts in the family.
+    pub fonts: &'static [FontId],
 }
 
+/// Font in a [`StaticCollectionData`].
 pub struct StaticFontData {
+    /// Family containing the font.
     pub family: FamilyId,
+    /// Stretch, weight and style of the font.
     pub attributes: Attributes,
+    /// Source containing the font data.
     pub source: SourceId,
+    /// Index of the font in a collection, or zero for a single font file.
     pub index: u32,
+    /// Format of the color glyphs in the font, if any.
+    pub color_format: Option<ColorFormat>,
 }
 
+/// Source of the fonts in a [`StaticCollectionData`].
 pub struct StaticSourceData {
-    pub file_name: PathBuf,
+    /// File name of the source, located with the search paths of the
+    /// collection unless the data is embedded.
+    pub file_name: &'static str,
+    /// Font data embedded in the binary.
+    pub data: Option<&'static [u8]>,
 }
 
+/// Fallback chain for a script in a [`StaticCollectionData`].
 pub struct StaticScriptFallbacks {
+    /// ISO 15924 tag of the script. Chains are sorted by this tag.
     pub script: [u8; 4],
+    /// Families in the chain, in order of preference.
     pub families: &'static [FamilyId],
 }
 
-const GENERIC_FAMILY_COUNT: usize = 6;
-const CJK_FAMILY_COUNT: usize = 5;
+const GENERIC_FAMILY_COUNT: usize = GenericFamily::ALL.len();
+
+// Generic families index fixed-size arrays by discriminant, so every variant
+// must appear in `GenericFamily::ALL` at the position of its discriminant.
+// The match is exhaustive so that a new variant fails to compile until it is
+// given a position here and in `GenericFamily::ALL`.
+const _: () = {
+    let mut i = 0;
+    while i < GENERIC_FAMILY_COUNT {
+        let position = match GenericFamily::ALL[i] {
+            GenericFamily::Serif => 0,
+            GenericFamily::SansSerif => 1,
+            GenericFamily::Monospace => 2,
+            GenericFamily::SystemUi => 3,
+            GenericFamily::Cursive => 4,
+            GenericFamily::Emoji => 5,
+            GenericFamily::Math => 6,
+            GenericFamily::Fantasy => 7,
+            GenericFamily::UiSerif => 8,
+            GenericFamily::UiSansSerif => 9,
+            GenericFamily::UiMonospace => 10,
+            GenericFamily::UiRounded => 11,
+        };
+        assert!(position == i && GenericFamily::ALL[i] as usize == i);
+        i += 1;
+    }
+};
+pub const CJK_FAMILY_COUNT: usize = 5;
+
+/// CJK locales in the order of the CJK fallback chains.
+const CJK_LOCALES: [Cjk; CJK_FAMILY_COUNT] = [
+    Cjk::None,
+    Cjk::Traditional,
+    Cjk::Simplified,
+    Cjk::Japanese,
+    Cjk::Korean,
+];
+
+/// Returns the CJK locale bucket of the fallback chain for the script and
+/// locale. Only Han has a chain for each bucket.
+pub fn fallback_cjk(script: Script, locale: Option<Locale>) -> Cjk {
+    match script {
+        Script::Han => locale.map(|l| l.cjk()).unwrap_or(Cjk::None),
+        _ => Cjk::None,
+    }
+}
 
+/// Scripts with fallback chains queried from the platform and the languages
+/// used for the queries.
+#[cfg(any(feature = "fontconfig", all(feature = "coretext", target_os = "macos")))]
+const PLATFORM_FALLBACK_LANGUAGES: &[(Script, Cjk, &str)] = &[
+    (Script::Han, Cjk::None, "zh-cn"),
+    (Script::Han, Cjk::Simplified, "zh-cn"),
+    (Script::Han, Cjk::Traditional, "zh-tw"),
+    (Script::Han, Cjk::Japanese, "ja"),
+    (Script::Han, Cjk::Korean, "ko"),
+    (Script::Hiragana, Cjk::None, "ja"),
+    (Script::Katakana, Cjk::None, "ja"),
+    (Script::Hangul, Cjk::None, "ko"),
+    (Script::Arabic, Cjk::None, "ar"),
+    (Script::Hebrew, Cjk::None, "he"),
+    (Script::Greek, Cjk::None, "el"),
+    (Script::Cyrillic, Cjk::None, "ru"),
+    (Script::Armenian, Cjk::None, "hy"),
+    (Script::Georgian, Cjk::None, "ka"),
+    (Script::Ethiopic, Cjk::None, "am"),
+    (Script::Thai, Cjk::None, "th"),
+    (Script::Lao, Cjk::None, "lo"),
+    (Script::Khmer, Cjk::None, "km"),
+    (Script::Myanmar, Cjk::None, "my"),
+    (Script::Tibetan, Cjk::None, "bo"),
+    (Script::Devanagari, Cjk::None, "hi"),
+    (Script::Bengali, Cjk::None, "bn"),
+    (Script::Gurmukhi, Cjk::None, "pa"),
+    (Script::Gujarati, Cjk::None, "gu"),
+    (Script::Oriya, Cjk::None, "or"),
+    (Script::Tamil, Cjk::None, "ta"),
+    (Script::Telugu, Cjk::None, "te"),
+    (Script::Kannada, Cjk::None, "kn"),
+    (Script::Malayalam, Cjk::None, "ml"),
+    (Script::Sinhala, Cjk::None, "si"),
+];
+
+/// Collection of fonts described entirely by static data, such as the
+/// collection generated by `fount_embed!`. See
+/// [`Library::from_embedded`](super::Library::from_embedded).
 pub struct StaticCollectionData {
+    /// Directories searched for sources that are not embedded.
     pub search_paths: &'static [&'static str],
+    /// Families, sorted by lowercase name.
     pub families: &'static [StaticFamilyData],
+    /// Fonts, indexed by font identifier.
     pub fonts: &'static [StaticFontData],
+    /// Sources, indexed by source identifier.
     pub sources: &'static [StaticSourceData],
+    /// Default families, in order of preference.
     pub default_families: &'static [FamilyId],
+    /// Fallback chains for scripts other than Han, sorted by script tag.
     pub script_fallbacks: &'static [StaticScriptFallbacks],
+    /// Families of each generic family, indexed by discriminant.
     pub generic_families: [&'static [FamilyId]; GENERIC_FAMILY_COUNT],
+    /// Fallback chains for Han text, indexed by CJK locale bucket.
     pub cjk_families: [&'static [FamilyId]; CJK_FAMILY_COUNT],
 }
 
@@ -694,8 +2560,118 @@ impl LowercaseString {
             }
             std::str::from_utf8(&self.buf[..end]).ok()
         } else {
-            self.heap = name.to_lowercase();
-            Some(&self.heap)
+            // Fold non-ASCII names into the stack buffer as well, only
+            // spilling to the heap for long names.
+            let mut end = 0;
+            for c in fold_name(name) {
+                if end + c.len_utf8() > self.buf.len() {
+                    self.heap.clear();
+                    self.heap.extend(fold_name(name));
+                    return Some(&self.heap);
+                }
+                end += c.encode_utf8(&mut self.buf[end..]).len();
+            }
+            std::str::from_utf8(&self.buf[..end]).ok()
         }
     }
 }
+
+/// Returns the characters of a family name folded for comparison.
+///
+/// Names are normalized to NFC so that precomposed and decomposed accents
+/// match and are then fully case folded, so "Straße" matches "STRASSE".
+/// Compatibility forms are kept distinct. This is applied both to the names
+/// of scanned fonts and to requested names.
+pub fn fold_name(name: &str) -> impl Iterator<Item = char> + '_ {
+    name.nfc().flat_map(casefold::fold)
+}
+
+/// Broad classification of a family used to synthesize defaults.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum FamilyClass {
+    Sans,
+    Serif,
+    Mono,
+}
+
+impl FamilyClass {
+    /// Classifies a family by its name, checking for monospace names first
+    /// since names like "DejaVu Sans Mono" also contain a sans serif word.
+    fn of_name(name: &str) -> Option<Self> {
+        const MONO: &[&str] = &[
+            "mono",
+            "code",
+            "courier",
+            "consol",
+            "fixed",
+            "terminal",
+            "typewriter",
+        ];
+        const SANS: &[&str] = &[
+            "sans",
+            "arial",
+            "helvetica",
+            "verdana",
+            "tahoma",
+            "segoe",
+            "gothic",
+            "grotesk",
+        ];
+        const SERIF: &[&str] = &[
+            "serif",
+            "times",
+            "georgia",
+            "roman",
+            "garamond",
+            "baskerville",
+            "mincho",
+        ];
+        let name = name.to_lowercase();
+        let matches = |words: &[&str]| words.iter().any(|word| name.contains(word));
+        if matches(MONO) {
+            Some(Self::Mono)
+        } else if matches(SANS) {
+            Some(Self::Sans)
+        } else if matches(SERIF) {
+            Some(Self::Serif)
+        } else {
+            None
+        }
+    }
+
+    /// Classifies a loaded family. Families are monospace if and only if
+    /// any of their fonts is fixed pitch.
+    fn of_family(family: &FamilyData) -> Option<Self> {
+        if family.capabilities.is_monospace() {
+            return Some(Self::Mono);
+        }
+        Self::of_name(&family.name).filter(|class| *class != Self::Mono)
+    }
+}
+
+/// Returns the names of the families that provide symbols, such as arrows
+/// and dingbats, on the current platform in order of preference.
+pub fn symbol_family_names() -> &'static [&'static str] {
+    use super::system::*;
+    match OS {
+        Os::Windows => &["segoe ui symbol", "segoe ui historic"],
+        Os::MacOs | Os::Ios => &["apple symbols"],
+        Os::Android => &["noto sans symbols", "noto sans symbols 2"],
+        _ => &[
+            "noto sans symbols",
+            "noto sans symbols 2",
+            "symbola",
+            "dejavu sans",
+        ],
+    }
+}
+
+fn setup_status(families: &[FamilyId], synthesized: bool) -> SetupStatus {
+    if families.is_empty() {
+        SetupStatus::Missing
+    } else if synthesized {
+        SetupStatus::Synthesized
+    } else {
+        SetupStatus::Found
+    }
+}
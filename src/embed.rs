@@ -0,0 +1,237 @@
+//! Font collections described by static data, used to embed application
+//! fonts in the binary.
+
+pub use super::data::{
+    StaticCollectionData, StaticFamilyData, StaticFontData, StaticScriptFallbacks, StaticSourceData,
+};
+pub use swash::Attributes;
+
+#[cfg(feature = "embed")]
+use super::{
+    data::{LowercaseString, CJK_FAMILY_COUNT},
+    scan::FontScanner,
+    Capabilities, GenericFamily,
+};
+#[cfg(feature = "embed")]
+use std::{collections::BTreeMap, fmt::Write, io, path::Path};
+
+/// Returns Rust source for an expression of type
+/// `&'static StaticCollectionData` that embeds the fonts in the specified
+/// files with `include_bytes!`.
+///
+/// This is the generator behind the `fount_embed!` macro of the
+/// `fount-embed` crate. A build script may instead write the source to a
+/// file in `OUT_DIR` and `include!` it. Paths should be absolute since
+/// `include_bytes!` resolves relative paths against the file containing the
+/// expression. Files are scanned when this is called, so creating a library
+/// from the collection with [`Library::from_embedded`](super::Library::from_embedded)
+/// performs no scanning. Files that contain no valid fonts are skipped.
+///
+/// Families are placed in the default families in the order their files
+/// are given, except for color families which are only used for emoji.
+/// Fixed pitch families are used for the monospace generic families and
+/// families with a math table for math.
+#[cfg(feature = "embed")]
+pub fn collection_source(paths: &[impl AsRef<Path>]) -> io::Result<String> {
+    struct Family {
+        name: String,
+        lowercase_name: String,
+        has_stretch: bool,
+        capabilities: u8,
+        fonts: Vec<usize>,
+        scripts: Vec<(swash::text::Script, swash::text::Cjk)>,
+    }
+    let mut scanner = FontScanner::new();
+    let mut sources = Vec::new();
+    let mut fonts = Vec::new();
+    let mut families = Vec::<Family>::new();
+    for path in paths {
+        let path = path.as_ref();
+        let scanned = scanner.scan_all(&std::fs::read(path)?);
+        if scanned.is_empty() {
+            continue;
+        }
+        let path_str = path.to_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "font path is not valid UTF-8")
+        })?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = sources.len();
+        sources.push((file_name, path_str.to_owned()));
+        for font in scanned {
+            let mut lowercase_buf = LowercaseString::new();
+            let lowercase_name = match lowercase_buf.get(&font.name) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            let family = match families
+                .iter()
+                .position(|family| family.lowercase_name == lowercase_name)
+            {
+                Some(family) => family,
+                None => {
+                    families.push(Family {
+                        name: font.name.clone(),
+                        lowercase_name,
+                        has_stretch: false,
+                        capabilities: 0,
+                        fonts: Vec::new(),
+                        scripts: Vec::new(),
+                    });
+                    families.len() - 1
+                }
+            };
+            let entry = &mut families[family];
+            entry.fonts.push(fonts.len());
+            entry.has_stretch |= font.attributes.stretch() != swash::Stretch::NORMAL;
+            entry.capabilities |= font.capabilities.0;
+            for script in &font.scripts {
+                if !entry.scripts.contains(script) {
+                    entry.scripts.push(*script);
+                }
+            }
+            fonts.push((
+                family,
+                font.attributes,
+                source,
+                font.index,
+                font.color_format,
+            ));
+        }
+    }
+    // Families are looked up by binary search on their lowercase names, which
+    // determines their identifiers.
+    let mut order = (0..families.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| {
+        families[*a]
+            .lowercase_name
+            .cmp(&families[*b].lowercase_name)
+    });
+    let mut ids = vec![0; families.len()];
+    for (id, family) in order.iter().enumerate() {
+        ids[*family] = id;
+    }
+    // Chains list families in the order their files were given.
+    let chain = |f: &dyn Fn(&Family) -> bool| {
+        families
+            .iter()
+            .enumerate()
+            .filter(|(_, family)| f(family))
+            .map(|(index, _)| ids[index])
+            .collect::<Vec<_>>()
+    };
+    let has = |flag: u8| move |family: &Family| family.capabilities & flag != 0;
+    let mut default_families = chain(&|family| !has(Capabilities::COLOR)(family));
+    if default_families.is_empty() {
+        default_families = chain(&|_| true);
+    }
+    let mut script_fallbacks = BTreeMap::<[u8; 4], Vec<usize>>::new();
+    let mut cjk_families = vec![Vec::new(); CJK_FAMILY_COUNT];
+    for (index, family) in families.iter().enumerate() {
+        for (script, cjk) in &family.scripts {
+            let chain = if *script == swash::text::Script::Han {
+                &mut cjk_families[*cjk as usize]
+            } else {
+                let tag = super::script_tags::script_tag(*script);
+                script_fallbacks.entry(tag).or_default()
+            };
+            if !chain.contains(&ids[index]) {
+                chain.push(ids[index]);
+            }
+        }
+    }
+    let generic_families = GenericFamily::ALL
+        .iter()
+        .map(|generic| match generic {
+            GenericFamily::Monospace | GenericFamily::UiMonospace => {
+                chain(&has(Capabilities::MONOSPACE))
+            }
+            GenericFamily::Emoji => chain(&has(Capabilities::COLOR)),
+            GenericFamily::Math => chain(&has(Capabilities::MATH)),
+            _ => Vec::new(),
+        })
+        .collect::<Vec<_>>();
+
+    let family_ids = |ids: &[usize]| {
+        let ids = ids
+            .iter()
+            .map(|id| format!("::fount::FamilyId::from_raw({})", id))
+            .collect::<Vec<_>>();
+        format!("&[{}]", ids.join(", "))
+    };
+    let mut out = String::new();
+    // Writing to a string cannot fail.
+    let _ = (|| -> std::fmt::Result {
+        writeln!(out, "{{")?;
+        writeln!(
+            out,
+            "static COLLECTION: ::fount::embed::StaticCollectionData = ::fount::embed::StaticCollectionData {{"
+        )?;
+        writeln!(out, "search_paths: &[],")?;
+        writeln!(out, "families: &[")?;
+        for &index in &order {
+            let family = &families[index];
+            let font_ids = family
+                .fonts
+                .iter()
+                .map(|id| format!("::fount::FontId::from_raw({})", id))
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "::fount::embed::StaticFamilyData {{ name: {:?}, lowercase_name: {:?}, has_stretch: {}, capabilities: {}, fonts: &[{}] }},",
+                family.name,
+                family.lowercase_name,
+                family.has_stretch,
+                family.capabilities,
+                font_ids.join(", ")
+            )?;
+        }
+        writeln!(out, "],")?;
+        writeln!(out, "fonts: &[")?;
+        for (family, attributes, source, index, color_format) in &fonts {
+            // The variant names of the format are also its paths.
+            let color_format = match color_format {
+                Some(format) => format!("Some(::fount::ColorFormat::{:?})", format),
+                None => "None".to_owned(),
+            };
+            writeln!(
+                out,
+                "::fount::embed::StaticFontData {{ family: ::fount::FamilyId::from_raw({}), attributes: ::fount::embed::Attributes({}), source: ::fount::SourceId::from_raw({}), index: {}, color_format: {} }},",
+                ids[*family], attributes.0, source, index, color_format
+            )?;
+        }
+        writeln!(out, "],")?;
+        writeln!(out, "sources: &[")?;
+        for (file_name, path) in &sources {
+            writeln!(
+                out,
+                "::fount::embed::StaticSourceData {{ file_name: {:?}, data: Some(include_bytes!({:?})) }},",
+                file_name, path
+            )?;
+        }
+        writeln!(out, "],")?;
+        writeln!(out, "default_families: {},", family_ids(&default_families))?;
+        writeln!(out, "script_fallbacks: &[")?;
+        for (tag, families) in &script_fallbacks {
+            writeln!(
+                out,
+                "::fount::embed::StaticScriptFallbacks {{ script: *b{:?}, families: {} }},",
+                String::from_utf8_lossy(tag),
+                family_ids(families)
+            )?;
+        }
+        writeln!(out, "],")?;
+        let arrays = |lists: &[Vec<usize>]| {
+            let lists = lists.iter().map(|ids| family_ids(ids)).collect::<Vec<_>>();
+            format!("[{}]", lists.join(", "))
+        };
+        writeln!(out, "generic_families: {},", arrays(&generic_families))?;
+        writeln!(out, "cjk_families: {},", arrays(&cjk_families))?;
+        writeln!(out, "}};")?;
+        writeln!(out, "&COLLECTION")?;
+        writeln!(out, "}}")
+    })();
+    Ok(out)
+}
@@ -10,17 +10,87 @@ pub struct FontData {
 
 impl FontData {
     /// Creates font data from the specified bytes.
+    ///
+    /// With the `woff` feature enabled, WOFF and WOFF2 data is decompressed
+    /// to an OpenType font.
     pub fn new(data: Vec<u8>) -> Self {
+        #[cfg(feature = "woff")]
+        let data = super::woff::decode(&data).unwrap_or(data);
         Self {
             inner: Arc::new(FontDataInner::Memory(data)),
         }
     }
 
+    /// Creates font data that refers to bytes with a static lifetime, such
+    /// as fonts embedded with `include_bytes!`, without copying them.
+    pub fn from_static(data: &'static [u8]) -> Self {
+        #[cfg(feature = "woff")]
+        if let Some(data) = super::woff::decode(data) {
+            return Self::new(data);
+        }
+        Self {
+            inner: Arc::new(FontDataInner::Static(data)),
+        }
+    }
+
+    /// Creates font data from the bytes produced by a reader, such as
+    /// standard input or a network stream.
+    ///
+    /// Reading stops with an error of kind
+    /// [`InvalidData`](std::io::ErrorKind::InvalidData) once more than
+    /// `limit` bytes have been read, which bounds the memory consumed by
+    /// untrusted input. WOFF and WOFF2 data is decompressed as described in
+    /// [`new`](Self::new), and the decompressed font is subject to the same
+    /// limit.
+    pub fn from_reader(reader: impl std::io::Read, limit: usize) -> Result<Self, std::io::Error> {
+        use std::io::Read;
+        let too_large = || {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "font data exceeds the size limit",
+            )
+        };
+        let mut data = Vec::new();
+        reader
+            .take((limit as u64).saturating_add(1))
+            .read_to_end(&mut data)?;
+        if data.len() > limit {
+            return Err(too_large());
+        }
+        #[cfg(feature = "woff")]
+        {
+            // Check the declared size before decompressing so that a small
+            // file cannot expand into a huge allocation, and the actual size
+            // afterwards in case the header understates it.
+            if super::woff::decoded_len(&data).is_some_and(|len| len > limit) {
+                return Err(too_large());
+            }
+            if let Some(decoded) = super::woff::decode(&data) {
+                if decoded.len() > limit {
+                    return Err(too_large());
+                }
+                data = decoded;
+            }
+        }
+        Ok(Self::new(data))
+    }
+
     /// Creates font data from the file at the specified path.
+    ///
+    /// The file is memory mapped rather than read so that only the pages
+    /// that are accessed count towards the resident size of the process.
+    /// This keeps large CJK fonts and collections cheap to load. The file
+    /// should not be truncated while the data is alive. With the `woff`
+    /// feature enabled, WOFF and WOFF2 files are decompressed into memory
+    /// instead.
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
         let path = path.as_ref();
         let file = std::fs::File::open(path)?;
         let map = unsafe { memmap2::Mmap::map(&file)? };
+        #[cfg(feature = "woff")]
+        if let Some(data) = super::woff::decode(&map) {
+            return Ok(Self::new(data));
+        }
         Ok(Self {
             inner: Arc::new(FontDataInner::Mapped(map)),
         })
@@ -62,6 +132,7 @@ impl AsRef<[u8]> for FontData {
 enum FontDataInner {
     Memory(Vec<u8>),
     Mapped(memmap2::Mmap),
+    Static(&'static [u8]),
 }
 
 impl FontDataInner {
@@ -69,6 +140,7 @@ impl FontDataInner {
         match self {
             Self::Memory(data) => data,
             Self::Mapped(mmap) => &*mmap,
+            Self::Static(data) => data,
         }
     }
 }
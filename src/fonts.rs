@@ -0,0 +1,189 @@
+//! High level facade over a library and context.
+
+use super::context::is_ignorable;
+use super::*;
+use core::ops::Range;
+use swash::text::{Codepoint as _, Script};
+use swash::FontRef;
+
+/// Convenience wrapper around a [`Library`] and [`FontContext`] that exposes
+/// the most common operations.
+///
+/// This is intended as a starting point for consumers that simply need to
+/// select fonts for a string. The underlying context remains available for
+/// more advanced queries.
+///
+/// # Example
+/// ```no_run
+/// use fount::prelude::*;
+///
+/// let fonts = Fonts::new();
+/// let families = [FamilyKey::from("Arial"), GenericFamily::SansSerif.into()];
+/// for run in fonts.fallback_for_text("Hello, мир", &families, Attributes::default(), None) {
+///     let handle = fonts.load(&run.font).unwrap();
+///     let font = handle.font_ref().unwrap();
+///     // shape `run.range` with `font`...
+/// }
+/// ```
+pub struct Fonts {
+    context: FontContext,
+}
+
+impl Fonts {
+    /// Creates a new facade over the default font library.
+    pub fn new() -> Self {
+        Self::with_library(&Library::default())
+    }
+
+    /// Creates a new facade over the specified font library.
+    pub fn with_library(library: &Library) -> Self {
+        Self {
+            context: FontContext::new(library),
+        }
+    }
+
+    /// Returns the underlying font library.
+    pub fn library(&self) -> &Library {
+        self.context.library()
+    }
+
+    /// Returns the underlying font context.
+    pub fn context(&self) -> &FontContext {
+        &self.context
+    }
+
+    /// Returns a mutable reference to the underlying font context.
+    pub fn context_mut(&mut self) -> &mut FontContext {
+        &mut self.context
+    }
+
+    /// Returns the font that best matches the attributes from the first
+    /// family in the list that can be resolved, falling back to the default
+    /// families.
+    pub fn resolve(&self, families: &[FamilyKey], attributes: Attributes) -> Option<FontEntry> {
+        self.context
+            .match_font(families, attributes, Script::Latin, None)
+    }
+
+    /// Splits the text into runs, each paired with a font that supports the
+    /// characters in the run.
+    ///
+    /// Characters with an override added by
+    /// [`Library::add_char_override`](super::Library::add_char_override)
+    /// are assigned that font. The font resolved from the family list is
+    /// used wherever it maps the other characters, and the remaining
+    /// characters are assigned a font from the fallback chain for their
+    /// script. Characters that no font supports, along with
+    /// whitespace and other characters that are not expected to be mapped,
+    /// remain in the current run.
+    pub fn fallback_for_text(
+        &self,
+        text: &str,
+        families: &[FamilyKey],
+        attributes: Attributes,
+        locale: Option<Locale>,
+    ) -> Vec<FontRun> {
+        let mut runs: Vec<FontRun> = Vec::new();
+        let primary = match self.resolve(families, attributes) {
+            Some(font) => font,
+            None => return runs,
+        };
+        let covers = |font: &FontEntry, ch: char| {
+            self.context
+                .coverage(font.id())
+                .map(|coverage| coverage.contains(ch))
+                .unwrap_or(false)
+        };
+        // True if the current run was started by an override, in which case
+        // it only extends to characters with the same override.
+        let mut in_override = false;
+        for (offset, ch) in text.char_indices() {
+            let end = offset + ch.len_utf8();
+            // Overrides take precedence over both the current run and the
+            // primary font.
+            let overridden = self.context.char_override(ch, attributes);
+            if let (None, false, Some(run)) = (overridden, in_override, runs.last_mut()) {
+                // A fallback run only continues over characters that the
+                // primary font does not map.
+                let continues = covers(&run.font, ch)
+                    && (run.font.id() == primary.id() || !covers(&primary, ch));
+                if ch.is_whitespace() || is_ignorable(ch) || continues {
+                    run.range.end = end;
+                    continue;
+                }
+            }
+            let font = if let Some(font) = overridden {
+                font
+            } else if covers(&primary, ch) {
+                primary
+            } else {
+                match self.context.fallback_for_char(ch, ch.script(), locale) {
+                    Some(font) => font,
+                    None => match runs.last_mut() {
+                        Some(run) => {
+                            run.range.end = end;
+                            continue;
+                        }
+                        None => primary,
+                    },
+                }
+            };
+            match runs.last_mut() {
+                Some(run) if run.font.id() == font.id() => run.range.end = end,
+                _ => runs.push(FontRun {
+                    range: offset..end,
+                    font,
+                }),
+            }
+            in_override = overridden.is_some();
+        }
+        runs
+    }
+
+    /// Loads the data for the specified font.
+    pub fn load(&self, font: &FontEntry) -> Option<FontHandle> {
+        Some(FontHandle {
+            data: self.context.load(font.source())?,
+            index: font.index(),
+            cache_key: font.cache_key(),
+        })
+    }
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Range of text paired with the font selected to render it.
+#[derive(Clone)]
+pub struct FontRun {
+    /// Byte range of the run in the source text.
+    pub range: Range<usize>,
+    /// Font selected for the run.
+    pub font: FontEntry,
+}
+
+/// Loaded font data along with the location of a font within it.
+#[derive(Clone)]
+pub struct FontHandle {
+    /// Data containing the font.
+    pub data: FontData,
+    /// Index of the font in the data.
+    pub index: u32,
+    /// Cache key for the font.
+    pub cache_key: CacheKey,
+}
+
+impl FontHandle {
+    /// Returns a reference to the font suitable for use with swash.
+    ///
+    /// The cache key of the reference is the one recorded by the library so
+    /// that it remains stable between loads.
+    pub fn font_ref(&self) -> Option<FontRef<'_>> {
+        let mut font = FontRef::from_index(&self.data, self.index as usize)?;
+        font.key = self.cache_key;
+        Some(font)
+    }
+}
@@ -16,6 +16,7 @@ pub struct SourceId(pub(crate) u32);
 
 const INDEX_MASK: u32 = 0x7FFFFFFF;
 const USER_BIT: u32 = 0x80000000;
+const PLACEHOLDER_BIT: u32 = 0x40000000;
 
 macro_rules! impl_id {
     ($name: ident) => {
@@ -29,7 +30,7 @@ macro_rules! impl_id {
             }
 
             pub(crate) fn alloc(index: usize, is_user: bool) -> Option<Self> {
-                if index >= i32::MAX as usize {
+                if index >= PLACEHOLDER_BIT as usize {
                     return None;
                 }
                 let mut id = index as u32;
@@ -39,6 +40,21 @@ macro_rules! impl_id {
                 Some(Self(id))
             }
 
+            /// Creates an identifier from its raw representation.
+            ///
+            /// This is intended for adapters that present fonts from other
+            /// systems through the entry types of this crate. An identifier
+            /// created this way only refers to an item in a library if the
+            /// value was originally produced by [`to_raw`](Self::to_raw).
+            pub const fn from_raw(value: u32) -> Self {
+                Self(value)
+            }
+
+            /// Returns the raw representation of the identifier.
+            pub const fn to_raw(self) -> u32 {
+                self.0
+            }
+
             /// Returns true if the identifier represents a dynamically
             /// registered user font.
             pub fn is_user_font(self) -> bool {
@@ -53,5 +69,22 @@ macro_rules! impl_id {
 }
 
 impl_id!(FamilyId);
+
+impl FamilyId {
+    pub(crate) const fn new_placeholder(index: u32) -> Self {
+        Self(index | PLACEHOLDER_BIT)
+    }
+
+    /// Returns true if the identifier represents a placeholder for a
+    /// missing font created with
+    /// [`Library::add_placeholder`](super::Library::add_placeholder).
+    pub fn is_placeholder(self) -> bool {
+        self.0 & (USER_BIT | PLACEHOLDER_BIT) == PLACEHOLDER_BIT
+    }
+
+    pub(crate) fn placeholder_index(self) -> usize {
+        (self.0 & !PLACEHOLDER_BIT) as usize
+    }
+}
 impl_id!(FontId);
 impl_id!(SourceId);
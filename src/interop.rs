@@ -0,0 +1,143 @@
+//! Conversion to and from [`fontdb`] databases.
+
+use super::data::{SourceData, SourceDataKind, SourceDataStatus};
+use super::font::FontData;
+use super::scan::ScannedFont;
+use super::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Stretch values in the order of the OS/2 width classes used by fontdb.
+const STRETCHES: [(Stretch, fontdb::Stretch); 9] = [
+    (Stretch::ULTRA_CONDENSED, fontdb::Stretch::UltraCondensed),
+    (Stretch::EXTRA_CONDENSED, fontdb::Stretch::ExtraCondensed),
+    (Stretch::CONDENSED, fontdb::Stretch::Condensed),
+    (Stretch::SEMI_CONDENSED, fontdb::Stretch::SemiCondensed),
+    (Stretch::NORMAL, fontdb::Stretch::Normal),
+    (Stretch::SEMI_EXPANDED, fontdb::Stretch::SemiExpanded),
+    (Stretch::EXPANDED, fontdb::Stretch::Expanded),
+    (Stretch::EXTRA_EXPANDED, fontdb::Stretch::ExtraExpanded),
+    (Stretch::ULTRA_EXPANDED, fontdb::Stretch::UltraExpanded),
+];
+
+impl FontContext {
+    /// Creates a fontdb database containing every font in the context.
+    ///
+    /// Fonts backed by files are exported by path and fonts registered from
+    /// memory share their buffers with the database, so no font data is
+    /// scanned or copied. PostScript names are not recorded by the library
+    /// and are left empty.
+    pub fn to_fontdb(&self) -> fontdb::Database {
+        let mut db = fontdb::Database::new();
+        let search_paths = self
+            .library()
+            .inner
+            .system()
+            .source_paths()
+            .map(|path| path.to_owned())
+            .collect::<Vec<_>>();
+        for family in self.families() {
+            let monospaced = family.capabilities().is_monospace();
+            for font in family.fonts().filter_map(|id| self.font(id)) {
+                let source = match self.source(font.source()).map(|source| source.kind) {
+                    Some(SourceKind::Path(path)) => fontdb::Source::File((*path).clone()),
+                    Some(SourceKind::FileName(name)) => {
+                        match search_paths
+                            .iter()
+                            .map(|dir| Path::new(dir).join(&name))
+                            .find(|path| path.is_file())
+                        {
+                            Some(path) => fontdb::Source::File(path),
+                            None => continue,
+                        }
+                    }
+                    Some(SourceKind::Data(data)) => fontdb::Source::Binary(Arc::new(data)),
+                    None => continue,
+                };
+                let (stretch, weight, style) = font.attributes().parts();
+                db.push_face_info(fontdb::FaceInfo {
+                    id: fontdb::ID::dummy(),
+                    source,
+                    index: font.index(),
+                    families: vec![(
+                        family.name().to_owned(),
+                        fontdb::Language::English_UnitedStates,
+                    )],
+                    post_script_name: String::new(),
+                    style: match style {
+                        Style::Normal => fontdb::Style::Normal,
+                        Style::Italic => fontdb::Style::Italic,
+                        Style::Oblique(_) => fontdb::Style::Oblique,
+                    },
+                    weight: fontdb::Weight(weight.0),
+                    stretch: STRETCHES
+                        .iter()
+                        .min_by_key(|(value, _)| (value.raw() as i32 - stretch.raw() as i32).abs())
+                        .map(|(_, value)| *value)
+                        .unwrap_or(fontdb::Stretch::Normal),
+                    monospaced,
+                });
+            }
+        }
+        db
+    }
+
+    /// Registers the faces of a fontdb database with the library.
+    ///
+    /// The metadata recorded by fontdb is used directly so the fonts are not
+    /// scanned again. As a consequence, the imported fonts do not contribute
+    /// to the script fallback chains. Faces backed by files are referenced by
+    /// path while other faces are copied into memory.
+    pub fn register_fontdb(&self, db: &fontdb::Database) -> Option<Registration> {
+        let mut files: HashMap<&Path, Vec<ScannedFont>> = HashMap::new();
+        let mut reg = Registration::default();
+        for face in db.faces() {
+            let name = match face.families.first() {
+                Some((name, _)) => name,
+                None => continue,
+            };
+            let stretch = STRETCHES
+                .iter()
+                .find(|(_, value)| *value == face.stretch)
+                .map(|(value, _)| *value)
+                .unwrap_or(Stretch::NORMAL);
+            let style = match face.style {
+                fontdb::Style::Normal => Style::Normal,
+                fontdb::Style::Italic => Style::Italic,
+                fontdb::Style::Oblique => Style::Oblique(Default::default()),
+            };
+            let weight = Weight(face.weight.0);
+            let attributes = Attributes::new(stretch, weight, style);
+            let mut font = ScannedFont::from_metadata(name, face.index, attributes);
+            if face.monospaced {
+                font.capabilities.insert(Capabilities::MONOSPACE);
+            }
+            match &face.source {
+                fontdb::Source::File(path) => files.entry(path.as_path()).or_default().push(font),
+                _ => {
+                    let data = match db.with_face_data(face.id, |data, _| data.to_vec()) {
+                        Some(data) => FontData::new(data),
+                        None => continue,
+                    };
+                    let source = SourceData {
+                        kind: SourceDataKind::Data(data),
+                        status: RwLock::new(SourceDataStatus::Vacant),
+                        trust: SourceTrust::Registered,
+                    };
+                    self.commit_fonts(&[font], source, &mut reg);
+                }
+            }
+        }
+        for (path, fonts) in files {
+            if let Ok(source) = SourceData::from_path(path) {
+                self.commit_fonts(&fonts, source, &mut reg);
+            }
+        }
+        if reg.fonts.is_empty() {
+            None
+        } else {
+            Some(reg)
+        }
+    }
+}
@@ -1,24 +1,65 @@
 #![allow(dead_code, unused_variables)]
 
+mod attributes;
+mod cache;
+mod casefold;
+#[cfg(feature = "config")]
+mod config;
 mod context;
+mod coverage;
+mod css;
 mod data;
+pub mod embed;
 mod font;
+mod fonts;
 mod id;
+#[cfg(feature = "fontdb")]
+mod interop;
 mod library;
+mod locale;
+mod metrics;
+mod names;
+#[cfg(not(feature = "font-kit"))]
+mod native;
+mod pattern;
+pub mod prelude;
+mod resolve;
 mod scan;
 mod script_tags;
 mod system;
+mod tables;
+mod vfs;
+#[cfg(feature = "notify")]
+mod watch;
+#[cfg(feature = "woff")]
+mod woff;
 
-pub use context::FontContext;
+pub use attributes::AttributesExt;
+#[cfg(feature = "config")]
+pub use config::{ConfigError, FontConfig};
+pub use context::{FontContext, ScopedRegistration};
+pub use coverage::Coverage;
 pub use data::SourcePaths;
 pub use font::FontData;
+pub use fonts::{FontHandle, FontRun, Fonts};
 pub use id::{FamilyId, FontId, SourceId};
-pub use library::{Library, LibraryBuilder};
+pub use library::{BuildFuture, ExternalFace, ExternalSource, Library, LibraryBuilder};
+pub use locale::{LanguageTag, ParseLanguageTagError};
+pub use metrics::{LineMetrics, MetricsPolicy};
+pub use pattern::FontPattern;
+pub use resolve::{
+    GenericFamilyResolver, LastResortResolver, NamespaceResolver, OverrideResolver, Resolver,
+    SystemFontResolver, UserFontResolver,
+};
+pub use scan::{FontScanner, PooledScanner, ScannedFont, ScannerPool};
+pub use vfs::{FileMetadata, FileSystem, OsFileSystem};
+#[cfg(feature = "notify")]
+pub use watch::FontWatcher;
 
 pub use swash::text::Language as Locale;
 
 use data::*;
-use std::{path::PathBuf, sync::Arc};
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
 use swash::{Attributes, CacheKey, Stretch, Style, Weight};
 
 use core::fmt;
@@ -33,9 +74,31 @@ pub enum GenericFamily {
     SystemUi = 3,
     Cursive = 4,
     Emoji = 5,
+    Math = 6,
+    Fantasy = 7,
+    UiSerif = 8,
+    UiSansSerif = 9,
+    UiMonospace = 10,
+    UiRounded = 11,
 }
 
 impl GenericFamily {
+    /// All generic families, in order of their discriminants.
+    pub const ALL: [Self; 12] = [
+        Self::Serif,
+        Self::SansSerif,
+        Self::Monospace,
+        Self::SystemUi,
+        Self::Cursive,
+        Self::Emoji,
+        Self::Math,
+        Self::Fantasy,
+        Self::UiSerif,
+        Self::UiSansSerif,
+        Self::UiMonospace,
+        Self::UiRounded,
+    ];
+
     /// Parses a generic family from a CSS generic family name.
     ///
     /// # Example
@@ -54,11 +117,40 @@ impl GenericFamily {
             "cursive" => Self::Cursive,
             "system-ui" => Self::SystemUi,
             "emoji" => Self::Emoji,
+            "math" => Self::Math,
+            "fantasy" => Self::Fantasy,
+            "ui-serif" => Self::UiSerif,
+            "ui-sans-serif" => Self::UiSansSerif,
+            "ui-monospace" => Self::UiMonospace,
+            "ui-rounded" => Self::UiRounded,
             _ => return None,
         })
     }
 }
 
+impl std::str::FromStr for GenericFamily {
+    type Err = ParseGenericFamilyError;
+
+    /// Parses a generic family from a CSS generic family keyword without
+    /// regard to case, so that generic families can be stored as strings in
+    /// configuration files.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(&s.to_ascii_lowercase()).ok_or(ParseGenericFamilyError)
+    }
+}
+
+/// Error returned when a string is not a generic family keyword.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ParseGenericFamilyError;
+
+impl fmt::Display for ParseGenericFamilyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown generic family keyword")
+    }
+}
+
+impl std::error::Error for ParseGenericFamilyError {}
+
 impl fmt::Display for GenericFamily {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let name = match self {
@@ -68,20 +160,125 @@ impl fmt::Display for GenericFamily {
             Self::Cursive => "cursive",
             Self::SystemUi => "system-ui",
             Self::Emoji => "emoji",
+            Self::Math => "math",
+            Self::Fantasy => "fantasy",
+            Self::UiSerif => "ui-serif",
+            Self::UiSansSerif => "ui-sans-serif",
+            Self::UiMonospace => "ui-monospace",
+            Self::UiRounded => "ui-rounded",
         };
         write!(f, "{}", name)
     }
 }
 
+/// Key that selects a font family by name, generic family or identifier.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FamilyKey<'a> {
+    /// Family with the specified name.
+    Named(Cow<'a, str>),
+    /// Generic font family.
+    Generic(GenericFamily),
+    /// Family with the specified identifier.
+    Id(FamilyId),
+}
+
+impl<'a> FamilyKey<'a> {
+    /// Parses the value of a CSS `font-family` property into a prioritized
+    /// list of keys.
+    ///
+    /// Quoted names and sequences of identifiers produce named families
+    /// while unquoted generic family keywords produce generic families.
+    /// Malformed entries are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// use fount::{FamilyKey, GenericFamily};
+    ///
+    /// let keys = FamilyKey::parse_list("\"Fira Sans\", Noto  Sans, sans-serif");
+    /// assert_eq!(
+    ///     keys,
+    ///     [
+    ///         FamilyKey::from("Fira Sans"),
+    ///         FamilyKey::from("Noto Sans"),
+    ///         FamilyKey::from(GenericFamily::SansSerif),
+    ///     ]
+    /// );
+    /// ```
+    pub fn parse_list(value: &'a str) -> Vec<Self> {
+        css::parse_family_list(value)
+    }
+}
+
+impl<'a> From<&'a str> for FamilyKey<'a> {
+    fn from(name: &'a str) -> Self {
+        Self::Named(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for FamilyKey<'_> {
+    fn from(name: String) -> Self {
+        Self::Named(Cow::Owned(name))
+    }
+}
+
+impl From<GenericFamily> for FamilyKey<'_> {
+    fn from(family: GenericFamily) -> Self {
+        Self::Generic(family)
+    }
+}
+
+impl From<FamilyId> for FamilyKey<'_> {
+    fn from(id: FamilyId) -> Self {
+        Self::Id(id)
+    }
+}
+
 /// Entry for a font family in a font library.
 #[derive(Clone)]
 pub struct FamilyEntry {
     id: FamilyId,
     has_stretch: bool,
+    capabilities: Capabilities,
     kind: FontFamilyKind,
 }
 
 impl FamilyEntry {
+    /// Creates a family entry from custom data.
+    ///
+    /// This allows adapters wrapping other font systems to present foreign
+    /// families to code that consumes entries from this crate. The family
+    /// capabilities are derived from the attributes of the fonts.
+    pub fn new(
+        id: FamilyId,
+        name: impl Into<String>,
+        fonts: impl IntoIterator<Item = (FontId, Attributes)>,
+    ) -> Self {
+        let fonts = fonts
+            .into_iter()
+            .map(|(id, attributes)| {
+                let (stretch, weight, style) = attributes.parts();
+                (id, stretch, weight, style)
+            })
+            .collect::<Vec<_>>();
+        let has_stretch = fonts.iter().any(|font| font.1 != Stretch::NORMAL);
+        let capabilities = Capabilities::from_attributes(fonts.iter());
+        Self {
+            id,
+            has_stretch,
+            capabilities,
+            kind: FontFamilyKind::Dynamic(Arc::new(FamilyData {
+                name: name.into(),
+                has_stretch,
+                capabilities,
+                glyph_count: 0,
+                file_size: 0,
+                refined: true,
+                priority: SourcePriority::Application,
+                fonts,
+            })),
+        }
+    }
+
     /// Returns the identifier for the font family.
     pub fn id(&self) -> FamilyId {
         self.id
@@ -95,12 +292,111 @@ impl FamilyEntry {
         }
     }
 
+    /// Returns a summary of the capabilities of the fonts in the family.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Returns true if any font in the family is fixed pitch, as marked in
+    /// the post table or detected from the advance widths of its glyphs.
+    pub fn is_monospace(&self) -> bool {
+        self.capabilities.is_monospace()
+    }
+
     /// Returns an iterator over the fonts that are members of the family.
     pub fn fonts<'a>(&'a self) -> impl Iterator<Item = FontId> + Clone + 'a {
-        self.fonts_with_attrs().map(|font| font.0)
+        self.as_family_ref().fonts()
     }
 
     /// Returns the font that most closely matches the specified attributes.
+    ///
+    /// This follows the CSS font matching algorithm: the nearest stretch is
+    /// selected first, then the nearest style and finally the nearest weight.
+    /// A font is always returned for a non-empty family.
+    pub fn query(&self, attributes: Attributes) -> Option<FontId> {
+        self.as_family_ref().query(attributes)
+    }
+
+    /// Returns the font that most closely matches the specified stretch,
+    /// weight and style.
+    ///
+    /// This is a convenience for [`query`](Self::query) when the attributes
+    /// are available as separate components.
+    pub fn query_parts(&self, stretch: Stretch, weight: Weight, style: Style) -> Option<FontId> {
+        self.query(Attributes::new(stretch, weight, style))
+    }
+
+    /// Returns a borrowed view of the family.
+    pub fn as_family_ref(&self) -> FamilyRef<'_> {
+        let (name, fonts) = match &self.kind {
+            FontFamilyKind::Static(name, fonts) => (*name, &**fonts),
+            FontFamilyKind::Dynamic(data) => (data.name.as_str(), data.fonts.as_slice()),
+        };
+        FamilyRef {
+            id: self.id,
+            name,
+            has_stretch: self.has_stretch,
+            capabilities: self.capabilities,
+            fonts,
+        }
+    }
+}
+
+/// Borrowed view of a font family in a font library.
+///
+/// This provides the same queries as [`FamilyEntry`] without cloning any
+/// part of the family. It is obtained with
+/// [`FontContext::with_family`] or
+/// [`FamilyEntry::as_family_ref`].
+#[derive(Copy, Clone)]
+pub struct FamilyRef<'a> {
+    pub(crate) id: FamilyId,
+    pub(crate) name: &'a str,
+    pub(crate) has_stretch: bool,
+    pub(crate) capabilities: Capabilities,
+    pub(crate) fonts: &'a [(FontId, Stretch, Weight, Style)],
+}
+
+impl<'a> FamilyRef<'a> {
+    /// Returns the identifier for the font family.
+    pub fn id(&self) -> FamilyId {
+        self.id
+    }
+
+    /// Returns the name of the font family.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns a summary of the capabilities of the fonts in the family.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Returns true if any font in the family is fixed pitch, as marked in
+    /// the post table or detected from the advance widths of its glyphs.
+    pub fn is_monospace(&self) -> bool {
+        self.capabilities.is_monospace()
+    }
+
+    /// Returns an iterator over the fonts that are members of the family.
+    pub fn fonts(&self) -> impl Iterator<Item = FontId> + Clone + 'a {
+        self.fonts.iter().map(|font| font.0)
+    }
+
+    /// Returns an iterator over the fonts that are members of the family
+    /// along with their attributes.
+    pub fn fonts_with_attributes(&self) -> impl Iterator<Item = (FontId, Attributes)> + Clone + 'a {
+        self.fonts
+            .iter()
+            .map(|&(id, stretch, weight, style)| (id, Attributes::new(stretch, weight, style)))
+    }
+
+    /// Returns the font that most closely matches the specified attributes.
+    ///
+    /// This follows the CSS font matching algorithm: the nearest stretch is
+    /// selected first, then the nearest style and finally the nearest weight.
+    /// A font is always returned for a non-empty family.
     pub fn query(&self, attributes: Attributes) -> Option<FontId> {
         let style = attributes.style();
         let weight = attributes.weight();
@@ -164,7 +460,7 @@ impl FamilyEntry {
                     let val = font.3;
                     match val {
                         Style::Oblique(_) => {
-                            matching_style = style;
+                            matching_style = val;
                             break;
                         }
                         Style::Italic => {
@@ -257,28 +553,132 @@ impl FamilyEntry {
         }
     }
 
-    fn fonts_with_attrs<'a>(
-        &'a self,
-    ) -> impl Iterator<Item = &(FontId, Stretch, Weight, Style)> + DoubleEndedIterator + Clone + 'a
-    {
-        let fonts = match &self.kind {
-            FontFamilyKind::Static(_, fonts) => *fonts,
-            FontFamilyKind::Dynamic(data) => &data.fonts,
-        };
-        fonts.iter()
+    /// Returns the font that most closely matches the specified stretch,
+    /// weight and style.
+    ///
+    /// This is a convenience for [`query`](Self::query) when the attributes
+    /// are available as separate components.
+    pub fn query_parts(&self, stretch: Stretch, weight: Weight, style: Style) -> Option<FontId> {
+        self.query(Attributes::new(stretch, weight, style))
+    }
+
+    fn fonts_with_attrs(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &'a (FontId, Stretch, Weight, Style)> + Clone {
+        self.fonts.iter()
+    }
+}
+
+/// Format of the color glyphs in a font.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ColorFormat {
+    /// Layered vector glyphs in the COLR and CPAL tables.
+    Colr,
+    /// Embedded color bitmaps in the CBDT and CBLC tables.
+    Cbdt,
+    /// Embedded color bitmaps in the sbix table.
+    Sbix,
+    /// SVG documents in the SVG table.
+    Svg,
+}
+
+/// Summary of boolean capabilities collected for a font family at scan time.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub(crate) const VARIABLE: u8 = 1;
+    pub(crate) const COLOR: u8 = 2;
+    pub(crate) const MONOSPACE: u8 = 4;
+    pub(crate) const ITALIC: u8 = 8;
+    pub(crate) const BOLD: u8 = 16;
+    pub(crate) const VERTICAL: u8 = 32;
+    pub(crate) const MATH: u8 = 64;
+
+    /// Returns capabilities derived solely from the attributes of the
+    /// specified fonts.
+    pub(crate) fn from_attributes<'a>(
+        fonts: impl Iterator<Item = &'a (FontId, Stretch, Weight, Style)>,
+    ) -> Self {
+        let mut caps = Self::default();
+        for font in fonts {
+            caps.insert_attributes(font.2, font.3);
+        }
+        caps
+    }
+
+    /// Returns capabilities of a static family from the recorded flags and
+    /// the attributes of its fonts.
+    pub(crate) fn from_static(flags: u8, fonts: &[(FontId, Stretch, Weight, Style)]) -> Self {
+        let mut caps = Self::from_attributes(fonts.iter());
+        caps.insert(flags);
+        caps
+    }
+
+    pub(crate) fn insert(&mut self, flags: u8) {
+        self.0 |= flags;
+    }
+
+    pub(crate) fn insert_attributes(&mut self, weight: Weight, style: Style) {
+        if style != Style::Normal {
+            self.0 |= Self::ITALIC;
+        }
+        if weight >= Weight::SEMI_BOLD {
+            self.0 |= Self::BOLD;
+        }
+    }
+
+    pub(crate) fn union(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Returns true if any font in the family is a variable font.
+    pub fn is_variable(self) -> bool {
+        self.0 & Self::VARIABLE != 0
+    }
+
+    /// Returns true if any font in the family contains color glyphs.
+    pub fn has_color(self) -> bool {
+        self.0 & Self::COLOR != 0
+    }
+
+    /// Returns true if any font in the family is fixed pitch.
+    pub fn is_monospace(self) -> bool {
+        self.0 & Self::MONOSPACE != 0
+    }
+
+    /// Returns true if the family contains an italic or oblique font.
+    pub fn has_italic(self) -> bool {
+        self.0 & Self::ITALIC != 0
+    }
+
+    /// Returns true if the family contains a font with a weight of semi-bold
+    /// or heavier.
+    pub fn has_bold(self) -> bool {
+        self.0 & Self::BOLD != 0
+    }
+
+    /// Returns true if any font in the family supports vertical layout.
+    pub fn supports_vertical(self) -> bool {
+        self.0 & Self::VERTICAL != 0
+    }
+
+    /// Returns true if any font in the family contains a math table.
+    pub fn has_math(self) -> bool {
+        self.0 & Self::MATH != 0
     }
 }
 
 #[derive(Clone)]
 enum FontFamilyKind {
-    Static(&'static str, &'static [(FontId, Stretch, Weight, Style)]),
+    Static(&'static str, StaticFamilyFonts),
     Dynamic(Arc<FamilyData>),
 }
 
 /// Iterator over the font families in a font library.
 #[derive(Clone)]
 pub struct Families {
-    user: Arc<(u64, CollectionData)>,
+    user: Arc<CollectionData>,
     library: Library,
     pos: usize,
     stage: u8,
@@ -290,22 +690,22 @@ impl Iterator for Families {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             if self.stage == 0 {
-                let len = self.user.1.families.len();
+                let len = self.user.families.len();
                 if self.pos >= len {
                     self.stage = 1;
+                    self.pos = 0;
                     continue;
                 }
                 let pos = self.pos;
                 self.pos += 1;
-                return self.user.1.family(FamilyId::new_user(pos as u32));
+                return self.user.family(FamilyId::new_user(pos as u32));
             } else {
                 let pos = self.pos;
                 self.pos += 1;
                 return self
                     .library
                     .inner
-                    .system
-                    .borrow()
+                    .system()
                     .family(FamilyId::new(pos as u32));
             }
         }
@@ -321,9 +721,50 @@ pub struct FontEntry {
     index: u32,
     attributes: Attributes,
     cache_key: CacheKey,
+    metrics: Option<metrics::FaceMetrics>,
+    metrics_policy: MetricsPolicy,
+    glyph_count: u16,
+    file_size: u64,
+    variant: u16,
+    optical_size: Option<(f32, f32)>,
+    cjk: swash::text::Cjk,
+    color_format: Option<ColorFormat>,
+    trust: SourceTrust,
 }
 
 impl FontEntry {
+    /// Creates a font entry from custom data.
+    ///
+    /// This allows adapters wrapping other font systems to present foreign
+    /// fonts to code that consumes entries from this crate. The entry
+    /// receives a new cache key, has no line metrics, glyph count or file
+    /// size and is trusted as [`SourceTrust::Registered`].
+    pub fn new(
+        id: FontId,
+        family: FamilyId,
+        source: SourceId,
+        index: u32,
+        attributes: Attributes,
+    ) -> Self {
+        Self {
+            id,
+            family,
+            source,
+            index,
+            attributes,
+            cache_key: CacheKey::new(),
+            metrics: None,
+            metrics_policy: MetricsPolicy::default(),
+            glyph_count: 0,
+            file_size: 0,
+            variant: 0,
+            optical_size: None,
+            cjk: swash::text::Cjk::None,
+            color_format: None,
+            trust: SourceTrust::Registered,
+        }
+    }
+
     /// Returns the identifier for the font.
     pub fn id(&self) -> FontId {
         self.id
@@ -353,6 +794,127 @@ impl FontEntry {
     pub fn cache_key(&self) -> CacheKey {
         self.cache_key
     }
+
+    /// Returns the number of glyphs in the font, or zero if unknown.
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_count
+    }
+
+    /// Returns the size in bytes of the file or buffer containing the font,
+    /// or zero if unknown.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Returns the position of the font among fonts in the family with the
+    /// same attributes. This is zero except for duplicates kept by
+    /// [`DuplicatePolicy::KeepAll`].
+    pub fn variant(&self) -> u16 {
+        self.variant
+    }
+
+    /// Returns the range of point sizes that the font is designed for, if
+    /// known.
+    ///
+    /// The range is taken from the optical size axis of a variable font or
+    /// from the OS/2 table of a static font. Both bounds are inclusive, so
+    /// the exclusive upper bound of the OS/2 table is replaced by the
+    /// largest size below it.
+    pub fn optical_size_range(&self) -> Option<(f32, f32)> {
+        self.optical_size
+    }
+
+    /// Returns true if the font is designed for the specified point size.
+    /// Fonts without a known optical size range are designed for all sizes.
+    pub fn supports_optical_size(&self, size: f32) -> bool {
+        self.optical_size
+            .map(|(min, max)| min <= size && size <= max)
+            .unwrap_or(true)
+    }
+
+    /// Returns the distance in points from the specified size to the optical
+    /// size range of the font, or zero if the font is designed for the size.
+    pub fn optical_size_distance(&self, size: f32) -> f32 {
+        match self.optical_size {
+            Some((min, _)) if size < min => min - size,
+            Some((_, max)) if size > max => size - max,
+            _ => 0.,
+        }
+    }
+
+    /// Returns the CJK locale that the glyphs of the font are designed for,
+    /// or `Cjk::None` if the font is not specific to a region.
+    pub fn cjk(&self) -> swash::text::Cjk {
+        self.cjk
+    }
+
+    /// Returns the format of the color glyphs in the font, or `None` for a
+    /// font with outlines only.
+    ///
+    /// Renderers can use this to choose between the color and outline
+    /// rendering paths. When a font contains several formats, the first in
+    /// the order of [`ColorFormat`] is reported.
+    pub fn color_format(&self) -> Option<ColorFormat> {
+        self.color_format
+    }
+
+    /// Returns the level of trust in the origin of the source containing the
+    /// font.
+    pub fn trust(&self) -> SourceTrust {
+        self.trust
+    }
+
+    /// Returns the line metrics of the font as selected by the metrics policy
+    /// of the library.
+    pub fn metrics(&self) -> Option<LineMetrics> {
+        self.metrics_with_policy(self.metrics_policy)
+    }
+
+    /// Returns the line metrics of the font as selected by the specified
+    /// policy.
+    pub fn metrics_with_policy(&self, policy: MetricsPolicy) -> Option<LineMetrics> {
+        self.metrics.map(|metrics| metrics.select(policy))
+    }
+}
+
+/// Named instance of a variable font.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NamedInstance {
+    /// Name of the instance, such as "Bold Condensed".
+    pub name: String,
+    /// Axis tags paired with the user space coordinates of the instance.
+    pub coords: Vec<(swash::Tag, f32)>,
+    /// Attributes derived from the coordinates of the instance.
+    pub attributes: Attributes,
+}
+
+/// Variation axis of a variable font.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct VariationAxis {
+    /// Tag that identifies the axis, such as `wght`.
+    pub tag: swash::Tag,
+    /// Minimum value of the axis.
+    pub min: f32,
+    /// Default value of the axis.
+    pub default: f32,
+    /// Maximum value of the axis.
+    pub max: f32,
+}
+
+impl VariationAxis {
+    /// Clamps a value to the range of the axis.
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.max(self.min).min(self.max)
+    }
+}
+
+/// Font source that contains multiple faces, such as a TrueType collection.
+#[derive(Clone)]
+pub struct CollectionEntry {
+    /// Identifier of the source.
+    pub source: SourceId,
+    /// Fonts contained in the source, ordered by index.
+    pub fonts: Vec<FontEntry>,
 }
 
 /// Entry for a font source in a font library.
@@ -360,6 +922,7 @@ impl FontEntry {
 pub struct SourceEntry {
     id: SourceId,
     kind: SourceKind,
+    trust: SourceTrust,
 }
 
 impl SourceEntry {
@@ -372,6 +935,11 @@ impl SourceEntry {
     pub fn kind(&self) -> &SourceKind {
         &self.kind
     }
+
+    /// Returns the level of trust in the origin of the font source.
+    pub fn trust(&self) -> SourceTrust {
+        self.trust
+    }
 }
 
 /// The kind of a font source.
@@ -385,6 +953,402 @@ pub enum SourceKind {
     Data(FontData),
 }
 
+/// Priority of a root from which fonts are discovered.
+///
+/// When roots of different priorities provide families with the same name,
+/// name resolution selects the family from the highest priority root. All
+/// of the families remain available for enumeration.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub enum SourcePriority {
+    /// Fonts installed for all users of the system.
+    #[default]
+    System,
+    /// Fonts installed for the current user.
+    User,
+    /// Fonts bundled with the application.
+    Application,
+}
+
+/// Level of trust in the origin of a font source.
+///
+/// Levels are ordered from the most to the least trusted.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum SourceTrust {
+    /// Fonts installed for all users of the system.
+    System,
+    /// Fonts installed for the current user.
+    UserInstalled,
+    /// Fonts bundled with or registered by the application.
+    Registered,
+    /// Fonts from untrusted content, such as fonts embedded in a document
+    /// or downloaded from the web.
+    Remote,
+}
+
+impl From<SourcePriority> for SourceTrust {
+    fn from(priority: SourcePriority) -> Self {
+        match priority {
+            SourcePriority::System => Self::System,
+            SourcePriority::User => Self::UserInstalled,
+            SourcePriority::Application => Self::Registered,
+        }
+    }
+}
+
+/// Hook for excluding font sources from a context based on their trust
+/// level.
+///
+/// A level can be used directly as a policy that allows sources at least as
+/// trusted as the level.
+pub trait TrustPolicy: Send + Sync {
+    /// Returns true if fonts from the source may be used by the context.
+    fn allows(&self, source: SourceId, trust: SourceTrust) -> bool;
+}
+
+impl TrustPolicy for SourceTrust {
+    fn allows(&self, _source: SourceId, trust: SourceTrust) -> bool {
+        trust <= *self
+    }
+}
+
+/// Policy for fonts in a family that have the same attributes, such as the
+/// hinted and unhinted builds of a family installed side by side.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum DuplicatePolicy {
+    /// Keep the font that was discovered first.
+    #[default]
+    FirstWins,
+    /// Prefer fonts located under a path containing "hinted".
+    PreferHinted,
+    /// Prefer the font file with the most recent modification time.
+    PreferNewest,
+    /// Keep every font. Fonts other than the first are distinguished by
+    /// [`FontEntry::variant`] and attribute queries may select any of them.
+    KeepAll,
+}
+
+/// Kind of font preferred for emoji.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum EmojiPreference {
+    /// Prefer color emoji fonts, as in graphical applications.
+    #[default]
+    Color,
+    /// Prefer monochrome symbol fonts, as in terminals that render emoji
+    /// in the foreground color. Color emoji fonts remain available as a
+    /// fallback.
+    Monochrome,
+}
+
+/// Reason a family was included in a fallback chain.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FallbackReason {
+    /// The family was explicitly preferred for the script in the context.
+    Preferred,
+    /// The family declares support for the script, but coverage has not
+    /// been verified.
+    Declared,
+    /// The character map of the family was verified to cover the script,
+    /// whether or not the family declares support for it.
+    CoverageVerified,
+    /// The family is a default family used because no fallbacks are known
+    /// for the script.
+    Default,
+}
+
+/// Family in a fallback chain along with the reason it was selected.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FallbackCandidate {
+    /// Identifier of the candidate family.
+    pub family: FamilyId,
+    /// Reason the family was selected.
+    pub reason: FallbackReason,
+}
+
+impl FallbackCandidate {
+    fn defaults(families: &[FamilyId]) -> Vec<Self> {
+        families
+            .iter()
+            .map(|&family| Self {
+                family,
+                reason: FallbackReason::Default,
+            })
+            .collect()
+    }
+}
+
+/// Parameters of a font selection passed to a [`MatchScorer`].
+#[derive(Copy, Clone, Debug)]
+pub struct MatchRequest {
+    /// Requested attributes.
+    pub attributes: Attributes,
+    /// Requested point size, if any.
+    pub size: Option<f32>,
+    /// Script of the text being matched.
+    pub script: swash::text::Script,
+    /// Locale of the text being matched, if any.
+    pub locale: Option<Locale>,
+}
+
+/// Hook for biasing family and font selection.
+///
+/// Both methods return a score where higher values are preferred. The
+/// default implementations return zero, which leaves the standard selection
+/// unchanged.
+pub trait MatchScorer: Send + Sync {
+    /// Scores a family in the fallback chain. Families are tried in order of
+    /// descending score, keeping the order of the chain for equal scores.
+    /// Families requested explicitly are always tried in the order given.
+    fn score_family(&self, family: &FamilyEntry, request: &MatchRequest) -> f32 {
+        0.
+    }
+
+    /// Scores a font within the selected family. The font selected by the
+    /// standard matching algorithm is replaced only by a font with a strictly
+    /// higher score.
+    fn score_font(&self, font: &FontEntry, request: &MatchRequest) -> f32 {
+        0.
+    }
+}
+
+/// Hook for persisting the fonts a user registers and the fallback
+/// preferences they choose, so that they can be restored at startup.
+///
+/// The notification methods are called after a change has been applied and
+/// the default implementations do nothing. Families are identified by name
+/// since identifiers are not stable between runs. Fonts registered from
+/// memory are not reported because they cannot be restored from a path.
+pub trait FontPersistence: Send + Sync {
+    /// Returns the configuration to restore when the library is built.
+    fn load(&self) -> FontPreferences;
+
+    /// Called after the fonts in a file or directory are registered with
+    /// [`FontContext::register_font_file`] or
+    /// [`FontContext::register_font_dir`].
+    fn fonts_registered(&self, path: &std::path::Path) {}
+
+    /// Called after a family is preferred for a script with
+    /// [`Library::prefer_for_script`], or with `None` after the preference
+    /// is cleared. Preferences set on a single [`FontContext`] are not
+    /// reported.
+    fn script_preference_changed(&self, script: swash::text::Script, family: Option<&str>) {}
+
+    /// Called after a character override is added with
+    /// [`Library::add_char_override`], or with `None` after it is removed.
+    fn char_override_changed(&self, ch: char, family: Option<&str>) {}
+}
+
+/// Font configuration restored through a [`FontPersistence`] hook.
+///
+/// Fonts are registered before the preferences are applied, so the
+/// preferences may name families from the restored fonts. Paths that cannot
+/// be read and families that cannot be resolved are ignored.
+#[derive(Clone, Default, Debug)]
+pub struct FontPreferences {
+    /// Font files and directories to register.
+    pub font_paths: Vec<PathBuf>,
+    /// Families preferred for scripts in every new context.
+    pub script_families: Vec<(swash::text::Script, String)>,
+    /// Families selected for individual characters.
+    pub char_overrides: Vec<(char, String)>,
+}
+
+/// Family as resolved for a generic or CJK bucket.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResolvedFamily {
+    /// Identifier of the family.
+    pub id: FamilyId,
+    /// Name of the family.
+    pub name: String,
+}
+
+/// Font requested by a document that is not installed, recorded by a
+/// placeholder family. See [`Library::add_placeholder`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MissingFont {
+    /// Requested family name.
+    pub name: String,
+    /// Requested attributes.
+    pub attributes: Attributes,
+}
+
+/// Freely available font family that could be installed to cover a script.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FontSuggestion {
+    /// Script that lacks coverage.
+    pub script: swash::text::Script,
+    /// Name of a font family that covers the script.
+    pub family: &'static str,
+}
+
+/// Font selected to render a character with requested attributes.
+#[derive(Copy, Clone)]
+pub struct MatchedFont {
+    /// Selected font.
+    pub font: FontEntry,
+    /// Script detected for the character.
+    pub script: swash::text::Script,
+    /// Synthesis required to approximate the requested attributes with the
+    /// selected font.
+    pub synthesis: swash::Synthesis,
+}
+
+/// Error returned when registering fonts fails.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RegisterError {
+    /// The library has been frozen and can no longer be modified.
+    Frozen,
+    /// The data did not contain any fonts that were not already registered.
+    NoFonts,
+    /// The data only contained malformed fonts. The reason the first face
+    /// was rejected is included.
+    Rejected(FaceRejection),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Frozen => write!(f, "the font library is frozen"),
+            Self::NoFonts => write!(f, "no new fonts were found"),
+            Self::Rejected(reason) => write!(f, "the font is malformed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+/// Error returned when loading the data for a source fails.
+#[derive(Clone, Debug)]
+pub enum LoadError {
+    /// No source exists with the specified identifier.
+    NotFound,
+    /// The source has been unregistered.
+    Removed,
+    /// The trust policy of the context does not allow the source.
+    Denied,
+    /// The backing file could not be read. The error is shared with later
+    /// attempts to load the source since failed loads are not retried.
+    Io(Arc<std::io::Error>),
+    /// The backing file does not contain font data.
+    InvalidData,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "the font source does not exist"),
+            Self::Removed => write!(f, "the font source has been removed"),
+            Self::Denied => write!(f, "the font source is not trusted"),
+            Self::Io(error) => write!(f, "failed to read the font file: {}", error),
+            Self::InvalidData => write!(f, "the font file is not a valid font"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+/// Status of the platform font enumeration backend.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BackendStatus {
+    /// The backend is available and is used to locate system fonts.
+    Available,
+    /// System fonts were disabled when the library was built.
+    Disabled,
+    /// The backend failed to initialize or could not enumerate any fonts.
+    /// System fonts are located by scanning the platform font directories
+    /// instead.
+    Failed(String),
+}
+
+/// Outcome of resolving the default families or a generic family when the
+/// library was built.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SetupStatus {
+    /// At least one of the families named for the platform was found.
+    Found,
+    /// None of the platform families were found so installed families were
+    /// chosen by classifying their names and metrics instead.
+    Synthesized,
+    /// No suitable family is installed.
+    Missing,
+}
+
+/// Reason a face was rejected by the structural checks applied before it is
+/// added to a collection.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FaceRejection {
+    /// The table directory extends past the end of the data.
+    Truncated,
+    /// The table with the specified tag extends past the end of the data.
+    TableOutOfBounds([u8; 4]),
+    /// The table with the specified tag, which is required for layout, is
+    /// missing.
+    MissingTable([u8; 4]),
+    /// The face contains neither outlines nor color bitmaps.
+    NoGlyphData,
+    /// The font header (head) table is malformed.
+    InvalidHead,
+    /// The character to glyph mapping (cmap) table has no usable subtable.
+    InvalidCmap,
+    /// The file or directory could not be read, so none of the faces it
+    /// contains were scanned.
+    Unreadable(std::io::ErrorKind),
+}
+
+impl fmt::Display for FaceRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "the table directory is truncated"),
+            Self::TableOutOfBounds(tag) => write!(
+                f,
+                "the {} table extends past the end of the data",
+                String::from_utf8_lossy(tag).trim_end()
+            ),
+            Self::MissingTable(tag) => write!(
+                f,
+                "the required {} table is missing",
+                String::from_utf8_lossy(tag).trim_end()
+            ),
+            Self::NoGlyphData => write!(f, "the font contains no glyph data"),
+            Self::InvalidHead => write!(f, "the head table is malformed"),
+            Self::InvalidCmap => write!(f, "the cmap table has no usable subtable"),
+            Self::Unreadable(kind) => write!(f, "the file could not be read: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for FaceRejection {}
+
+/// Face that was rejected while scanning because it is malformed or its
+/// file could not be read.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RejectedFace {
+    /// Path of the file that contains the face or `None` for fonts
+    /// registered from memory.
+    pub path: Option<PathBuf>,
+    /// Index of the face in its data.
+    pub index: u32,
+    /// Reason the face was rejected.
+    pub reason: FaceRejection,
+}
+
+impl RejectedFace {
+    /// Creates a rejection for a file or directory that could not be read.
+    pub(crate) fn unreadable(path: PathBuf, error: &std::io::Error) -> Self {
+        Self {
+            path: Some(path),
+            index: 0,
+            reason: FaceRejection::Unreadable(error.kind()),
+        }
+    }
+}
+
 /// Context that describes the result of font registration.
 #[derive(Clone, Default)]
 pub struct Registration {
@@ -392,4 +1356,101 @@ pub struct Registration {
     pub families: Vec<FamilyId>,
     /// List of fonts that were registered.
     pub fonts: Vec<FontId>,
+    /// List of faces that were not registered because they are malformed or
+    /// their files could not be read.
+    pub rejected: Vec<RejectedFace>,
+}
+
+/// Families and fonts added to and removed from a library between two
+/// generations.
+///
+/// This is returned by [`Library::changes_since`]. Fonts in files that are
+/// replaced on disk and picked up by a font watcher are reported as removed
+/// and added again since they receive new identifiers.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct CollectionDiff {
+    /// Families that were added.
+    pub added_families: Vec<FamilyId>,
+    /// Families that were removed because none of their fonts remain.
+    pub removed_families: Vec<FamilyId>,
+    /// Fonts that were added.
+    pub added_fonts: Vec<FontId>,
+    /// Fonts that were removed.
+    pub removed_fonts: Vec<FontId>,
+}
+
+impl CollectionDiff {
+    /// Returns true if nothing was added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added_families.is_empty()
+            && self.removed_families.is_empty()
+            && self.added_fonts.is_empty()
+            && self.removed_fonts.is_empty()
+    }
+
+    /// Appends a later set of changes. Families and fonts that were added
+    /// and then removed cancel out since identifiers are never reused.
+    pub(crate) fn merge(&mut self, later: &CollectionDiff) {
+        fn apply<T: Copy + PartialEq>(
+            added: &mut Vec<T>,
+            removed: &mut Vec<T>,
+            later: (&[T], &[T]),
+        ) {
+            added.extend_from_slice(later.0);
+            for id in later.1 {
+                match added.iter().position(|added| added == id) {
+                    Some(index) => {
+                        added.remove(index);
+                    }
+                    None => removed.push(*id),
+                }
+            }
+        }
+        apply(
+            &mut self.added_families,
+            &mut self.removed_families,
+            (&later.added_families, &later.removed_families),
+        );
+        apply(
+            &mut self.added_fonts,
+            &mut self.removed_fonts,
+            (&later.added_fonts, &later.removed_fonts),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn optical_size_range_is_inclusive() {
+        let mut font = FontEntry::new(
+            FontId::from_raw(0),
+            FamilyId::from_raw(0),
+            SourceId::from_raw(0),
+            0,
+            Attributes::default(),
+        );
+        assert_eq!(font.optical_size_distance(100.), 0.);
+        font.optical_size = Some((8., 12.));
+        for (size, supported) in [
+            (6., false),
+            (8., true),
+            (11.5, true),
+            (12., true),
+            (12.5, false),
+            (14., false),
+        ] {
+            assert_eq!(font.supports_optical_size(size), supported, "{}", size);
+            assert_eq!(
+                font.optical_size_distance(size) == 0.,
+                supported,
+                "{}",
+                size
+            );
+        }
+        assert_eq!(font.optical_size_distance(6.), 2.);
+        assert_eq!(font.optical_size_distance(14.), 2.);
+    }
 }
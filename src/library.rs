@@ -1,9 +1,29 @@
+use super::cache::ScanCache;
 use super::data::*;
-use crate::scan::FontScanner;
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::sync::atomic::AtomicU64;
-use std::sync::Arc;
+use super::font::FontData;
+use super::id::FamilyId;
+use super::names::NameCache;
+use super::script_tags::{script_from_index, script_sample};
+use super::vfs::{FileSystem, OsFileSystem};
+use super::{
+    BackendStatus, CollectionDiff, DuplicatePolicy, EmojiPreference, FontContext, FontPersistence,
+    FontPreferences, GenericFamily, LanguageTag, Locale, MetricsPolicy, MissingFont, RejectedFace,
+    SetupStatus, SourcePriority, SourceTrust,
+};
+use crate::scan::{scan_paths, FontScanner, ScannedFont, ScannerPool};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use swash::text::{Cjk, Script};
+use swash::Attributes;
 
 /// Indexed collection of fonts and associated metadata supporting queries and
 /// fallback.
@@ -11,23 +31,443 @@ use std::sync::Arc;
 /// This struct is opaque and provides shared storage for a font collection.
 /// Accessing the collection is done by creating a [`FontContext`](super::context::FontContext)
 /// wrapping this struct.
+///
+/// The library is `Send` and `Sync` and may be shared freely between
+/// threads. Each thread should create its own context.
 #[derive(Clone)]
 pub struct Library {
     pub(crate) inner: Arc<Inner>,
 }
 
 impl Library {
-    fn new(system: SystemCollectionData) -> Self {
-        let mut user = CollectionData::default();
-        user.is_user = true;
+    fn new(
+        system: SystemCollectionData,
+        names: NameCache,
+        persistence: Option<Arc<dyn FontPersistence>>,
+    ) -> Self {
+        // Fonts registered over a static collection are added to the user
+        // collection, which never consults the platform.
+        let user = CollectionData {
+            is_user: true,
+            system_fonts: false,
+            file_system: system.file_system(),
+            ..CollectionData::default()
+        };
         Self {
             inner: Arc::new(Inner {
-                system: Rc::new(RefCell::new(system)),
-                user: Rc::new(RefCell::new(user)),
-                user_version: Arc::new(AtomicU64::new(0)),
+                system: RwLock::new(system),
+                frozen: OnceLock::new(),
+                user: RwLock::new(Arc::new(user)),
+                user_version: AtomicU64::new(0),
+                changes: Mutex::default(),
+                names,
+                char_overrides: RwLock::default(),
+                script_preferences: RwLock::default(),
+                placeholders: RwLock::default(),
+                persistence,
             }),
         }
     }
+
+    /// Registers the fonts and applies the preferences restored by the
+    /// persistence hook without reporting them back to it.
+    fn restore(&self, saved: FontPreferences) {
+        let context = FontContext::new(self);
+        for path in &saved.font_paths {
+            let _ = context.register_paths(path);
+        }
+        let mut preferences = self
+            .inner
+            .script_preferences
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        for (script, name) in &saved.script_families {
+            if let Some(family) = context.family_by_name(name) {
+                preferences.insert(*script, family.id());
+            }
+        }
+        drop(preferences);
+        let mut overrides = self
+            .inner
+            .char_overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        for (ch, name) in &saved.char_overrides {
+            if let Some(family) = context.family_by_name(name) {
+                overrides.insert(*ch, family.id());
+            }
+        }
+    }
+
+    /// Creates a library containing only the fonts described by a static
+    /// collection, such as one generated from application fonts by the
+    /// `fount_embed!` macro of the `fount-embed` crate.
+    ///
+    /// Families, fallback chains and generic families are taken from the
+    /// collection, so no fonts are scanned when the library is created and
+    /// the platform font source is never consulted.
+    ///
+    /// Fonts registered with a [`FontContext`] overlay the collection: they
+    /// are enumerated along with its families and extend its fallback
+    /// chains.
+    /// Faces that the collection already provides, either from the same
+    /// file or with identical data, are not added again. The registration
+    /// reports them with the identifiers of the collection instead, so
+    /// enumeration and fallback never present the same face twice.
+    pub fn from_embedded(data: &'static StaticCollectionData) -> Self {
+        Self::from_embedded_with_file_system(data, Arc::new(OsFileSystem))
+    }
+
+    /// Creates a library from a static collection whose sources that are not
+    /// embedded are loaded through the specified file system. See
+    /// [`from_embedded`](Self::from_embedded).
+    pub fn from_embedded_with_file_system(
+        data: &'static StaticCollectionData,
+        file_system: Arc<dyn FileSystem>,
+    ) -> Self {
+        let system = SystemCollectionData::Static(StaticCollection::new(data, file_system));
+        Self::new(system, NameCache::new(0), None)
+    }
+
+    /// Returns the current generation of the library.
+    ///
+    /// The generation is incremented whenever fonts are registered, installed
+    /// or removed. Caches derived from the library, such as glyph atlases or
+    /// shaping caches, can record the generation and discard their contents
+    /// when it changes.
+    pub fn generation(&self) -> u64 {
+        self.inner.user_version.load(Ordering::Acquire)
+    }
+
+    /// Returns the families and fonts that were added and removed after the
+    /// specified [generation](Self::generation).
+    ///
+    /// Applications can record the generation and use this to report newly
+    /// installed fonts when it changes. Fonts loaded on demand from the
+    /// platform are not reported since they were available all along.
+    pub fn changes_since(&self, generation: u64) -> CollectionDiff {
+        let mut diff = CollectionDiff::default();
+        let changes = self.inner.changes.lock().unwrap();
+        let start = changes.partition_point(|(changed, _)| *changed <= generation);
+        for (_, changes) in &changes[start..] {
+            diff.merge(changes);
+        }
+        diff
+    }
+
+    /// Selects the family used to render a character ahead of any other
+    /// fallback.
+    ///
+    /// This gives fine control over mixed-script text, for example forcing
+    /// U+00A0 or specific CJK punctuation to a particular font. The font in
+    /// the family that best matches the requested attributes is used if it
+    /// maps the character, otherwise the override is ignored. Overrides
+    /// apply to all contexts and replace any previous override for the
+    /// character. The library [generation](Self::generation) is advanced so
+    /// that cached fallback results are discarded.
+    pub fn add_char_override(&self, ch: char, family: FamilyId) {
+        self.inner
+            .char_overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(ch, family);
+        self.inner.bump_generation(CollectionDiff::default());
+        if let Some(persistence) = &self.inner.persistence {
+            if let Some(family) = FontContext::new(self).family(family) {
+                persistence.char_override_changed(ch, Some(family.name()));
+            }
+        }
+    }
+
+    /// Removes the override for a character added with
+    /// [`add_char_override`](Self::add_char_override).
+    pub fn remove_char_override(&self, ch: char) {
+        let removed = self
+            .inner
+            .char_overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&ch);
+        if removed.is_some() {
+            self.inner.bump_generation(CollectionDiff::default());
+            if let Some(persistence) = &self.inner.persistence {
+                persistence.char_override_changed(ch, None);
+            }
+        }
+    }
+
+    /// Prefers the specified family for the script in contexts created
+    /// from now on.
+    ///
+    /// This is the library-level counterpart of
+    /// [`FontContext::prefer_for_script`]: the preference is copied into
+    /// each new [`FontContext`] and reported to the
+    /// [persistence hook](LibraryBuilder::persistence), if any, so that it is
+    /// restored on the next run. Existing contexts are unaffected.
+    pub fn prefer_for_script(&self, script: Script, family: FamilyId) {
+        self.inner
+            .script_preferences
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(script, family);
+        if let Some(persistence) = &self.inner.persistence {
+            if let Some(family) = FontContext::new(self).family(family) {
+                persistence.script_preference_changed(script, Some(family.name()));
+            }
+        }
+    }
+
+    /// Removes a preference previously set with
+    /// [`prefer_for_script`](Self::prefer_for_script).
+    pub fn clear_script_preference(&self, script: Script) {
+        let removed = self
+            .inner
+            .script_preferences
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&script);
+        if let (Some(persistence), Some(_)) = (&self.inner.persistence, removed) {
+            persistence.script_preference_changed(script, None);
+        }
+    }
+
+    /// Creates a placeholder family for a font that a document requests but
+    /// that is not installed.
+    ///
+    /// The placeholder remembers the requested name and attributes so that
+    /// exports and user interfaces can report exactly which fonts the
+    /// document wanted. It can be used wherever a family identifier is
+    /// expected: [`FontContext::family`] resolves it to the family with the
+    /// requested name if one has become available and otherwise to the
+    /// first default family. Requesting a placeholder with the same name,
+    /// ignoring case, and attributes returns the existing identifier.
+    pub fn add_placeholder(&self, name: &str, attributes: Attributes) -> FamilyId {
+        let mut placeholders = self
+            .inner
+            .placeholders
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        let index = match placeholders.iter().position(|missing| {
+            missing.attributes == attributes && missing.name.eq_ignore_ascii_case(name)
+        }) {
+            Some(index) => index,
+            None => {
+                placeholders.push(MissingFont {
+                    name: name.to_owned(),
+                    attributes,
+                });
+                placeholders.len() - 1
+            }
+        };
+        FamilyId::new_placeholder(index as u32)
+    }
+
+    /// Returns the font requested by a placeholder family created with
+    /// [`add_placeholder`](Self::add_placeholder).
+    pub fn missing_font(&self, id: FamilyId) -> Option<MissingFont> {
+        if !id.is_placeholder() {
+            return None;
+        }
+        self.inner
+            .placeholders
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id.placeholder_index())
+            .cloned()
+    }
+
+    /// Returns every placeholder family along with the font it requested.
+    pub fn placeholders(&self) -> Vec<(FamilyId, MissingFont)> {
+        self.inner
+            .placeholders
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .enumerate()
+            .map(|(index, missing)| (FamilyId::new_placeholder(index as u32), missing.clone()))
+            .collect()
+    }
+
+    /// Returns the pool of scanners used when fonts are added to the
+    /// library.
+    pub fn scanner_pool(&self) -> Arc<ScannerPool> {
+        self.inner.system().scanners()
+    }
+
+    /// Returns the faces that were rejected as malformed while scanning.
+    ///
+    /// Fonts are checked for a valid table directory, the tables required
+    /// for layout and usable head and cmap tables before they are added so
+    /// that broken files in font directories cannot enter fallback chains.
+    /// This includes faces rejected when registering fonts.
+    pub fn rejected_faces(&self) -> Vec<RejectedFace> {
+        self.inner.system().rejected_faces().to_vec()
+    }
+
+    /// Returns the status of the platform font enumeration backend.
+    ///
+    /// Initialization failures, such as a broken fontconfig configuration
+    /// or missing system libraries, are not fatal. They are recorded here
+    /// and the library falls back to scanning the platform font
+    /// directories. Calling this initializes the backend if it has not
+    /// been used yet.
+    pub fn backend_status(&self) -> BackendStatus {
+        match &*self.inner.system() {
+            SystemCollectionData::Scanned(data) if !data.collection.system_fonts => {
+                BackendStatus::Disabled
+            }
+            SystemCollectionData::Scanned(data) => data.collection.system_source.status(),
+            SystemCollectionData::Static(_) => BackendStatus::Available,
+        }
+    }
+
+    /// Returns whether the default families were found when the library was
+    /// built.
+    ///
+    /// If none of the families expected on the platform are installed, the
+    /// library picks a sans serif family, or failing that any text family,
+    /// from those that are installed and reports
+    /// [`SetupStatus::Synthesized`].
+    pub fn default_status(&self) -> SetupStatus {
+        self.inner.system().default_status()
+    }
+
+    /// Returns whether the specified generic family was found when the
+    /// library was built.
+    ///
+    /// Missing serif, sans serif and monospace generic families, including
+    /// their `ui-` variants, are synthesized from installed families of the
+    /// same class. Serif and sans serif fall back to the default families as
+    /// do the other generic families, while monospace is only synthesized
+    /// from fixed pitch families. Emoji and math are only synthesized from
+    /// families with color glyphs and math tables respectively. Headless
+    /// libraries only synthesize them from the fonts added to the builder.
+    /// Nothing is synthesized when the default, serif, sans serif and
+    /// monospace families were all found, so the other generic families may
+    /// then be reported as [`SetupStatus::Missing`].
+    pub fn generic_status(&self, family: GenericFamily) -> SetupStatus {
+        self.inner.system().generic_status(family)
+    }
+
+    /// Returns the length of the fallback chain of each script.
+    ///
+    /// Applications can log this at startup or warn about scripts with
+    /// empty chains, which will render as missing glyphs on the current
+    /// machine, rather than discovering it when such text is first shown.
+    /// Han is reported with the chain used when no locale is specified.
+    /// Scripts without a chain of their own are reported with a length of
+    /// zero even though fallback then tries the default families. Common,
+    /// inherited and unknown characters are not reported. Chains that have
+    /// not been queried from the platform yet are queried first.
+    pub fn fallback_health(&self) -> Vec<(Script, usize)> {
+        if let Some(mut system) = self.inner.system_mut() {
+            system.resolve_all_platform_fallbacks();
+        }
+        let system = self.inner.system();
+        (0..)
+            .map_while(script_from_index)
+            .filter(|script| script_sample(*script).is_some())
+            .map(|script| {
+                let len = if system.has_script_fallbacks(script) {
+                    system.fallback_families(script, None).len()
+                } else {
+                    0
+                };
+                (script, len)
+            })
+            .collect()
+    }
+
+    /// Returns the families recommended for user interface labels on the
+    /// current platform, in order: the system interface families, the
+    /// preferred CJK family for the locale, the symbol families and the
+    /// emoji families.
+    ///
+    /// This gives applications a complete chain for text such as menu
+    /// items and buttons without hard-coding the fonts of each platform.
+    /// Families that are not installed are omitted.
+    pub fn standard_ui_chain(&self, locale: Option<Locale>) -> Vec<FamilyId> {
+        // Symbol families are only loaded on demand, which requires the
+        // write lock, so it is only taken for families not loaded yet.
+        let mut symbols = Vec::new();
+        for name in symbol_family_names() {
+            let loaded = self
+                .inner
+                .system()
+                .find_family(name)
+                .map(|family| family.id());
+            let id = loaded.or_else(|| {
+                let mut system = self.inner.system_mut()?;
+                system.family_by_name(name).map(|family| family.id())
+            });
+            symbols.extend(id);
+        }
+        self.inner
+            .resolve_fallbacks(Script::Han, fallback_cjk(Script::Han, locale));
+        let system = self.inner.system();
+        let mut chain = Vec::new();
+        let families = system
+            .generic_families(GenericFamily::SystemUi)
+            .iter()
+            .chain(system.fallback_families(Script::Han, locale).first())
+            .chain(&symbols)
+            .chain(system.generic_families(GenericFamily::Emoji));
+        for id in families {
+            if !chain.contains(id) {
+                chain.push(*id);
+            }
+        }
+        chain
+    }
+
+    /// Freezes the library, preventing any further modification.
+    ///
+    /// After freezing, queries read the collection without acquiring any
+    /// locks which maximizes throughput for renderers that configure fonts
+    /// once up front. Fallback chains that have not been queried from the
+    /// platform are completed first. Families that were not resolved before
+    /// freezing can no longer be discovered, registration fails with
+    /// [`RegisterError::Frozen`](super::RegisterError::Frozen) and the
+    /// attributes of families are no longer refined on first use.
+    pub fn freeze(&self) {
+        let mut system = self.inner.system.write().unwrap();
+        if self.inner.frozen.get().is_none() {
+            system.resolve_all_platform_fallbacks();
+            let data = core::mem::replace(
+                &mut *system,
+                SystemCollectionData::Scanned(Default::default()),
+            );
+            let _ = self.inner.frozen.set(data);
+        }
+    }
+
+    /// Returns true if the library has been frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.inner.frozen.get().is_some()
+    }
+
+    /// Writes any fonts scanned since the library was built to the cache
+    /// file configured with [`LibraryBuilder::cache_path`].
+    ///
+    /// System fonts are scanned lazily as families are requested, so saving
+    /// the cache periodically or at exit avoids rescanning them on the next
+    /// run. This does nothing if no cache file was configured.
+    pub fn save_cache(&self) -> io::Result<()> {
+        let (cache, file_system) = match &*self.inner.system() {
+            SystemCollectionData::Scanned(data) => (
+                data.collection.scan_cache.clone(),
+                data.collection.file_system.clone(),
+            ),
+            SystemCollectionData::Static(_) => return Ok(()),
+        };
+        match cache {
+            Some(cache) => cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .save(&*file_system),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Default for Library {
@@ -37,9 +477,103 @@ impl Default for Library {
 }
 
 pub struct Inner {
-    pub system: Rc<RefCell<SystemCollectionData>>,
-    pub user: Rc<RefCell<CollectionData>>,
-    pub user_version: Arc<AtomicU64>,
+    pub system: RwLock<SystemCollectionData>,
+    /// System collection moved out of the lock by [`Library::freeze`].
+    pub frozen: OnceLock<SystemCollectionData>,
+    pub user: RwLock<Arc<CollectionData>>,
+    pub user_version: AtomicU64,
+    /// Changes to the collection keyed by the generation that made them
+    /// visible.
+    pub changes: Mutex<Vec<(u64, CollectionDiff)>>,
+    /// Localized strings read on request.
+    pub names: NameCache,
+    /// Families selected for individual characters ahead of fallback.
+    pub char_overrides: RwLock<HashMap<char, FamilyId>>,
+    /// Script preferences set on the library or restored by the persistence
+    /// hook, applied to each new context.
+    pub script_preferences: RwLock<HashMap<Script, FamilyId>>,
+    /// Fonts requested by documents but not installed, indexed by
+    /// placeholder identifier.
+    pub placeholders: RwLock<Vec<MissingFont>>,
+    /// Hook notified of changes to the user's font configuration.
+    pub persistence: Option<Arc<dyn FontPersistence>>,
+}
+
+impl Inner {
+    /// Returns a reference to the system collection, locking it for reading
+    /// unless the library is frozen.
+    pub fn system(&self) -> SystemRef<'_> {
+        if let Some(frozen) = self.frozen.get() {
+            return SystemRef::Frozen(frozen);
+        }
+        let guard = self.system.read().unwrap();
+        // The library may have been frozen while waiting for the lock, in
+        // which case the locked collection is empty.
+        match self.frozen.get() {
+            Some(frozen) => SystemRef::Frozen(frozen),
+            None => SystemRef::Locked(guard),
+        }
+    }
+
+    /// Returns a reference to the system collection, or `None` if it is
+    /// locked for writing.
+    pub fn try_system(&self) -> Option<SystemRef<'_>> {
+        if let Some(frozen) = self.frozen.get() {
+            return Some(SystemRef::Frozen(frozen));
+        }
+        let guard = self.system.try_read().ok()?;
+        Some(match self.frozen.get() {
+            Some(frozen) => SystemRef::Frozen(frozen),
+            None => SystemRef::Locked(guard),
+        })
+    }
+
+    /// Locks the system collection for writing. Returns `None` if the
+    /// library is frozen.
+    pub fn system_mut(&self) -> Option<RwLockWriteGuard<'_, SystemCollectionData>> {
+        let guard = self.system.write().unwrap();
+        if self.frozen.get().is_some() {
+            None
+        } else {
+            Some(guard)
+        }
+    }
+
+    /// Queries the platform for the fallback chain of the script and CJK
+    /// locale if it has not been queried yet.
+    pub fn resolve_fallbacks(&self, script: Script, cjk: Cjk) {
+        let pending = self.system().needs_platform_fallbacks(script, cjk);
+        if pending {
+            if let Some(mut system) = self.system_mut() {
+                system.resolve_platform_fallbacks(script, cjk);
+            }
+        }
+    }
+
+    /// Advances the generation and records the changes it makes visible.
+    pub fn bump_generation(&self, diff: CollectionDiff) {
+        let mut changes = self.changes.lock().unwrap();
+        let generation = self.user_version.fetch_add(1, Ordering::Relaxed) + 1;
+        if !diff.is_empty() {
+            changes.push((generation, diff));
+        }
+    }
+}
+
+pub enum SystemRef<'a> {
+    Frozen(&'a SystemCollectionData),
+    Locked(RwLockReadGuard<'a, SystemCollectionData>),
+}
+
+impl Deref for SystemRef<'_> {
+    type Target = SystemCollectionData;
+
+    fn deref(&self) -> &SystemCollectionData {
+        match self {
+            Self::Frozen(data) => data,
+            Self::Locked(guard) => guard,
+        }
+    }
 }
 
 /// Builder for configuring a font library.
@@ -47,16 +581,469 @@ pub struct Inner {
 pub struct LibraryBuilder {
     scanner: FontScanner,
     system: CollectionData,
+    name_cache_capacity: usize,
+    prewarm: Option<Vec<String>>,
+    default_families: Vec<String>,
+    generic_families: Vec<(GenericFamily, Vec<String>)>,
+    fallbacks: Vec<(Script, Cjk, Vec<String>)>,
+    locale_fallbacks: Vec<(LanguageTag, Script, Vec<String>)>,
+    aliases: Vec<(String, Vec<String>)>,
+    persistence: Option<Arc<dyn FontPersistence>>,
 }
 
 impl LibraryBuilder {
+    /// Creates a builder configured for headless use, such as server side
+    /// thumbnail generation and rendering in CI.
+    ///
+    /// The resulting library never consults the platform font source, does
+    /// not read desktop settings or any directory under the home directory
+    /// and `Library::watch` does not monitor any directories. Only fonts
+    /// added through the builder, typically with
+    /// [`add_font_dir`](Self::add_font_dir), and fonts registered later are
+    /// available. This is equivalent to
+    /// [`with_system_fonts(false)`](Self::with_system_fonts).
+    pub fn headless() -> Self {
+        let mut builder = Self::default();
+        builder.with_system_fonts(false);
+        builder
+    }
+
+    /// Sets the file system from which fonts are scanned and loaded.
+    ///
+    /// All path based sources, including the fonts located by the platform
+    /// and those added with [`add_font_dir`](Self::add_font_dir), are read
+    /// through the file system. Set this before adding fonts. A virtual file
+    /// system is typically combined with [`headless`](Self::headless) so
+    /// that only the fonts it contains are considered. The default is
+    /// [`OsFileSystem`](super::OsFileSystem).
+    pub fn file_system(&mut self, file_system: Arc<dyn FileSystem>) -> &mut Self {
+        self.system.file_system = file_system;
+        self
+    }
+
+    /// Sets the policy used to select line metrics for fonts in the library.
+    pub fn metrics_policy(&mut self, policy: MetricsPolicy) -> &mut Self {
+        self.system.metrics_policy = policy;
+        self
+    }
+
+    /// Scans the fonts in the specified file or directory.
+    ///
+    /// When multiple roots provide a family with the same name, the family
+    /// from the root with the highest priority is selected by name while
+    /// the others remain available for enumeration. Fonts located by the
+    /// platform have [`SourcePriority::System`]. Paths that cannot be read
+    /// are ignored.
+    pub fn add_root(&mut self, path: impl AsRef<Path>, priority: SourcePriority) -> &mut Self {
+        let _ = scan_paths(&[path], priority, &mut self.system);
+        self
+    }
+
+    /// Scans all fonts in the specified directory and its subdirectories.
+    ///
+    /// This is intended for fonts bundled with an application. The fonts
+    /// have [`SourcePriority::Application`], so they shadow installed fonts
+    /// with the same family names, and they participate in generic family
+    /// selection and script fallback just like system fonts.
+    pub fn add_font_dir(&mut self, path: impl AsRef<Path>) -> io::Result<&mut Self> {
+        if !self.system.file_system.metadata(path.as_ref())?.is_dir {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not a directory"));
+        }
+        scan_paths(&[path], SourcePriority::Application, &mut self.system)?;
+        Ok(self)
+    }
+
+    /// Scans the fonts in the specified file. See
+    /// [`add_font_dir`](Self::add_font_dir) for details.
+    pub fn add_font_file(&mut self, path: impl AsRef<Path>) -> io::Result<&mut Self> {
+        if self.system.file_system.metadata(path.as_ref())?.is_dir {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not a file"));
+        }
+        scan_paths(&[path], SourcePriority::Application, &mut self.system)?;
+        Ok(self)
+    }
+
+    /// Adds fonts discovered by an external enumerator such as DirectWrite
+    /// or CoreText.
+    ///
+    /// The supplied metadata is trusted and the fonts are not parsed, so
+    /// these fonts can be matched by family name but do not participate in
+    /// script fallback.
+    pub fn add_external_faces(
+        &mut self,
+        faces: impl IntoIterator<Item = ExternalFace>,
+    ) -> &mut Self {
+        let mut files: HashMap<PathBuf, Vec<ScannedFont>> = HashMap::new();
+        for face in faces {
+            let font = ScannedFont::from_metadata(&face.family, face.index, face.attributes);
+            match face.source {
+                ExternalSource::Path(path) => files.entry(path).or_default().push(font),
+                ExternalSource::Data(data) => {
+                    let source = SourceData {
+                        kind: SourceDataKind::Data(data),
+                        status: RwLock::new(SourceDataStatus::Vacant),
+                        trust: SourceTrust::Registered,
+                    };
+                    self.system.add_scanned_fonts(&[font], source, None);
+                }
+            }
+        }
+        for (path, fonts) in files {
+            let source = SourceData {
+                kind: SourceDataKind::Path(Arc::new(path)),
+                status: RwLock::new(SourceDataStatus::Vacant),
+                trust: SourceTrust::Registered,
+            };
+            self.system.add_scanned_fonts(&fonts, source, None);
+        }
+        self
+    }
+
+    /// Sets whether the library includes fonts installed on the system.
+    ///
+    /// When disabled, the platform font source and system font directories
+    /// are never consulted and the library contains only the fonts added by
+    /// this builder and those registered later. Default, generic and
+    /// fallback families are selected from those fonts when their names
+    /// match. This is useful for producing identical results in tests and
+    /// sandboxed environments. The default is `true`.
+    ///
+    /// When enabled, the directories listed in the `FOUNT_FONT_PATH`
+    /// environment variable, separated like `PATH`, are also searched. On
+    /// Linux and the BSDs, the `fonts` subdirectories of the entries in
+    /// `XDG_DATA_DIRS` are searched as well. These directories are scanned
+    /// the first time the platform cannot locate a requested family or when
+    /// the platform font directories are scanned.
+    pub fn with_system_fonts(&mut self, enable: bool) -> &mut Self {
+        self.system.system_fonts = enable;
+        self
+    }
+
+    /// Sets whether families named with a weight suffix are merged into the
+    /// base family.
+    ///
+    /// Some foundries ship each weight as a separate family, such as
+    /// "Roboto Thin" and "Roboto Black". When enabled, such fonts are added
+    /// to the family named without the suffix and their weight is taken
+    /// from the suffix. The original names continue to resolve to the merged
+    /// family. This applies to fonts added after the call, including system
+    /// fonts that are discovered lazily. The default is `false`.
+    pub fn merge_weight_families(&mut self, merge: bool) -> &mut Self {
+        self.system.merge_weight_families = merge;
+        self
+    }
+
+    /// Sets the kind of font preferred for emoji.
+    ///
+    /// This orders the emoji generic family, which is consulted ahead of
+    /// the fallback chain for characters that default to emoji
+    /// presentation. The default is [`EmojiPreference::Color`].
+    pub fn emoji_preference(&mut self, preference: EmojiPreference) -> &mut Self {
+        self.system.emoji_preference = preference;
+        self
+    }
+
+    /// Sets the policy for fonts in a family that have the same attributes.
+    ///
+    /// Linux distributions commonly install both hinted and unhinted builds
+    /// of a family. By default the font discovered first is kept. This
+    /// applies to fonts added after the call.
+    pub fn duplicate_policy(&mut self, policy: DuplicatePolicy) -> &mut Self {
+        self.system.duplicate_policy = policy;
+        self
+    }
+
+    /// Sets whether files backing font sources are checked for modification
+    /// before previously loaded data is reused.
+    ///
+    /// When enabled, a change in the size or modification time of a file
+    /// causes the data to be reloaded, the affected fonts to receive new
+    /// cache keys and the library [generation](Library::generation) to be
+    /// advanced. This guards long running sessions against mixing data
+    /// from before and after a font upgrade at the cost of checking the file
+    /// on each load. The default is `false`.
+    pub fn verify_sources(&mut self, verify: bool) -> &mut Self {
+        self.system.verify_sources = verify;
+        self
+    }
+
+    /// Sets the approximate number of bytes retained by the cache of
+    /// localized strings returned by
+    /// [`FontContext::localized_name`](super::FontContext::localized_name).
+    ///
+    /// The cache is shared by all contexts of the library and duplicate
+    /// strings are stored once. When it exceeds the capacity, the oldest
+    /// entries are discarded. The default is zero, which disables the cache
+    /// so that each lookup reads the name table of the font.
+    pub fn name_cache_capacity(&mut self, bytes: usize) -> &mut Self {
+        self.name_cache_capacity = bytes;
+        self
+    }
+
+    /// Sets the families that are resolved while building the library.
+    ///
+    /// By default, the build resolves a fixed list of platform fallback
+    /// families, such as the Noto CJK families on Linux, so that they are
+    /// ready for the first fallback query. When set, exactly the specified
+    /// names are resolved instead and all other families are discovered on
+    /// first use. Only the default and generic families are still resolved
+    /// since every query depends on them. Fallback chains preferred by
+    /// fontconfig or Core Text are never queried while building, with or
+    /// without prewarmed names, and are completed when a script first needs
+    /// fallback. Use [`build_async`](Self::build_async) to perform this work
+    /// on a background thread. Names that do not match a family are ignored.
+    pub fn prewarm_families(&mut self, names: &[&str]) -> &mut Self {
+        self.prewarm = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Sets the default families, in order of preference.
+    ///
+    /// This replaces the platform defaults, such as Segoe UI on Windows and
+    /// Helvetica on macOS, for applications that ship their own interface
+    /// font. Names may refer to fonts added through the builder. Names that
+    /// do not match a family are ignored and the platform defaults are used
+    /// if none of them match.
+    pub fn default_families(&mut self, names: &[&str]) -> &mut Self {
+        self.default_families = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Sets the families of a generic family, in order of preference.
+    ///
+    /// This replaces the families chosen for the platform and those read
+    /// from the desktop settings, for example to prefer
+    /// `["JetBrains Mono", "Cascadia Code"]` for monospace. Names that do
+    /// not match a family are ignored and the platform families are kept if
+    /// none of them match.
+    pub fn generic(&mut self, generic: GenericFamily, names: &[&str]) -> &mut Self {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        self.generic_families.retain(|(g, _)| *g != generic);
+        self.generic_families.push((generic, names));
+        self
+    }
+
+    /// Resolves a family name to the first family found with one of the
+    /// specified names, such as "Helvetica" to "Liberation Sans".
+    ///
+    /// Aliases only apply to names for which no family is installed and
+    /// are resolved when the library is built. Calling this again for the
+    /// same name replaces the previous names.
+    pub fn alias(&mut self, name: &str, names: &[&str]) -> &mut Self {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        self.aliases
+            .retain(|(alias, _)| !alias.eq_ignore_ascii_case(name));
+        self.aliases.push((name.to_owned(), names));
+        self
+    }
+
+    /// Sets the fallback chain for a script to the families with the
+    /// specified names, in order of preference.
+    ///
+    /// The names are resolved when the library is built. If any of them are
+    /// found, the chain replaces the families discovered by the platform
+    /// configuration and is not extended with families added later, giving
+    /// full control over fallback for the script. For Han, this sets the
+    /// chain used when no locale is given; use [`cjk`](Self::cjk) for the
+    /// chains of specific locales. Calling this again for the same script
+    /// replaces the previous names.
+    pub fn fallback(&mut self, script: Script, names: &[&str]) -> &mut Self {
+        self.set_fallback(script, Cjk::None, names)
+    }
+
+    /// Sets the fallback chain for Han text in the specified CJK locale
+    /// bucket to the families with the specified names, in order of
+    /// preference. See [`fallback`](Self::fallback).
+    pub fn cjk(&mut self, cjk: Cjk, names: &[&str]) -> &mut Self {
+        self.set_fallback(Script::Han, cjk, names)
+    }
+
+    fn set_fallback(&mut self, script: Script, cjk: Cjk, names: &[&str]) -> &mut Self {
+        let names = names.iter().map(|name| name.to_string()).collect();
+        self.fallbacks.retain(|(s, c, _)| (*s, *c) != (script, cjk));
+        self.fallbacks.push((script, cjk, names));
+        self
+    }
+
+    /// Sets the fallback chain for a script in text tagged with a language,
+    /// such as Han in "zh-HK" or Arabic in "ur", to the families with the
+    /// specified names, in order of preference.
+    ///
+    /// A chain applies when the locale passed to fallback queries has the
+    /// language of the tag and the script and region of the tag, if it
+    /// specifies them. The chain matching the most components is used and
+    /// takes precedence over the chain for the script alone. Locales
+    /// converted with [`LanguageTag::to_locale`] include the likely script,
+    /// so a chain for "zh-Hant" applies to "zh-TW". Chains for which none
+    /// of the names are found when the library is built are ignored.
+    /// Calling this again for the same tag and script replaces the previous
+    /// names.
+    ///
+    /// Locales only record alphabetic regions, so a tag with a numeric
+    /// region such as "es-419" could never match and is ignored.
+    pub fn locale_fallback(
+        &mut self,
+        tag: &LanguageTag,
+        script: Script,
+        names: &[&str],
+    ) -> &mut Self {
+        let numeric_region = tag
+            .region()
+            .is_some_and(|region| !region.bytes().all(|b| b.is_ascii_alphabetic()));
+        if numeric_region {
+            return self;
+        }
+        let names = names.iter().map(|name| name.to_string()).collect();
+        self.locale_fallbacks
+            .retain(|(t, s, _)| (t, *s) != (tag, script));
+        self.locale_fallbacks.push((tag.clone(), script, names));
+        self
+    }
+
+    /// Sets a hook that persists the fonts registered by the user and their
+    /// fallback preferences.
+    ///
+    /// The configuration returned by [`FontPersistence::load`] is restored
+    /// when the library is built: its fonts are registered, its character
+    /// overrides are added and its script preferences are applied to every
+    /// new [`FontContext`]. Later registrations of font files and
+    /// directories, script preferences set with
+    /// [`Library::prefer_for_script`] and character overrides are reported
+    /// to the hook.
+    pub fn persistence(&mut self, persistence: Arc<dyn FontPersistence>) -> &mut Self {
+        self.persistence = Some(persistence);
+        self
+    }
+
+    /// Sets the path of a file used to cache the results of scanning system
+    /// fonts between runs.
+    ///
+    /// The cache is keyed by font file path and invalidated per file when the
+    /// modification time or size of the file changes. Fonts scanned while
+    /// building the library are written back to the cache by
+    /// [`build`](Self::build) and later additions are persisted with
+    /// [`Library::save_cache`].
+    pub fn cache_path(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        let cache = ScanCache::load(path);
+        self.system.scan_cache = Some(Arc::new(Mutex::new(cache)));
+        self
+    }
+
+    /// Builds the library on a background thread.
+    ///
+    /// The returned future resolves once font discovery is complete and may
+    /// be polled by any executor. This allows applications to present a
+    /// window before the collection is ready and then swap in the library
+    /// when the future completes.
+    pub fn build_async(self) -> BuildFuture {
+        let state = Arc::new(Mutex::new(BuildState::default()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| self.build()));
+            let mut state = thread_state.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        BuildFuture { state }
+    }
+
     pub fn build(mut self) -> Library {
-        self.system.setup_default();
+        self.system.setup_aliases(&self.aliases);
+        self.system.setup_default(&self.default_families);
         self.system.setup_default_generic();
-        self.system.setup_fallbacks();
+        #[cfg(feature = "desktop-settings")]
+        self.system.setup_desktop_generic();
+        self.system.setup_custom_generic(&self.generic_families);
+        self.system.synthesize_defaults();
+        // Custom chains are fixed before the platform queries are registered
+        // so that the queries and curated families leave them untouched.
+        self.system.setup_custom_fallbacks(&self.fallbacks);
+        // Platform queries are only registered here and run on first use.
+        #[cfg(feature = "fontconfig")]
+        self.system.setup_fontconfig_fallbacks();
+        #[cfg(all(feature = "coretext", target_os = "macos"))]
+        self.system.setup_coretext_fallbacks();
+        match &self.prewarm {
+            Some(names) => {
+                for name in names {
+                    let _ = self.system.family_id(name);
+                }
+            }
+            None => self.system.setup_fallbacks(),
+        }
+        self.system.setup_locale_fallbacks(&self.locale_fallbacks);
+        if let Some(cache) = &self.system.scan_cache {
+            // The cache is an optimization so failing to write it is not
+            // fatal.
+            let _ = cache
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .save(&*self.system.file_system);
+        }
         let system = SystemCollectionData::Scanned(ScannedCollectionData {
             collection: self.system,
         });
-        Library::new(system)
+        let library = Library::new(
+            system,
+            NameCache::new(self.name_cache_capacity),
+            self.persistence.clone(),
+        );
+        if let Some(persistence) = &self.persistence {
+            library.restore(persistence.load());
+        }
+        library
     }
 }
+
+/// Future returned by [`LibraryBuilder::build_async`] that resolves to the
+/// built library.
+pub struct BuildFuture {
+    state: Arc<Mutex<BuildState>>,
+}
+
+#[derive(Default)]
+struct BuildState {
+    result: Option<thread::Result<Library>>,
+    waker: Option<Waker>,
+}
+
+impl Future for BuildFuture {
+    type Output = Library;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Library> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(Ok(library)) => Poll::Ready(library),
+            // Propagate a panic from the build thread to the caller.
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Font described by an external enumerator.
+#[derive(Clone)]
+pub struct ExternalFace {
+    /// Location of the font data.
+    pub source: ExternalSource,
+    /// Index of the font in a collection, or zero for a single font file.
+    pub index: u32,
+    /// Name of the family containing the font.
+    pub family: String,
+    /// Stretch, weight and style of the font.
+    pub attributes: Attributes,
+}
+
+/// Location of the data for an [`ExternalFace`].
+#[derive(Clone)]
+pub enum ExternalSource {
+    /// Full path to a font file.
+    Path(PathBuf),
+    /// Buffer containing font data.
+    Data(FontData),
+}
@@ -0,0 +1,372 @@
+//! Parsing of BCP 47 language tags.
+
+use super::Locale;
+use core::fmt;
+use swash::text::Cjk;
+
+/// Language tag parsed according to BCP 47, such as "zh-Hant-HK", "sr-Cyrl"
+/// or "es-419".
+///
+/// [`Locale`] only records a language, script and alphabetic region and is
+/// mainly used to select among the CJK fallback chains. This type accepts
+/// the full tag syntax, including extended language subtags, numeric
+/// regions, variants, extensions and private use subtags, so that the
+/// components can be inspected and used to select fallback chains
+/// configured for particular regions with
+/// [`LibraryBuilder::locale_fallback`](super::LibraryBuilder::locale_fallback).
+///
+/// Subtags may be separated by hyphens or underscores and their case is
+/// normalized, so "ZH_hant_hk" is equivalent to "zh-Hant-HK".
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LanguageTag {
+    language: String,
+    extended_languages: Vec<String>,
+    script: Option<String>,
+    region: Option<String>,
+    variants: Vec<String>,
+    extensions: Vec<String>,
+    private_use: Option<String>,
+}
+
+impl LanguageTag {
+    /// Parses a language tag. Returns `None` if the tag is not well formed.
+    ///
+    /// # Example
+    /// ```
+    /// use fount::LanguageTag;
+    ///
+    /// let tag = LanguageTag::parse("zh-hant-hk").unwrap();
+    /// assert_eq!(tag.language(), "zh");
+    /// assert_eq!(tag.script(), Some("Hant"));
+    /// assert_eq!(tag.region(), Some("HK"));
+    /// assert_eq!(tag.to_string(), "zh-Hant-HK");
+    /// ```
+    pub fn parse(tag: &str) -> Option<Self> {
+        let mut subtags = tag.trim().split(['-', '_']).peekable();
+        let language = subtags.next()?;
+        if !is_alpha(language, 2, 8) || language.len() == 4 {
+            return None;
+        }
+        let mut tag = Self {
+            language: language.to_ascii_lowercase(),
+            extended_languages: Vec::new(),
+            script: None,
+            region: None,
+            variants: Vec::new(),
+            extensions: Vec::new(),
+            private_use: None,
+        };
+        if tag.language.len() <= 3 {
+            while tag.extended_languages.len() < 3 {
+                match subtags.next_if(|s| is_alpha(s, 3, 3)) {
+                    Some(extlang) => tag.extended_languages.push(extlang.to_ascii_lowercase()),
+                    None => break,
+                }
+            }
+        }
+        if let Some(script) = subtags.next_if(|s| is_alpha(s, 4, 4)) {
+            let mut script = script.to_ascii_lowercase();
+            script[..1].make_ascii_uppercase();
+            tag.script = Some(script);
+        }
+        let is_region = |s: &&str| is_alpha(s, 2, 2) || (s.len() == 3 && is_digits(s));
+        if let Some(region) = subtags.next_if(is_region) {
+            tag.region = Some(region.to_ascii_uppercase());
+        }
+        while let Some(variant) = subtags.next_if(|s| is_variant(s)) {
+            let variant = variant.to_ascii_lowercase();
+            if tag.variants.contains(&variant) {
+                return None;
+            }
+            tag.variants.push(variant);
+        }
+        while let Some(singleton) = subtags.next_if(|s| s.len() == 1) {
+            let singleton = singleton.to_ascii_lowercase();
+            let is_private = singleton == "x";
+            let (min, max) = if is_private { (1, 8) } else { (2, 8) };
+            let mut sequence = singleton;
+            while let Some(subtag) = subtags.next_if(|s| is_alphanumeric(s, min, max)) {
+                sequence.push('-');
+                sequence.push_str(&subtag.to_ascii_lowercase());
+            }
+            if sequence.len() == 1 || !is_alphanumeric(&sequence[..1], 1, 1) {
+                return None;
+            }
+            if is_private {
+                tag.private_use = Some(sequence);
+                break;
+            }
+            tag.extensions.push(sequence);
+        }
+        if subtags.next().is_some() {
+            return None;
+        }
+        Some(tag)
+    }
+
+    /// Returns the primary language subtag, such as "zh".
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Returns the extended language subtags, such as "yue" in "zh-yue".
+    pub fn extended_languages(&self) -> &[String] {
+        &self.extended_languages
+    }
+
+    /// Returns the script subtag, such as "Hant", if the tag specifies one.
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+
+    /// Returns the region subtag, such as "HK" or "419", if the tag
+    /// specifies one.
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// Returns the variant subtags, such as "pinyin".
+    pub fn variants(&self) -> &[String] {
+        &self.variants
+    }
+
+    /// Returns the extension sequences, such as "u-nu-latn", each starting
+    /// with its singleton.
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Returns the private use sequence, such as "x-custom", if any.
+    pub fn private_use(&self) -> Option<&str> {
+        self.private_use.as_deref()
+    }
+
+    /// Returns the script of the tag, or the script in which the language
+    /// is most likely written if the tag does not specify one.
+    ///
+    /// Chinese is assumed to be written in traditional characters in Hong
+    /// Kong, Macao and Taiwan and in simplified characters elsewhere. Only
+    /// languages whose script affects font selection are inferred.
+    pub fn likely_script(&self) -> Option<&str> {
+        if let Some(script) = self.script.as_deref() {
+            return Some(script);
+        }
+        let language = match self.extended_languages.first() {
+            Some(extlang) if self.language == "zh" => extlang.as_str(),
+            _ => self.language.as_str(),
+        };
+        Some(match language {
+            "zh" | "cmn" | "wuu" | "hak" | "nan" | "gan" | "hsn" => match self.region.as_deref() {
+                Some("HK") | Some("MO") | Some("TW") => "Hant",
+                _ => "Hans",
+            },
+            "yue" => match self.region.as_deref() {
+                Some("CN") => "Hans",
+                _ => "Hant",
+            },
+            "ja" => "Jpan",
+            "ko" => "Kore",
+            "ar" | "fa" | "ur" | "ps" | "ug" | "ckb" => "Arab",
+            "he" | "yi" => "Hebr",
+            "el" => "Grek",
+            "ru" | "uk" | "be" | "bg" | "mk" | "sr" | "kk" | "ky" | "mn" | "tg" => "Cyrl",
+            "hy" => "Armn",
+            "ka" => "Geor",
+            "hi" | "mr" | "ne" | "sa" => "Deva",
+            "bn" | "as" => "Beng",
+            "pa" => "Guru",
+            "gu" => "Gujr",
+            "or" => "Orya",
+            "ta" => "Taml",
+            "te" => "Telu",
+            "kn" => "Knda",
+            "ml" => "Mlym",
+            "si" => "Sinh",
+            "th" => "Thai",
+            "lo" => "Laoo",
+            "my" => "Mymr",
+            "km" => "Khmr",
+            "bo" | "dz" => "Tibt",
+            "am" | "ti" => "Ethi",
+            "dv" => "Thaa",
+            "syr" => "Syrc",
+            "chr" => "Cher",
+            "iu" => "Cans",
+            _ => return None,
+        })
+    }
+
+    /// Returns the CJK locale of the tag, inferring the script from the
+    /// language and region where necessary.
+    ///
+    /// Unlike [`Locale::cjk`], this distinguishes simplified and traditional
+    /// Chinese by region, as in "zh-CN" and "zh-HK".
+    pub fn cjk(&self) -> Cjk {
+        match self.likely_script() {
+            Some("Hans") => Cjk::Simplified,
+            Some("Hant") => Cjk::Traditional,
+            Some("Jpan") | Some("Hira") | Some("Kana") => Cjk::Japanese,
+            Some("Kore") | Some("Hang") => Cjk::Korean,
+            _ => Cjk::None,
+        }
+    }
+
+    /// Converts the tag to a locale with the language, the likely script and
+    /// an alphabetic region, which is used to select fallback chains.
+    /// Returns `None` if the language is longer than three letters.
+    ///
+    /// The likely script is included so that the CJK locale of the result
+    /// matches [`cjk`](Self::cjk) for Chinese and so that chains configured
+    /// for a script, such as "zh-Hant", apply to tags that imply it, such
+    /// as "zh-HK".
+    pub fn to_locale(&self) -> Option<Locale> {
+        if self.language.len() > 3 {
+            return None;
+        }
+        let mut tag = self.language.clone();
+        if let Some(script) = self.likely_script() {
+            tag.push('-');
+            tag.push_str(script);
+        }
+        if let Some(region) = self.region.as_deref().filter(|r| r.len() == 2) {
+            tag.push('-');
+            tag.push_str(region);
+        }
+        Locale::parse(&tag)
+    }
+
+    /// Returns the number of components of the tag that the locale matches,
+    /// or `None` if any component specified by the tag differs.
+    pub(crate) fn match_locale(&self, locale: &Locale) -> Option<usize> {
+        if self.language != locale.language() {
+            return None;
+        }
+        let mut specificity = 1;
+        if let Some(script) = &self.script {
+            if locale.script() != Some(script.as_str()) {
+                return None;
+            }
+            specificity += 1;
+        }
+        if let Some(region) = &self.region {
+            if locale.region() != Some(region.as_str()) {
+                return None;
+            }
+            specificity += 1;
+        }
+        Some(specificity)
+    }
+}
+
+impl std::str::FromStr for LanguageTag {
+    type Err = ParseLanguageTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s).ok_or(ParseLanguageTagError)
+    }
+}
+
+/// Error returned when a string is not a well formed language tag.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ParseLanguageTagError;
+
+impl fmt::Display for ParseLanguageTagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed language tag")
+    }
+}
+
+impl std::error::Error for ParseLanguageTagError {}
+
+impl fmt::Display for LanguageTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.language)?;
+        let subtags = self
+            .extended_languages
+            .iter()
+            .map(|s| s.as_str())
+            .chain(self.script.as_deref())
+            .chain(self.region.as_deref())
+            .chain(self.variants.iter().map(|s| s.as_str()))
+            .chain(self.extensions.iter().map(|s| s.as_str()))
+            .chain(self.private_use.as_deref());
+        for subtag in subtags {
+            write!(f, "-{}", subtag)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_alpha(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+fn is_alphanumeric(s: &str, min: usize, max: usize) -> bool {
+    (min..=max).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn is_digits(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn is_variant(s: &str) -> bool {
+    is_alphanumeric(s, 5, 8)
+        || (s.len() == 4 && s.as_bytes()[0].is_ascii_digit() && is_alphanumeric(s, 4, 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(tag: &str) -> LanguageTag {
+        LanguageTag::parse(tag).unwrap_or_else(|| panic!("{} should parse", tag))
+    }
+
+    #[test]
+    fn parses_components() {
+        let tag = parse("ZH_hant_hk");
+        assert_eq!(tag.language(), "zh");
+        assert_eq!(tag.script(), Some("Hant"));
+        assert_eq!(tag.region(), Some("HK"));
+        assert_eq!(tag.to_string(), "zh-Hant-HK");
+        let tag = parse("zh-yue-CN");
+        assert_eq!(tag.extended_languages(), ["yue"]);
+        assert_eq!(tag.likely_script(), Some("Hans"));
+        assert_eq!(parse("es-419").region(), Some("419"));
+        let tag = parse("de-DE-1996-u-co-phonebk-x-private");
+        assert_eq!(tag.variants(), ["1996"]);
+        assert_eq!(tag.extensions(), ["u-co-phonebk"]);
+        assert_eq!(tag.private_use(), Some("x-private"));
+    }
+
+    #[test]
+    fn rejects_malformed_tags() {
+        for tag in [
+            "",
+            "a",
+            "abcd",
+            "en--US",
+            "en-US-",
+            "de-1996-1996",
+            "en-x",
+            "en-a-x-b",
+            "en-US-US",
+        ] {
+            assert_eq!(LanguageTag::parse(tag), None, "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn converts_to_locale() {
+        let locale = parse("zh-HK").to_locale().unwrap();
+        assert_eq!(locale.script(), Some("Hant"));
+        assert_eq!(locale.region(), Some("HK"));
+        // Numeric regions cannot be represented by a locale.
+        let locale = parse("es-419").to_locale().unwrap();
+        assert_eq!(locale.region(), None);
+        assert_eq!(parse("es-419").match_locale(&locale), None);
+        assert_eq!(parse("es").match_locale(&locale), Some(1));
+        assert_eq!(parse("cmn").cjk(), Cjk::Simplified);
+        assert_eq!(parse("sr-Latn").likely_script(), Some("Latn"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Line metrics recorded from the OS/2 and hhea tables.
+
+use super::system::{Os, OS};
+use super::tables::{read_i16, read_u16};
+use swash::{tag_from_bytes, FontRef, Tag};
+
+const HEAD: Tag = tag_from_bytes(b"head");
+const HHEA: Tag = tag_from_bytes(b"hhea");
+const OS2: Tag = tag_from_bytes(b"OS/2");
+
+/// Bit in the OS/2 fsSelection field indicating that the typographic
+/// metrics should be used for line layout.
+const USE_TYPO_METRICS: u16 = 1 << 7;
+
+/// Policy for selecting the table that provides line metrics.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum MetricsPolicy {
+    /// Use the typographic metrics from the OS/2 table, falling back to the
+    /// hhea table if the OS/2 table is missing.
+    Typographic,
+    /// Use the metrics from the hhea table.
+    Legacy,
+    /// Follow the conventions of the current platform.
+    #[default]
+    Platform,
+}
+
+/// Vertical line metrics in font units.
+///
+/// Both ascent and descent are expressed as positive distances from the
+/// baseline.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct LineMetrics {
+    /// Number of font units per em.
+    pub units_per_em: u16,
+    /// Distance from the baseline to the top of the line.
+    pub ascent: i32,
+    /// Distance from the baseline to the bottom of the line.
+    pub descent: i32,
+    /// Recommended additional spacing between lines.
+    pub line_gap: i32,
+}
+
+impl LineMetrics {
+    /// Returns the recommended distance between consecutive baselines.
+    pub fn line_height(&self) -> i32 {
+        self.ascent + self.descent + self.line_gap
+    }
+}
+
+/// Full set of line metrics recorded for a font at scan time.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct FaceMetrics {
+    pub typographic: Option<LineMetrics>,
+    pub windows: Option<LineMetrics>,
+    pub legacy: LineMetrics,
+    pub use_typographic: bool,
+}
+
+impl FaceMetrics {
+    pub fn from_font(font: &FontRef) -> Option<Self> {
+        let units_per_em = read_u16(font.table(HEAD)?, 18)?;
+        let legacy = read_metrics(font.table(HHEA)?, units_per_em, 4, true)?;
+        let mut metrics = Self {
+            legacy,
+            ..Default::default()
+        };
+        if let Some(os2) = font.table(OS2) {
+            metrics.use_typographic =
+                read_u16(os2, 62).map(|bits| bits & USE_TYPO_METRICS != 0) == Some(true);
+            metrics.typographic = read_metrics(os2, units_per_em, 68, true);
+            metrics.windows = read_metrics(os2, units_per_em, 74, false);
+        }
+        Some(metrics)
+    }
+
+    pub fn select(&self, policy: MetricsPolicy) -> LineMetrics {
+        match policy {
+            MetricsPolicy::Typographic => self.typographic.unwrap_or(self.legacy),
+            MetricsPolicy::Legacy => self.legacy,
+            MetricsPolicy::Platform => {
+                if self.use_typographic {
+                    return self.typographic.unwrap_or(self.legacy);
+                }
+                match OS {
+                    Os::Windows => self.windows.unwrap_or(self.legacy),
+                    _ => self.legacy,
+                }
+            }
+        }
+    }
+}
+
+/// Reads ascent, descent and line gap fields starting at the specified
+/// offset. Signed fields store the descent as a negative value while the
+/// unsigned Windows fields have no line gap.
+fn read_metrics(
+    data: &[u8],
+    units_per_em: u16,
+    offset: usize,
+    signed: bool,
+) -> Option<LineMetrics> {
+    Some(if signed {
+        LineMetrics {
+            units_per_em,
+            ascent: read_i16(data, offset)? as i32,
+            descent: -(read_i16(data, offset + 2)? as i32),
+            line_gap: read_i16(data, offset + 4)? as i32,
+        }
+    } else {
+        LineMetrics {
+            units_per_em,
+            ascent: read_u16(data, offset)? as i32,
+            descent: read_u16(data, offset + 2)? as i32,
+            line_gap: 0,
+        }
+    })
+}
@@ -0,0 +1,179 @@
+//! Bounded cache of localized strings.
+//!
+//! Extracting every localized name from every font on a system with a large
+//! font set can consume a significant amount of memory. Strings are instead
+//! read from the name table on request and retained in a cache shared by the
+//! library. Equal strings are stored once and the least recently inserted
+//! entries are discarded when the cache exceeds its capacity. Entries whose
+//! strings are still held by callers are kept since discarding them would
+//! not release any memory.
+
+use super::id::FontId;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Approximate bookkeeping cost of an entry in bytes, in addition to the
+/// lengths of the language and string.
+const ENTRY_OVERHEAD: usize = 48;
+
+type Key = (FontId, u16);
+
+/// Language and cached string, which is `None` if the font lacks it.
+type Entry = (Box<str>, Option<Arc<str>>);
+
+/// Cache of localized strings keyed by font, string identifier and language.
+#[derive(Default)]
+pub struct NameCache {
+    capacity: usize,
+    state: Mutex<NameCacheState>,
+}
+
+#[derive(Default)]
+struct NameCacheState {
+    entries: HashMap<Key, Vec<Entry>>,
+    /// Interned strings with the number of entries that refer to each.
+    strings: HashMap<Arc<str>, usize>,
+    order: VecDeque<(Key, Box<str>)>,
+    size: usize,
+}
+
+impl NameCache {
+    /// Creates a cache holding approximately `capacity` bytes. A capacity
+    /// of zero disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns the cached string for the key or computes and caches it.
+    ///
+    /// Strings that are not present in the font are cached as well so that
+    /// repeated lookups do not reparse the name table.
+    pub fn get_or_insert(
+        &self,
+        font: FontId,
+        id: u16,
+        language: &str,
+        f: impl FnOnce() -> Option<String>,
+    ) -> Option<Arc<str>> {
+        if self.capacity == 0 {
+            return f().map(Into::into);
+        }
+        let key = (font, id);
+        {
+            let state = self.lock();
+            if let Some(value) = state.get(key, language) {
+                return value.clone();
+            }
+        }
+        // Compute the string without holding the lock since this may load
+        // the font.
+        let value = f();
+        let mut state = self.lock();
+        if let Some(value) = state.get(key, language) {
+            return value.clone();
+        }
+        let value = value.map(|value| state.intern(value));
+        let cost = ENTRY_OVERHEAD + language.len() + value.as_ref().map_or(0, |v| v.len());
+        state.size += cost;
+        state
+            .entries
+            .entry(key)
+            .or_default()
+            .push((language.into(), value.clone()));
+        state.order.push_back((key, language.into()));
+        while state.size > self.capacity {
+            if !state.evict() {
+                break;
+            }
+        }
+        value
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&self) {
+        *self.lock() = Default::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, NameCacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl NameCacheState {
+    fn get(&self, key: Key, language: &str) -> Option<&Option<Arc<str>>> {
+        self.entries
+            .get(&key)?
+            .iter()
+            .find(|(lang, _)| &**lang == language)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the shared copy of a string, counting the new entry that
+    /// refers to it.
+    fn intern(&mut self, value: String) -> Arc<str> {
+        if let Some((value, count)) = self.strings.get_key_value(value.as_str()) {
+            let value = value.clone();
+            *self.strings.get_mut(&value).unwrap() = count + 1;
+            return value;
+        }
+        let value: Arc<str> = value.into();
+        self.strings.insert(value.clone(), 1);
+        value
+    }
+
+    /// Returns true if a cached string is referenced outside of the cache.
+    fn is_held(&self, value: &Option<Arc<str>>) -> bool {
+        match value {
+            // The cache holds one reference in the interned set and one for
+            // each entry.
+            Some(value) => {
+                let count = self.strings.get(value).copied().unwrap_or(0);
+                Arc::strong_count(value) > count + 1
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the oldest entry whose string is not held by a caller.
+    /// Returns false if no entry can be removed.
+    fn evict(&mut self) -> bool {
+        for _ in 0..self.order.len() {
+            let (key, language) = match self.order.pop_front() {
+                Some(oldest) => oldest,
+                None => return false,
+            };
+            let values = match self.entries.get(&key) {
+                Some(values) => values,
+                None => continue,
+            };
+            let pos = match values.iter().position(|(lang, _)| *lang == language) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            if self.is_held(&values[pos].1) {
+                // Keep the entry and consider it again after the others.
+                self.order.push_back((key, language));
+                continue;
+            }
+            let values = self.entries.get_mut(&key).unwrap();
+            let (_, value) = values.remove(pos);
+            if values.is_empty() {
+                self.entries.remove(&key);
+            }
+            self.size -= ENTRY_OVERHEAD + language.len() + value.as_ref().map_or(0, |v| v.len());
+            if let Some(value) = value {
+                match self.strings.get_mut(&value) {
+                    Some(count) if *count > 1 => *count -= 1,
+                    _ => {
+                        self.strings.remove(&value);
+                    }
+                }
+            }
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,420 @@
+//! Native enumeration of installed fonts, used in place of font-kit when the
+//! `font-kit` feature is disabled.
+//!
+//! Only the mapping from family names to font files is needed, so each
+//! platform uses the simplest service that provides it: fontconfig on Linux
+//! and the BSDs, Core Text on macOS with the `coretext` feature and the font
+//! registry on Windows. Other platforms report an error so that the platform
+//! font directories are scanned instead.
+
+#[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Location of a font returned by the platform.
+#[allow(dead_code)]
+pub enum Handle {
+    Path {
+        path: PathBuf,
+        font_index: u32,
+    },
+    Memory {
+        bytes: Arc<Vec<u8>>,
+        font_index: u32,
+    },
+}
+
+/// Fonts that belong to a family.
+pub struct FamilyHandle {
+    fonts: Vec<Handle>,
+}
+
+impl FamilyHandle {
+    pub fn fonts(&self) -> &[Handle] {
+        &self.fonts
+    }
+}
+
+/// Platform font source.
+pub struct SystemSource {
+    /// Face names and files listed in the font registry.
+    #[cfg(target_os = "windows")]
+    faces: Vec<(String, PathBuf)>,
+    /// Fonts listed by fontconfig, which are enumerated once rather than
+    /// for each lookup.
+    #[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+    index: io::Result<FontIndex>,
+}
+
+impl SystemSource {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(target_os = "windows")]
+            faces: registry_faces(),
+            #[cfg(not(any(
+                all(feature = "coretext", target_os = "macos"),
+                target_os = "windows"
+            )))]
+            index: FontIndex::new(),
+        }
+    }
+
+    /// Returns the names of all installed families.
+    pub fn all_families(&self) -> io::Result<Vec<String>> {
+        let mut families = Vec::<String>::new();
+        for name in self.family_names()? {
+            let name = name.trim();
+            if !name.is_empty() && !families.iter().any(|f| f.eq_ignore_ascii_case(name)) {
+                families.push(name.to_owned());
+            }
+        }
+        Ok(families)
+    }
+
+    /// Returns the fonts of the family with the specified name. Returns an
+    /// error if the family is not installed.
+    pub fn select_family_by_name(&self, name: &str) -> io::Result<FamilyHandle> {
+        let mut fonts = Vec::<Handle>::new();
+        for (path, font_index) in self.family_files(name)? {
+            let known = fonts.iter().any(|font| match font {
+                Handle::Path {
+                    path: p,
+                    font_index: i,
+                } => *p == path && *i == font_index,
+                _ => false,
+            });
+            if !known {
+                fonts.push(Handle::Path { path, font_index });
+            }
+        }
+        if fonts.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(FamilyHandle { fonts })
+    }
+
+    #[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+    fn family_names(&self) -> io::Result<Vec<String>> {
+        Ok(self.index()?.families.clone())
+    }
+
+    #[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+    fn family_files(&self, name: &str) -> io::Result<Vec<(PathBuf, u32)>> {
+        let index = self.index()?;
+        Ok(index
+            .files
+            .get(&FontIndex::key(name))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    #[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+    fn index(&self) -> io::Result<&FontIndex> {
+        self.index
+            .as_ref()
+            .map_err(|err| io::Error::new(err.kind(), err.to_string()))
+    }
+
+    #[cfg(all(feature = "coretext", target_os = "macos"))]
+    fn family_names(&self) -> io::Result<Vec<String>> {
+        let names = core_text::font_collection::get_family_names();
+        Ok(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    #[cfg(all(feature = "coretext", target_os = "macos"))]
+    fn family_files(&self, name: &str) -> io::Result<Vec<(PathBuf, u32)>> {
+        let descriptors = core_text::font_collection::create_for_family(name)
+            .and_then(|collection| collection.get_descriptors());
+        let descriptors = match descriptors {
+            Some(descriptors) => descriptors,
+            None => return Ok(Vec::new()),
+        };
+        // Core Text does not report the index of a font in a collection file,
+        // but scanning a file adds every font that it contains.
+        Ok(descriptors
+            .iter()
+            .filter_map(|descriptor| Some((descriptor.font_path()?, 0)))
+            .collect())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn family_names(&self) -> io::Result<Vec<String>> {
+        if self.faces.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(self
+            .faces
+            .iter()
+            .map(|(face, _)| registry_family(face).to_owned())
+            .collect())
+    }
+
+    #[cfg(target_os = "windows")]
+    fn family_files(&self, name: &str) -> io::Result<Vec<(PathBuf, u32)>> {
+        // Registry entries name faces rather than families, so every face
+        // whose name starts with the family name is scanned. Fonts of other
+        // families found this way are added under their own names.
+        let name = name.to_lowercase();
+        Ok(self
+            .faces
+            .iter()
+            .filter(|(face, _)| {
+                let face = face.to_lowercase();
+                face == name
+                    || face
+                        .strip_prefix(&name)
+                        .is_some_and(|rest| rest.starts_with(' '))
+            })
+            .map(|(_, path)| (path.clone(), 0))
+            .collect())
+    }
+}
+
+/// Families and files of the fonts listed by fontconfig.
+#[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+struct FontIndex {
+    /// Primary family names in the order listed.
+    families: Vec<String>,
+    /// Files and face indices keyed by every name of their families.
+    files: HashMap<String, Vec<(PathBuf, u32)>>,
+}
+
+#[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+impl FontIndex {
+    fn new() -> io::Result<Self> {
+        let output = fc_list(&[], "%{index}\t%{file}\t%{family}\n")?;
+        let mut index = Self {
+            families: Vec::new(),
+            files: HashMap::new(),
+        };
+        for line in output.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (face_index, path, names) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(face_index), Some(path), Some(names)) => (face_index, path, names),
+                _ => continue,
+            };
+            let face_index = face_index.trim().parse().unwrap_or(0);
+            // Fonts list their localized family names after the primary one.
+            let names = names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty());
+            if let Some(primary) = names.clone().next() {
+                index.families.push(primary.to_owned());
+            }
+            for name in names {
+                let files = index.files.entry(Self::key(name)).or_default();
+                let file = (PathBuf::from(path), face_index);
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    /// Returns the key for a family name. Fontconfig compares family names
+    /// without regard to case or spaces.
+    fn key(name: &str) -> String {
+        name.chars()
+            .filter(|ch| *ch != ' ')
+            .flat_map(char::to_lowercase)
+            .collect()
+    }
+}
+
+/// Runs `fc-list` with the specified arguments and output format.
+#[cfg(not(any(all(feature = "coretext", target_os = "macos"), target_os = "windows")))]
+fn fc_list(args: &[&str], format: &str) -> io::Result<String> {
+    use super::system::{Os, OS};
+    if !matches!(OS, Os::Unix | Os::Bsd) {
+        return Err(io::ErrorKind::Unsupported.into());
+    }
+    let output = std::process::Command::new("fc-list")
+        .arg("--format")
+        .arg(format)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("fc-list exited with {}", output.status),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads the faces listed in the machine and user font registry keys.
+#[cfg(target_os = "windows")]
+fn registry_faces() -> Vec<(String, PathBuf)> {
+    const KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion\Fonts";
+    let fonts_dir = std::env::var_os("WINDIR")
+        .map(|dir| PathBuf::from(dir).join("Fonts"))
+        .unwrap_or_else(|| PathBuf::from(r"C:\Windows\Fonts"));
+    let mut faces = Vec::new();
+    for root in [registry::HKEY_LOCAL_MACHINE, registry::HKEY_CURRENT_USER] {
+        for (name, file) in registry::string_values(root, KEY) {
+            let name = name.trim();
+            // Machine wide fonts are stored as file names relative to the
+            // fonts directory while per-user fonts have full paths.
+            let path = PathBuf::from(file);
+            let path = if path.is_absolute() {
+                path
+            } else {
+                fonts_dir.join(path)
+            };
+            // Strip the format suffix, as in "Arial Bold (TrueType)", and
+            // split collections, as in "Cambria & Cambria Math".
+            let name = match name.rfind(" (") {
+                Some(pos) => &name[..pos],
+                None => name,
+            };
+            for face in name.split(" & ") {
+                faces.push((face.trim().to_owned(), path.clone()));
+            }
+        }
+    }
+    faces
+}
+
+/// Returns the family name of a face listed in the registry by removing
+/// trailing style words, as in "Segoe UI Semibold Italic".
+#[cfg(target_os = "windows")]
+fn registry_family(face: &str) -> &str {
+    const STYLES: &[&str] = &[
+        "regular",
+        "italic",
+        "oblique",
+        "bold",
+        "semibold",
+        "demibold",
+        "extrabold",
+        "black",
+        "heavy",
+        "medium",
+        "light",
+        "semilight",
+        "extralight",
+        "thin",
+        "condensed",
+        "narrow",
+    ];
+    let mut family = face;
+    while let Some((rest, word)) = family.rsplit_once(' ') {
+        if !STYLES.iter().any(|style| word.eq_ignore_ascii_case(style)) {
+            break;
+        }
+        family = rest;
+    }
+    family
+}
+
+/// Minimal bindings to the registry functions of the Windows API.
+#[cfg(target_os = "windows")]
+mod registry {
+    use std::ffi::{OsStr, OsString};
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::ptr;
+
+    /// Handle to an open registry key.
+    pub type Hkey = isize;
+
+    pub const HKEY_CURRENT_USER: Hkey = 0x8000_0001u32 as i32 as Hkey;
+    pub const HKEY_LOCAL_MACHINE: Hkey = 0x8000_0002u32 as i32 as Hkey;
+
+    const KEY_READ: u32 = 0x2_0019;
+    const REG_SZ: u32 = 1;
+    const REG_EXPAND_SZ: u32 = 2;
+    const ERROR_SUCCESS: i32 = 0;
+    const ERROR_MORE_DATA: i32 = 234;
+    /// Maximum length of a value name in characters, including the
+    /// terminator.
+    const MAX_NAME_LEN: usize = 16_384;
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn RegOpenKeyExW(
+            key: Hkey,
+            sub_key: *const u16,
+            options: u32,
+            desired: u32,
+            result: *mut Hkey,
+        ) -> i32;
+        fn RegEnumValueW(
+            key: Hkey,
+            index: u32,
+            name: *mut u16,
+            name_len: *mut u32,
+            reserved: *mut u32,
+            kind: *mut u32,
+            data: *mut u8,
+            data_len: *mut u32,
+        ) -> i32;
+        fn RegCloseKey(key: Hkey) -> i32;
+    }
+
+    /// Returns the names and contents of the string values of a key. Keys
+    /// that cannot be opened have no values.
+    pub fn string_values(root: Hkey, path: &str) -> Vec<(String, OsString)> {
+        let path = OsStr::new(path)
+            .encode_wide()
+            .chain(Some(0))
+            .collect::<Vec<_>>();
+        let mut key: Hkey = 0;
+        // The path is nul terminated and the result is written to a
+        // valid handle.
+        if unsafe { RegOpenKeyExW(root, path.as_ptr(), 0, KEY_READ, &mut key) } != ERROR_SUCCESS {
+            return Vec::new();
+        }
+        let mut values = Vec::new();
+        let mut name = vec![0u16; MAX_NAME_LEN];
+        let mut data = vec![0u8; 512];
+        let mut index = 0;
+        loop {
+            let mut name_len = name.len() as u32;
+            let mut data_len = data.len() as u32;
+            let mut kind = 0;
+            // The lengths passed are the sizes of the buffers.
+            let status = unsafe {
+                RegEnumValueW(
+                    key,
+                    index,
+                    name.as_mut_ptr(),
+                    &mut name_len,
+                    ptr::null_mut(),
+                    &mut kind,
+                    data.as_mut_ptr(),
+                    &mut data_len,
+                )
+            };
+            match status {
+                ERROR_SUCCESS => {}
+                // Retry the same value with a buffer of the reported size.
+                ERROR_MORE_DATA if data_len as usize > data.len() => {
+                    data.resize(data_len as usize, 0);
+                    continue;
+                }
+                // Includes the end of the values.
+                _ => break,
+            }
+            index += 1;
+            if kind != REG_SZ && kind != REG_EXPAND_SZ {
+                continue;
+            }
+            let contents = data[..data_len as usize]
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .take_while(|&unit| unit != 0)
+                .collect::<Vec<_>>();
+            values.push((
+                String::from_utf16_lossy(&name[..name_len as usize]),
+                OsString::from_wide(&contents),
+            ));
+        }
+        // The key was opened above.
+        unsafe { RegCloseKey(key) };
+        values
+    }
+}
@@ -0,0 +1,313 @@
+//! Parsing of fontconfig pattern strings.
+
+use super::{FamilyKey, GenericFamily};
+use std::borrow::Cow;
+use swash::{Attributes, ObliqueAngle, Stretch, Style, Weight};
+
+/// Font query parsed from a fontconfig pattern string such as
+/// "DejaVu Sans:style=Bold:size=12" or "Monospace-11".
+///
+/// This eases migration for applications that store font settings in the
+/// format used by fontconfig. Use with
+/// [`FontContext::match_pattern`](super::FontContext::match_pattern).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct FontPattern {
+    /// Requested families in order of preference.
+    pub families: Vec<String>,
+    /// Requested attributes.
+    pub attributes: Attributes,
+    /// Requested point size, if any.
+    pub size: Option<f32>,
+}
+
+impl FontPattern {
+    /// Parses a fontconfig pattern string.
+    ///
+    /// The pattern begins with a comma separated list of families,
+    /// optionally followed by a hyphen and a point size, and continues with
+    /// colon separated elements. The `family`, `style`, `weight`, `slant`,
+    /// `width` and `size` elements are recognized, along with constants
+    /// such as "bold" and "italic" in place of an element. Other elements
+    /// are ignored. Backslashes escape the separators in names. Returns
+    /// `None` if a recognized element has an invalid value.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut pattern = Self::default();
+        let mut elements = split_escaped(s, ':').into_iter();
+        let head = elements.next().unwrap_or_default();
+        let mut names = head;
+        // The size follows the last unescaped hyphen. Names containing a
+        // hyphen, such as "sans-serif", are accepted when the remainder is
+        // not a size.
+        if let [_, .., size] = split_escaped(head, '-')[..] {
+            if let Ok(value) = size.trim().parse::<f32>() {
+                names = &head[..head.len() - size.len() - 1];
+                pattern.size = Some(value);
+            }
+        }
+        pattern.push_families(names);
+        let (mut stretch, mut weight, mut style) = pattern.attributes.parts();
+        for element in elements {
+            let (name, value) = match element.find('=') {
+                Some(pos) => (&element[..pos], &element[pos + 1..]),
+                None => {
+                    apply_style_name(element, &mut stretch, &mut weight, &mut style);
+                    continue;
+                }
+            };
+            match name.trim().to_ascii_lowercase().as_str() {
+                "family" => pattern.push_families(value),
+                "style" => apply_style_name(value, &mut stretch, &mut weight, &mut style),
+                "weight" => weight = parse_weight(value)?,
+                "slant" => style = parse_slant(value)?,
+                "width" => stretch = parse_width(value)?,
+                "size" => pattern.size = Some(value.trim().parse().ok()?),
+                _ => {}
+            }
+        }
+        pattern.attributes = Attributes::new(stretch, weight, style);
+        Some(pattern)
+    }
+
+    /// Returns the families of the pattern as keys for
+    /// [`FontContext::match_font`](super::FontContext::match_font).
+    ///
+    /// The fontconfig aliases "sans", "sans-serif", "serif", "mono" and
+    /// "monospace" produce generic families.
+    pub fn family_keys(&self) -> Vec<FamilyKey<'_>> {
+        self.families
+            .iter()
+            .map(|name| {
+                let generic = match name.to_ascii_lowercase().as_str() {
+                    "sans" => Some(GenericFamily::SansSerif),
+                    "mono" => Some(GenericFamily::Monospace),
+                    name => GenericFamily::parse(name),
+                };
+                match generic {
+                    Some(generic) => FamilyKey::Generic(generic),
+                    None => FamilyKey::Named(Cow::Borrowed(name)),
+                }
+            })
+            .collect()
+    }
+
+    fn push_families(&mut self, list: &str) {
+        for name in split_escaped(list, ',') {
+            let name = unescape(name.trim());
+            if !name.is_empty() {
+                self.families.push(name);
+            }
+        }
+    }
+}
+
+/// Splits a string at each occurrence of the separator that is not escaped
+/// by a backslash. Escapes are preserved in the parts.
+fn split_escaped(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, ch) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == separator {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+/// Applies the words of a fontconfig style name such as "Bold Italic" or a
+/// constant such as "semicondensed". Unrecognized words are ignored.
+fn apply_style_name(name: &str, stretch: &mut Stretch, weight: &mut Weight, style: &mut Style) {
+    for word in name.split_whitespace() {
+        let word = word.to_ascii_lowercase();
+        if let Some(value) = weight_constant(&word) {
+            *weight = value;
+        } else if let Some(value) = slant_constant(&word) {
+            *style = value;
+        } else if let Some(value) = width_constant(&word) {
+            *stretch = value;
+        }
+    }
+}
+
+/// Weights on the fontconfig scale and the corresponding OpenType weights.
+const WEIGHTS: [(f32, f32); 11] = [
+    (0., 100.),
+    (40., 200.),
+    (50., 300.),
+    (55., 350.),
+    (75., 380.),
+    (80., 400.),
+    (100., 500.),
+    (180., 600.),
+    (200., 700.),
+    (205., 800.),
+    (210., 900.),
+];
+
+fn parse_weight(value: &str) -> Option<Weight> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(weight) = weight_constant(&value) {
+        return Some(weight);
+    }
+    let value = value.parse::<f32>().ok()?.max(0.);
+    let pos = WEIGHTS.iter().position(|(fc, _)| value <= *fc);
+    let weight = match pos {
+        Some(0) => WEIGHTS[0].1,
+        Some(pos) => {
+            let (fc0, ot0) = WEIGHTS[pos - 1];
+            let (fc1, ot1) = WEIGHTS[pos];
+            ot0 + (value - fc0) * (ot1 - ot0) / (fc1 - fc0)
+        }
+        None => 1000f32.min(900. + (value - 210.) * 2.),
+    };
+    Some(Weight(weight.round() as u16))
+}
+
+fn weight_constant(word: &str) -> Option<Weight> {
+    Some(match word {
+        "thin" => Weight::THIN,
+        "extralight" | "ultralight" => Weight::EXTRA_LIGHT,
+        "light" => Weight::LIGHT,
+        "demilight" | "semilight" => Weight(350),
+        "book" => Weight(380),
+        "regular" | "normal" => Weight::NORMAL,
+        "medium" => Weight::MEDIUM,
+        "demibold" | "semibold" => Weight::SEMI_BOLD,
+        "bold" => Weight::BOLD,
+        "extrabold" | "ultrabold" => Weight::EXTRA_BOLD,
+        "black" | "heavy" => Weight::BLACK,
+        _ => return None,
+    })
+}
+
+fn parse_slant(value: &str) -> Option<Style> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(style) = slant_constant(&value) {
+        return Some(style);
+    }
+    Some(match value.parse::<u32>().ok()? {
+        0 => Style::Normal,
+        100 => Style::Italic,
+        110 => Style::Oblique(ObliqueAngle::from_degrees(14.)),
+        _ => return None,
+    })
+}
+
+fn slant_constant(word: &str) -> Option<Style> {
+    Some(match word {
+        "roman" => Style::Normal,
+        "italic" => Style::Italic,
+        "oblique" => Style::Oblique(ObliqueAngle::from_degrees(14.)),
+        _ => return None,
+    })
+}
+
+fn parse_width(value: &str) -> Option<Stretch> {
+    let value = value.trim().to_ascii_lowercase();
+    if let Some(stretch) = width_constant(&value) {
+        return Some(stretch);
+    }
+    let percentage = value.parse::<f32>().ok()?;
+    if percentage > 0. {
+        Some(Stretch::from_percentage(percentage))
+    } else {
+        None
+    }
+}
+
+fn width_constant(word: &str) -> Option<Stretch> {
+    Some(match word {
+        "ultracondensed" => Stretch::ULTRA_CONDENSED,
+        "extracondensed" => Stretch::EXTRA_CONDENSED,
+        "condensed" => Stretch::CONDENSED,
+        "semicondensed" => Stretch::SEMI_CONDENSED,
+        "semiexpanded" => Stretch::SEMI_EXPANDED,
+        "expanded" => Stretch::EXPANDED,
+        "extraexpanded" => Stretch::EXTRA_EXPANDED,
+        "ultraexpanded" => Stretch::ULTRA_EXPANDED,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AttributesExt;
+
+    #[test]
+    fn parses_families_and_size() {
+        let pattern = FontPattern::parse("DejaVu Sans,Noto Sans-12").unwrap();
+        assert_eq!(pattern.families, ["DejaVu Sans", "Noto Sans"]);
+        assert_eq!(pattern.size, Some(12.));
+        let pattern = FontPattern::parse("Noto-Sans-10.5").unwrap();
+        assert_eq!(pattern.families, ["Noto-Sans"]);
+        assert_eq!(pattern.size, Some(10.5));
+        let pattern = FontPattern::parse("sans-serif").unwrap();
+        assert_eq!(pattern.families, ["sans-serif"]);
+        assert_eq!(pattern.size, None);
+        let pattern = FontPattern::parse("Foo\\-12").unwrap();
+        assert_eq!(pattern.families, ["Foo-12"]);
+        assert_eq!(pattern.size, None);
+    }
+
+    #[test]
+    fn parses_elements() {
+        let pattern = FontPattern::parse("Mono:style=Bold Italic:size=11:width=condensed").unwrap();
+        assert_eq!(pattern.families, ["Mono"]);
+        assert_eq!(pattern.size, Some(11.));
+        assert_eq!(
+            pattern.attributes,
+            Attributes::new(Stretch::CONDENSED, Weight::BOLD, Style::Italic)
+        );
+        let pattern = FontPattern::parse(":family=A\\,B,C:weight=80:slant=100").unwrap();
+        assert_eq!(pattern.families, ["A,B", "C"]);
+        assert_eq!(
+            pattern.attributes,
+            Attributes::default().with_style(Style::Italic)
+        );
+        let pattern = FontPattern::parse("Serif:semibold:unknown=1").unwrap();
+        assert_eq!(pattern.attributes.weight(), Weight::SEMI_BOLD);
+        assert_eq!(FontPattern::parse("Serif:weight=heavyish"), None);
+        assert_eq!(FontPattern::parse("Serif:slant=50"), None);
+    }
+
+    #[test]
+    fn maps_fontconfig_weights() {
+        assert_eq!(parse_weight("0"), Some(Weight::THIN));
+        assert_eq!(parse_weight("80"), Some(Weight::NORMAL));
+        assert_eq!(parse_weight("90"), Some(Weight(450)));
+        assert_eq!(parse_weight("200"), Some(Weight::BOLD));
+        assert_eq!(parse_weight("1000"), Some(Weight(1000)));
+    }
+
+    #[test]
+    fn produces_generic_keys() {
+        let pattern = FontPattern::parse("mono,Sans,Fira Code").unwrap();
+        assert_eq!(
+            pattern.family_keys(),
+            [
+                FamilyKey::Generic(GenericFamily::Monospace),
+                FamilyKey::Generic(GenericFamily::SansSerif),
+                FamilyKey::Named(Cow::Borrowed("Fira Code")),
+            ]
+        );
+    }
+}
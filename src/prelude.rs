@@ -0,0 +1,13 @@
+//! Re-exports of the types needed for common font selection tasks.
+//!
+//! ```
+//! use fount::prelude::*;
+//! ```
+
+pub use crate::fonts::{FontHandle, FontRun, Fonts};
+pub use crate::{
+    AttributesExt, FamilyEntry, FamilyId, FamilyKey, FamilyRef, FontContext, FontEntry, FontId,
+    GenericFamily, Library, LibraryBuilder, Locale,
+};
+pub use swash::text::Script;
+pub use swash::{Attributes, Stretch, Style, Weight};
@@ -0,0 +1,155 @@
+//! Chain of resolvers for family names and font requests.
+
+use super::data::fold_name;
+use super::scan::namespaced_name;
+use super::{FamilyId, FontContext, FontEntry, GenericFamily, MatchRequest};
+use std::collections::HashMap;
+
+/// Stage in the resolution of family names and font requests.
+///
+/// A context consults its resolvers in order and uses the first result.
+/// The default stack consists of [`GenericFamilyResolver`],
+/// [`UserFontResolver`], [`SystemFontResolver`] and [`LastResortResolver`]. Custom resolvers can
+/// be inserted with [`FontContext::insert_resolver`], for example to
+/// resolve the names of fonts embedded in a document to fonts registered
+/// with a [`ScopedRegistration`](super::ScopedRegistration). Both methods
+/// return `None` by default, which defers to the next resolver.
+pub trait Resolver: Send + Sync {
+    /// Resolves a family name.
+    fn resolve_family(&self, context: &FontContext, name: &str) -> Option<FamilyId> {
+        None
+    }
+
+    /// Selects a font for a request when none of the requested families
+    /// could be resolved or provided a font.
+    fn resolve_fallback(&self, context: &FontContext, request: &MatchRequest) -> Option<FontEntry> {
+        None
+    }
+}
+
+/// Resolver that maps specific names to families ahead of all other
+/// resolvers when inserted at the front of the stack.
+///
+/// Names are compared without regard to case.
+#[derive(Clone, Default, Debug)]
+pub struct OverrideResolver {
+    families: HashMap<String, FamilyId>,
+}
+
+impl OverrideResolver {
+    /// Creates an empty override resolver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps a family name to the specified family, replacing any previous
+    /// mapping for the name.
+    pub fn add(&mut self, name: &str, family: FamilyId) -> &mut Self {
+        self.families.insert(fold_name(name).collect(), family);
+        self
+    }
+
+    /// Removes the mapping for a family name.
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        self.families.remove(&fold_name(name).collect::<String>());
+        self
+    }
+}
+
+impl Resolver for OverrideResolver {
+    fn resolve_family(&self, _context: &FontContext, name: &str) -> Option<FamilyId> {
+        self.families
+            .get(&fold_name(name).collect::<String>())
+            .copied()
+    }
+}
+
+/// Resolver for fonts registered with
+/// [`FontContext::try_register_fonts_namespaced`].
+///
+/// Family names are resolved to the families registered in the namespace,
+/// so a context that renders a document can resolve the names it uses to
+/// the fonts embedded in it while other contexts sharing the library are
+/// unaffected. Names without a family in the namespace defer to the next
+/// resolver.
+#[derive(Clone, Debug)]
+pub struct NamespaceResolver {
+    namespace: String,
+}
+
+impl NamespaceResolver {
+    /// Creates a resolver for the specified namespace.
+    pub fn new(namespace: impl Into<String>) -> Self {
+        Self {
+            namespace: namespace.into(),
+        }
+    }
+
+    /// Returns the namespace of the resolver.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Returns the name under which a family registered in the namespace is
+    /// stored in the library.
+    pub fn family_name(&self, name: &str) -> String {
+        namespaced_name(name, &self.namespace)
+    }
+}
+
+impl Resolver for NamespaceResolver {
+    fn resolve_family(&self, context: &FontContext, name: &str) -> Option<FamilyId> {
+        context.known_family_id(&self.family_name(name))
+    }
+}
+
+/// Resolver that treats CSS generic family keywords, such as "sans-serif"
+/// and "system-ui", as requests for the first family of the generic family.
+///
+/// Keywords are matched without regard to case, which lets configuration
+/// files store generic families as plain family names.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct GenericFamilyResolver;
+
+impl Resolver for GenericFamilyResolver {
+    fn resolve_family(&self, context: &FontContext, name: &str) -> Option<FamilyId> {
+        let generic = name.parse::<GenericFamily>().ok()?;
+        context
+            .generic_families(generic)
+            .into_iter()
+            .find(|id| context.with_family(*id, |_| ()).is_some())
+    }
+}
+
+/// Resolver for families in the user collection of the library.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct UserFontResolver;
+
+impl Resolver for UserFontResolver {
+    fn resolve_family(&self, context: &FontContext, name: &str) -> Option<FamilyId> {
+        context.user_family_id(name)
+    }
+}
+
+/// Resolver for families in the system collection of the library, which
+/// includes fonts added by the builder and registered fonts. Families that
+/// have not been used yet are located through the platform.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct SystemFontResolver;
+
+impl Resolver for SystemFontResolver {
+    fn resolve_family(&self, context: &FontContext, name: &str) -> Option<FamilyId> {
+        context.system_family_id(name)
+    }
+}
+
+/// Resolver that selects a font from the fallback chain for the script of
+/// the request followed by the default families.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct LastResortResolver;
+
+impl Resolver for LastResortResolver {
+    fn resolve_fallback(&self, context: &FontContext, request: &MatchRequest) -> Option<FontEntry> {
+        context.last_resort(request)
+    }
+}
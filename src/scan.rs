@@ -1,43 +1,399 @@
+use super::cache::{FileStamp, ScanCache};
 use super::data::*;
 use super::id::*;
-use super::{GenericFamily, Registration};
-use std::cell::RefCell;
+use super::metrics::FaceMetrics;
+use super::tables::{meta_design_languages, read_u16, read_u32, stat_axis_values, validate_face};
+use super::vfs::FileSystem;
+use super::{
+    Capabilities, ColorFormat, DuplicatePolicy, FaceRejection, NamedInstance, Registration,
+    RejectedFace, SourcePriority, VariationAxis,
+};
+use std::cmp::Reverse;
 use std::collections::HashSet;
-use std::path::Path;
-use std::rc::Rc;
-use std::sync::{Arc, RwLock};
-use std::{fs, io};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use swash::text::{Cjk, Script};
-use swash::{Attributes, CacheKey, FontDataRef, FontRef, Stretch, StringId};
+use swash::{
+    tag_from_bytes, Attributes, CacheKey, FontDataRef, FontRef, ObliqueAngle, Stretch, StringId,
+    Style, Tag, Weight,
+};
 
-#[derive(Default)]
+const COLOR_TABLES: [(Tag, ColorFormat); 4] = [
+    (tag_from_bytes(b"COLR"), ColorFormat::Colr),
+    (tag_from_bytes(b"CBDT"), ColorFormat::Cbdt),
+    (tag_from_bytes(b"sbix"), ColorFormat::Sbix),
+    (tag_from_bytes(b"SVG "), ColorFormat::Svg),
+];
+const CPAL: Tag = tag_from_bytes(b"CPAL");
+const MATH: Tag = tag_from_bytes(b"MATH");
+const VERT: Tag = tag_from_bytes(b"vert");
+const HEAD: Tag = tag_from_bytes(b"head");
+const OS2: Tag = tag_from_bytes(b"OS/2");
+const POST: Tag = tag_from_bytes(b"post");
+const WGHT: Tag = tag_from_bytes(b"wght");
+const WDTH: Tag = tag_from_bytes(b"wdth");
+const SLNT: Tag = tag_from_bytes(b"slnt");
+const ITAL: Tag = tag_from_bytes(b"ital");
+const STAT: Tag = tag_from_bytes(b"STAT");
+const OPSZ: Tag = tag_from_bytes(b"opsz");
+const META: Tag = tag_from_bytes(b"meta");
+
+/// Maximum number of localized family names kept for a font.
+const MAX_LOCALIZED_NAMES: usize = 32;
+
+/// Metadata for a font produced by a [`FontScanner`].
+///
+/// Scanned fonts can be registered with
+/// [`FontContext::register_scanned_fonts`](super::FontContext::register_scanned_fonts)
+/// to avoid parsing the data again.
+#[derive(Clone)]
 pub struct ScannedFont {
-    pub name: String,
-    pub lowercase_name: String,
-    pub index: u32,
-    pub attributes: Attributes,
-    pub cache_key: CacheKey,
-    pub scripts: HashSet<(Script, Cjk)>,
+    pub(crate) name: String,
+    pub(crate) lowercase_name: String,
+    /// Lowercase family names in other languages.
+    pub(crate) localized_names: Vec<String>,
+    pub(crate) index: u32,
+    pub(crate) attributes: Attributes,
+    pub(crate) cache_key: CacheKey,
+    pub(crate) scripts: HashSet<(Script, Cjk)>,
+    pub(crate) verified_scripts: HashSet<Script>,
+    pub(crate) capabilities: Capabilities,
+    pub(crate) metrics: Option<FaceMetrics>,
+    pub(crate) glyph_count: u16,
+    pub(crate) file_size: u64,
+    pub(crate) priority: SourcePriority,
+    pub(crate) instances: Arc<[NamedInstance]>,
+    pub(crate) axes: Arc<[VariationAxis]>,
+    pub(crate) optical_size: Option<(f32, f32)>,
+    pub(crate) cjk: Cjk,
+    pub(crate) color_format: Option<ColorFormat>,
+    /// True if the family names are qualified with a namespace.
+    pub(crate) namespaced: bool,
+}
+
+impl ScannedFont {
+    /// Qualifies the family names of the font with a namespace. See
+    /// [`NamespaceResolver`](super::NamespaceResolver).
+    pub(crate) fn set_namespace(&mut self, namespace: &str) {
+        self.name = namespaced_name(&self.name, namespace);
+        self.lowercase_name = fold_name(&self.name).collect();
+        // Localized names would otherwise still resolve to the font.
+        for name in &mut self.localized_names {
+            *name = fold_name(&namespaced_name(name, namespace)).collect();
+        }
+        self.namespaced = true;
+    }
+}
+
+impl ScannedFont {
+    /// Returns the family name of the font.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the index of the font in its data.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the primary attributes of the font.
+    pub fn attributes(&self) -> Attributes {
+        self.attributes
+    }
+
+    /// Returns a summary of the capabilities of the font.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Returns the format of the color glyphs in the font, if any.
+    pub fn color_format(&self) -> Option<ColorFormat> {
+        self.color_format
+    }
+
+    /// Returns the number of glyphs in the font.
+    pub fn glyph_count(&self) -> u16 {
+        self.glyph_count
+    }
+
+    /// Returns the scripts that the font was verified to support.
+    ///
+    /// A script is included when the font declares it, or maps a
+    /// representative character of it, and the character map of the font
+    /// contains that character. Declared scripts without a representative
+    /// character, such as [`Script::Common`], are omitted, so this can be a
+    /// subset of the scripts the font is used for in fallback.
+    pub fn scripts(&self) -> impl Iterator<Item = Script> + '_ {
+        self.verified_scripts.iter().copied()
+    }
+
+    /// Returns the variation axes of the font.
+    pub fn axes(&self) -> &[VariationAxis] {
+        &self.axes
+    }
+
+    /// Returns the named instances of the font.
+    pub fn instances(&self) -> &[NamedInstance] {
+        &self.instances
+    }
+}
+
+/// Returns a family name qualified by a namespace.
+pub fn namespaced_name(name: &str, namespace: &str) -> String {
+    format!("{}@{}", name, namespace)
+}
+
+impl Default for ScannedFont {
+    fn default() -> Self {
+        Self {
+            name: Default::default(),
+            lowercase_name: Default::default(),
+            localized_names: Default::default(),
+            index: 0,
+            attributes: Default::default(),
+            cache_key: Default::default(),
+            scripts: Default::default(),
+            verified_scripts: Default::default(),
+            capabilities: Default::default(),
+            metrics: None,
+            glyph_count: 0,
+            file_size: 0,
+            priority: Default::default(),
+            instances: Default::default(),
+            axes: Default::default(),
+            optical_size: None,
+            color_format: None,
+            cjk: Cjk::None,
+            namespaced: false,
+        }
+    }
+}
+
+impl ScannedFont {
+    /// Creates a font from metadata supplied by an external source rather
+    /// than by parsing the font. Such fonts do not declare any scripts and
+    /// so are not added to fallback chains.
+    pub(crate) fn from_metadata(name: &str, index: u32, attributes: Attributes) -> Self {
+        let (_, weight, style) = attributes.parts();
+        let mut capabilities = Capabilities::default();
+        capabilities.insert_attributes(weight, style);
+        Self {
+            name: name.to_owned(),
+            lowercase_name: fold_name(name).collect(),
+            index,
+            attributes,
+            cache_key: CacheKey::new(),
+            capabilities,
+            ..Default::default()
+        }
+    }
 }
 
+/// Parser that extracts the metadata used to select fonts.
+///
+/// The scanner keeps the buffers used while parsing, so reusing one for many
+/// fonts avoids allocating fresh state for each. See [`ScannerPool`] for
+/// scanning on multiple threads.
 #[derive(Default)]
 pub struct FontScanner {
     name: String,
     font: ScannedFont,
+    rejected: Vec<(u32, FaceRejection)>,
 }
 
 impl FontScanner {
-    pub fn scan(&mut self, data: &[u8], source: &SourceData, mut f: impl FnMut(&ScannedFont)) {
+    /// Creates a new scanner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scans all fonts in the specified data, invoking the callback for
+    /// each. The font passed to the callback is reused for the next font.
+    ///
+    /// With the `woff` feature enabled, WOFF and WOFF2 data is decompressed
+    /// before scanning.
+    ///
+    /// Faces that fail the structural checks are skipped and can be
+    /// retrieved with [`rejected`](Self::rejected).
+    pub fn scan(&mut self, data: &[u8], mut f: impl FnMut(&ScannedFont)) {
+        self.rejected.clear();
+        #[cfg(feature = "woff")]
+        if let Some(data) = super::woff::decode(data) {
+            return self.scan(&data, f);
+        }
         if let Some(font_data) = FontDataRef::new(data) {
+            self.font.file_size = data.len() as u64;
             let len = font_data.len();
             for i in 0..len {
                 if let Some(font) = font_data.get(i) {
-                    self.scan_font(&font, i as u32, &mut f);
+                    match validate_face(data, font.offset as usize) {
+                        Ok(()) => {
+                            self.scan_font(&font, i as u32, &mut f);
+                        }
+                        Err(reason) => self.rejected.push((i as u32, reason)),
+                    }
                 }
             }
         }
     }
 
+    /// Returns the indices of the faces rejected by the last scan as
+    /// malformed along with the reasons.
+    pub fn rejected(&self) -> &[(u32, FaceRejection)] {
+        &self.rejected
+    }
+
+    /// Returns the faces rejected by the last scan, attributed to the
+    /// specified path.
+    pub(crate) fn rejected_faces(&self, path: Option<&Path>) -> Vec<RejectedFace> {
+        self.rejected
+            .iter()
+            .map(|(index, reason)| RejectedFace {
+                path: path.map(|path| path.to_owned()),
+                index: *index,
+                reason: *reason,
+            })
+            .collect()
+    }
+
+    /// Scans all fonts in the specified data, returning owned copies of the
+    /// results so that they can be committed to a collection later.
+    pub fn scan_all(&mut self, data: &[u8]) -> Vec<ScannedFont> {
+        let mut fonts = Vec::new();
+        self.scan(data, |font| fonts.push(font.clone()));
+        fonts
+    }
+}
+
+/// Pool of reusable scanners shared by the threads that scan fonts.
+///
+/// Each library owns a pool that is used when fonts are registered and is
+/// available from [`Library::scanner_pool`](super::Library::scanner_pool)
+/// so that external tools can drive scanning directly.
+#[derive(Default)]
+pub struct ScannerPool {
+    idle: Mutex<Vec<FontScanner>>,
+}
+
+impl ScannerPool {
+    /// Creates an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a scanner from the pool, creating one if all are in use. The
+    /// scanner is returned to the pool when the guard is dropped.
+    pub fn get(&self) -> PooledScanner<'_> {
+        let scanner = self
+            .idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop()
+            .unwrap_or_default();
+        PooledScanner {
+            pool: self,
+            scanner: Some(scanner),
+        }
+    }
+
+    /// Scans each of the specified buffers, returning the fonts found in
+    /// each in the original order.
+    ///
+    /// The buffers are parsed concurrently on a pool of threads sized to
+    /// the available parallelism.
+    pub fn scan_all(&self, data: &[&[u8]]) -> Vec<Vec<ScannedFont>> {
+        self.map(data, |scanner, data| scanner.scan_all(data))
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect()
+    }
+
+    /// Applies a function to each item on a pool of threads, returning the
+    /// results in order. Results are `None` for items whose thread panicked.
+    pub(crate) fn map<T: Sync, R: Send>(
+        &self,
+        items: &[T],
+        f: impl Fn(&mut FontScanner, &T) -> R + Sync,
+    ) -> Vec<Option<R>> {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(items.len());
+        if threads <= 1 {
+            let mut scanner = self.get();
+            return items
+                .iter()
+                .map(|item| Some(f(&mut scanner, item)))
+                .collect();
+        }
+        let mut results = (0..items.len()).map(|_| None).collect::<Vec<_>>();
+        let next = AtomicUsize::new(0);
+        let scanned = thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut scanner = self.get();
+                        let mut scanned = Vec::new();
+                        loop {
+                            let n = next.fetch_add(1, Ordering::Relaxed);
+                            let item = match items.get(n) {
+                                Some(item) => item,
+                                None => break,
+                            };
+                            scanned.push((n, f(&mut scanner, item)));
+                        }
+                        scanned
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_default())
+                .collect::<Vec<_>>()
+        });
+        for (n, result) in scanned {
+            results[n] = Some(result);
+        }
+        results
+    }
+}
+
+/// Scanner borrowed from a [`ScannerPool`].
+pub struct PooledScanner<'a> {
+    pool: &'a ScannerPool,
+    scanner: Option<FontScanner>,
+}
+
+impl std::ops::Deref for PooledScanner<'_> {
+    type Target = FontScanner;
+
+    fn deref(&self) -> &FontScanner {
+        self.scanner.as_ref().unwrap()
+    }
+}
+
+impl std::ops::DerefMut for PooledScanner<'_> {
+    fn deref_mut(&mut self) -> &mut FontScanner {
+        self.scanner.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledScanner<'_> {
+    fn drop(&mut self) {
+        if let Some(scanner) = self.scanner.take() {
+            self.pool
+                .idle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(scanner);
+        }
+    }
+}
+
+impl FontScanner {
     fn scan_font(
         &mut self,
         font: &FontRef,
@@ -46,9 +402,12 @@ impl FontScanner {
     ) -> Option<()> {
         self.font.name.clear();
         self.font.lowercase_name.clear();
+        self.font.localized_names.clear();
         self.font.index = index;
         self.font.attributes = Attributes::default();
         self.font.scripts.clear();
+        self.font.verified_scripts.clear();
+        self.font.capabilities = Capabilities::default();
         self.name.clear();
         let strings = font.localized_strings();
         let is_var = font.variations().len() != 0;
@@ -59,6 +418,7 @@ impl FontScanner {
         } else {
             StringId::Family
         };
+        let mut primary_id = name_id;
         if let Some(name) = strings.find_by_id(name_id, Some("en")) {
             self.font.name.extend(name.chars());
         } else if let Some(name) = strings.find_by_id(name_id, None) {
@@ -80,8 +440,10 @@ impl FontScanner {
         }
         if !self.name.is_empty() && self.name.len() < self.font.name.len() {
             core::mem::swap(&mut self.font.name, &mut self.name);
+            primary_id = name_id;
         }
         if self.font.name.is_empty() {
+            primary_id = name_id;
             if let Some(name) = strings.find_by_id(name_id, Some("en")) {
                 self.font.name.extend(name.chars());
             } else if let Some(name) = strings.find_by_id(name_id, None) {
@@ -91,11 +453,54 @@ impl FontScanner {
         if self.font.name.is_empty() {
             return None;
         }
-        self.font
-            .lowercase_name
-            .extend(self.font.name.chars().map(|ch| ch.to_lowercase()).flatten());
-        self.font.attributes = font.attributes();
+        self.font.lowercase_name.extend(fold_name(&self.font.name));
+        // Record the name in every other language so that families can be
+        // resolved by their localized names, such as "メイリオ" for Meiryo.
+        for string in strings {
+            if string.id() != primary_id || !string.is_decodable() {
+                continue;
+            }
+            if self.font.localized_names.len() >= MAX_LOCALIZED_NAMES {
+                break;
+            }
+            let name = fold_name(&string.chars().collect::<String>()).collect::<String>();
+            if !name.is_empty()
+                && name != self.font.lowercase_name
+                && !self.font.localized_names.contains(&name)
+            {
+                self.font.localized_names.push(name);
+            }
+        }
+        let mut parts = font.attributes().parts();
+        apply_stat(font, &mut parts);
+        self.font.attributes = Attributes::new(parts.0, parts.1, parts.2);
         self.font.cache_key = font.key;
+        self.font.capabilities = scan_capabilities(font, is_var);
+        self.font.color_format = color_format(font);
+        self.font.capabilities.insert_attributes(parts.1, parts.2);
+        self.font.metrics = FaceMetrics::from_font(font);
+        self.font.glyph_count = font.metrics(&[]).glyph_count;
+        self.font.instances = if is_var {
+            scan_instances(font).into()
+        } else {
+            Default::default()
+        };
+        self.font.axes = font
+            .variations()
+            .map(|var| VariationAxis {
+                tag: var.tag(),
+                min: var.min_value(),
+                default: var.default_value(),
+                max: var.max_value(),
+            })
+            .collect();
+        self.font.optical_size = self
+            .font
+            .axes
+            .iter()
+            .find(|axis| axis.tag == OPSZ)
+            .map(|axis| (axis.min, axis.max))
+            .or_else(|| os2_optical_size(font));
         for ws in font.writing_systems() {
             let script = match (ws.script(), ws.language()) {
                 (Some(Script::Han), Some(lang)) => (Script::Han, lang.cjk()),
@@ -104,57 +509,413 @@ impl FontScanner {
             };
             self.font.scripts.insert(script);
         }
+        self.font.cjk = scan_cjk(font, &self.font.scripts);
+        let charmap = font.charmap();
+        // Many fonts declare only a subset of the scripts they support, so
+        // also sample the character map for a representative character of
+        // each script. Latin is covered by nearly every font and Han
+        // coverage cannot be attributed to a particular locale, so both
+        // rely on the declared writing systems.
+        for script in (0..).map_while(crate::script_tags::script_from_index) {
+            if matches!(
+                script,
+                Script::Latin | Script::Han | Script::Common | Script::Inherited | Script::Unknown
+            ) {
+                continue;
+            }
+            if let Some(ch) = crate::script_tags::script_sample(script) {
+                if charmap.map(ch) != 0 {
+                    self.font.scripts.insert((script, Cjk::None));
+                }
+            }
+        }
+        for (script, _) in &self.font.scripts {
+            if let Some(ch) = crate::script_tags::script_sample(*script) {
+                if charmap.map(ch) != 0 {
+                    self.font.verified_scripts.insert(*script);
+                }
+            }
+        }
         f(&self.font);
         Some(())
     }
 }
 
+fn scan_capabilities(font: &FontRef, is_var: bool) -> Capabilities {
+    let mut caps = Capabilities::default();
+    let (_, weight, style) = font.attributes().parts();
+    caps.insert_attributes(weight, style);
+    if is_var {
+        caps.insert(Capabilities::VARIABLE);
+    }
+    if color_format(font).is_some() {
+        caps.insert(Capabilities::COLOR);
+    }
+    if font.table(MATH).is_some() {
+        caps.insert(Capabilities::MATH);
+    }
+    let metrics = font.metrics(&[]);
+    if metrics.is_monospace || has_uniform_advances(font) {
+        caps.insert(Capabilities::MONOSPACE);
+    }
+    if metrics.has_vertical_metrics || font.features().any(|f| f.tag() == VERT) {
+        caps.insert(Capabilities::VERTICAL);
+    }
+    caps
+}
+
+/// Returns true if narrow and wide letters, a digit and a period share an
+/// advance width, which catches fixed pitch fonts that do not set the flag in the
+/// post table.
+fn has_uniform_advances(font: &FontRef) -> bool {
+    let charmap = font.charmap();
+    let glyph_metrics = font.glyph_metrics(&[]);
+    let mut advances = "iMW0."
+        .chars()
+        .map(|ch| charmap.map(ch))
+        .filter(|glyph_id| *glyph_id != 0)
+        .map(|glyph_id| glyph_metrics.advance_width(glyph_id));
+    let first = match advances.next() {
+        Some(advance) if advance > 0. => advance,
+        _ => return false,
+    };
+    let mut count = 1;
+    for advance in advances {
+        if advance != first {
+            return false;
+        }
+        count += 1;
+    }
+    // Require enough glyphs to tell proportional fonts apart.
+    count >= 3
+}
+
+/// Returns the format of the color glyphs in a font.
+fn color_format(font: &FontRef) -> Option<ColorFormat> {
+    COLOR_TABLES
+        .iter()
+        .filter(|(tag, _)| font.table(*tag).is_some())
+        // Layered glyphs are useless without the palettes.
+        .find(|(_, format)| *format != ColorFormat::Colr || font.table(CPAL).is_some())
+        .map(|(_, format)| *format)
+}
+
+/// Computes more precise attributes for a font than those recorded at scan
+/// time.
+///
+/// This reads the exact OS/2 weight class (mapping the legacy 1-9 values),
+/// the oblique angle from the post table, the default instance of any
+/// weight, width, slant and italic variation axes and falls back to the
+/// head table style bits for fonts without an OS/2 table.
+pub fn refine_attributes(font: &FontRef) -> (Stretch, Weight, Style) {
+    let (stretch, mut weight, mut style) = font.attributes().parts();
+    if let Some(os2) = font.table(OS2) {
+        match read_u16(os2, 4) {
+            Some(class @ 1..=9) => weight = Weight(class * 100),
+            Some(class @ 10..=1000) => weight = Weight(class),
+            _ => {}
+        }
+    } else if let Some(mac_style) = font.table(HEAD).and_then(|head| read_u16(head, 44)) {
+        if mac_style & 1 != 0 {
+            weight = Weight::BOLD;
+        }
+        if mac_style & 2 != 0 {
+            style = Style::Italic;
+        }
+    }
+    // The post table stores the angle counter-clockwise from vertical while
+    // CSS oblique angles are clockwise.
+    let angle = font
+        .table(POST)
+        .and_then(|post| read_u32(post, 4))
+        .map(|fixed| -(fixed as i32 as f32 / 65536.))
+        .filter(|angle| *angle != 0. && angle.abs() <= 90.);
+    if let Some(angle) = angle {
+        if style != Style::Italic {
+            style = Style::Oblique(ObliqueAngle::from_degrees(angle));
+        }
+    }
+    let mut parts = (stretch, weight, style);
+    apply_stat(font, &mut parts);
+    for var in font.variations() {
+        apply_axis(var.tag(), var.default_value(), &mut parts);
+    }
+    parts
+}
+
+/// Returns the optical size range recorded in version 5 of the OS/2 table.
+fn os2_optical_size(font: &FontRef) -> Option<(f32, f32)> {
+    let os2 = font.table(OS2)?;
+    if read_u16(os2, 0)? < 5 {
+        return None;
+    }
+    // The bounds are stored in twentieths of a point.
+    let lower = read_u16(os2, 96)?;
+    let upper = read_u16(os2, 98)?;
+    if lower >= upper || upper == 0xFFFF {
+        return None;
+    }
+    // Unlike the maximum of an optical size axis, the upper bound is
+    // exclusive, so the largest size below it is recorded instead. It is
+    // positive, so the preceding float has the preceding bit pattern.
+    let upper = upper as f32 / 20.;
+    Some((lower as f32 / 20., f32::from_bits(upper.to_bits() - 1)))
+}
+
+/// Returns the CJK locale that the glyphs of a font are designed for.
+///
+/// Pan-CJK families such as Noto Sans CJK and Source Han Sans provide a face
+/// for each region with the same attributes. The design languages in the
+/// metadata table are consulted first, followed by a regional suffix in the
+/// family name and finally the Han writing systems declared by the font.
+fn scan_cjk(font: &FontRef, scripts: &HashSet<(Script, Cjk)>) -> Cjk {
+    if let Some(languages) = font.table(META).and_then(meta_design_languages) {
+        let cjk = single_cjk(languages.split(',').map(|tag| cjk_from_tag(tag.trim())));
+        if cjk != Cjk::None {
+            return cjk;
+        }
+    }
+    if !scripts.iter().any(|(script, _)| *script == Script::Han) {
+        return Cjk::None;
+    }
+    let strings = font.localized_strings();
+    let name = strings
+        .find_by_id(StringId::Family, Some("en"))
+        .or_else(|| strings.find_by_id(StringId::Family, None))
+        .map(|name| name.chars().collect::<String>())
+        .unwrap_or_default();
+    let cjk = single_cjk(name.split(' ').map(|word| match word {
+        "JP" | "J" => Cjk::Japanese,
+        "KR" | "K" => Cjk::Korean,
+        "SC" | "CN" => Cjk::Simplified,
+        "TC" | "HK" | "TW" => Cjk::Traditional,
+        _ => Cjk::None,
+    }));
+    if cjk != Cjk::None {
+        return cjk;
+    }
+    single_cjk(scripts.iter().map(|(_, cjk)| *cjk))
+}
+
+/// Returns the CJK locale for a script or language tag.
+fn cjk_from_tag(tag: &str) -> Cjk {
+    let tag = tag.to_ascii_lowercase();
+    match tag.as_str() {
+        "jpan" | "hira" | "kana" => Cjk::Japanese,
+        "kore" | "hang" => Cjk::Korean,
+        "hans" | "zh-hans" | "zh-cn" | "zh-sg" => Cjk::Simplified,
+        "hant" | "zh-hant" | "zh-tw" | "zh-hk" | "zh-mo" => Cjk::Traditional,
+        _ if tag == "ja" || tag.starts_with("ja-") => Cjk::Japanese,
+        _ if tag == "ko" || tag.starts_with("ko-") => Cjk::Korean,
+        _ => Cjk::None,
+    }
+}
+
+/// Returns the only CJK locale in the sequence, or `Cjk::None` if there is
+/// none or there are several.
+fn single_cjk(iter: impl Iterator<Item = Cjk>) -> Cjk {
+    let mut found = Cjk::None;
+    for cjk in iter.filter(|cjk| *cjk != Cjk::None) {
+        if found != Cjk::None && found != cjk {
+            return Cjk::None;
+        }
+        found = cjk;
+    }
+    found
+}
+
+/// Updates attributes with the axis values in the style attributes table of
+/// a static font.
+///
+/// The table of a static font describes the position of the font within
+/// its family on each axis. Axes with more than one value describe other
+/// members of the family and are ignored. Variable fonts are skipped since
+/// the variation axes are more precise.
+fn apply_stat(font: &FontRef, parts: &mut (Stretch, Weight, Style)) {
+    if font.variations().len() != 0 {
+        return;
+    }
+    let values = match font.table(STAT).and_then(stat_axis_values) {
+        Some(values) => values,
+        None => return,
+    };
+    for (tag, value) in &values {
+        if values.iter().all(|(t, v)| t != tag || v == value) {
+            apply_axis(*tag, *value, parts);
+        }
+    }
+}
+
+/// Updates attributes with the value of a variation axis.
+fn apply_axis(tag: Tag, value: f32, parts: &mut (Stretch, Weight, Style)) {
+    match tag {
+        WGHT => parts.1 = Weight(value.round().clamp(1., 1000.) as u16),
+        WDTH => parts.0 = Stretch::from_percentage(value),
+        SLNT if value != 0. => parts.2 = Style::Oblique(ObliqueAngle::from_degrees(-value)),
+        ITAL if value >= 1. => parts.2 = Style::Italic,
+        _ => {}
+    }
+}
+
+/// Collects the named instances of a variable font.
+fn scan_instances(font: &FontRef) -> Vec<NamedInstance> {
+    let axes = font.variations().map(|var| var.tag()).collect::<Vec<_>>();
+    font.instances()
+        .filter_map(|instance| {
+            let name = instance
+                .name(Some("en"))
+                .or_else(|| instance.name(None))?
+                .chars()
+                .collect::<String>();
+            let coords = axes
+                .iter()
+                .copied()
+                .zip(instance.values())
+                .collect::<Vec<_>>();
+            let mut parts = font.attributes().parts();
+            for (tag, value) in &coords {
+                apply_axis(*tag, *value, &mut parts);
+            }
+            Some(NamedInstance {
+                name,
+                coords,
+                attributes: Attributes::new(parts.0, parts.1, parts.2),
+            })
+        })
+        .collect()
+}
+
 impl CollectionData {
     pub fn add_fonts(
         &mut self,
         data: super::font::FontData,
         source: SourceData,
+        reg: Option<&mut Registration>,
+    ) -> Option<u32> {
+        let fonts = self.scanners.get().scan_all(&data);
+        self.add_scanned_fonts(&fonts, source, reg)
+    }
+
+    /// Commits fonts produced by a [`FontScanner`] to the collection.
+    ///
+    /// This is split from scanning so that callers can parse font data
+    /// without holding a lock on the collection.
+    pub fn add_scanned_fonts(
+        &mut self,
+        fonts: &[ScannedFont],
+        source: SourceData,
         mut reg: Option<&mut Registration>,
     ) -> Option<u32> {
-        let mut scanner = FontScanner::default();
         let is_user = self.is_user;
         let source_id = SourceId::alloc(self.sources.len(), is_user)?;
         let mut added_source = false;
         let mut count = 0;
-        scanner.scan(&*data, &source, |font| {
+        let no_scripts = HashSet::new();
+        for original in fonts {
+            let merged = if self.merge_weight_families {
+                merge_weight_name(original)
+            } else {
+                None
+            };
+            let font = merged.as_ref().unwrap_or(original);
             let font_id = if let Some(font_id) = FontId::alloc(self.fonts.len(), is_user) {
                 font_id
             } else {
-                return;
+                continue;
+            };
+            let name = font.lowercase_name.as_str();
+            let mapped = match self.family_map.get(name) {
+                // Negative lookups only suppress fonts from the system.
+                Some(None) if font.priority == SourcePriority::System => continue,
+                Some(mapped) => *mapped,
+                None => None,
             };
             let family_id =
-                if let Some(family_id) = self.family_map.get(font.lowercase_name.as_str()) {
-                    if family_id.is_none() {
-                        return;
+                if let Some(family_id) = self.family_roots.get(&(name.into(), font.priority)) {
+                    *family_id
+                } else if let Some(family_id) = FamilyId::alloc(self.families.len(), is_user) {
+                    let family = FamilyData {
+                        name: font.name.as_str().into(),
+                        has_stretch: false,
+                        capabilities: Capabilities::default(),
+                        glyph_count: 0,
+                        file_size: 0,
+                        refined: false,
+                        priority: font.priority,
+                        fonts: Vec::new(),
+                    };
+                    self.families.push(Arc::new(family));
+                    self.family_roots
+                        .insert((name.into(), font.priority), family_id);
+                    // Families from higher priority roots shadow same-named
+                    // families for name resolution.
+                    let shadows = match mapped {
+                        Some(mapped) => self.families[mapped.to_usize()].priority < font.priority,
+                        None => true,
+                    };
+                    // Localized and merged names resolve to another family
+                    // only until a family with that name is found.
+                    let aliased = mapped.is_some_and(|mapped| {
+                        let family = &self.families[mapped.to_usize()];
+                        !fold_name(&family.name).eq(name.chars())
+                    });
+                    if aliased {
+                        self.family_map.insert(name.into(), Some(family_id));
+                    } else if shadows {
+                        self.family_map.insert(name.into(), Some(family_id));
+                        if let Some(mapped) = mapped {
+                            self.replace_family(mapped, family_id);
+                        }
                     }
-                    family_id.unwrap()
+                    family_id
                 } else {
-                    if let Some(family_id) = FamilyId::alloc(self.families.len(), is_user) {
-                        let family = FamilyData {
-                            name: font.name.as_str().into(),
-                            has_stretch: false,
-                            fonts: Vec::new(),
-                        };
-                        self.families.push(Arc::new(family));
-                        self.family_map
-                            .insert(font.lowercase_name.as_str().into(), Some(family_id));
-                        family_id
-                    } else {
-                        return;
-                    }
+                    continue;
                 };
-            let family = Arc::make_mut(self.families.get_mut(family_id.to_usize()).unwrap());
+            // Keep the original name resolving to the merged family and
+            // resolve localized names unless they name another family.
+            let aliases = original
+                .localized_names
+                .iter()
+                .chain(merged.as_ref().map(|_| &original.lowercase_name));
+            for alias in aliases {
+                let entry = self.family_map.entry(alias.as_str().into()).or_default();
+                if entry.is_none() {
+                    *entry = Some(family_id);
+                }
+            }
             let (stretch, weight, style) = font.attributes.parts();
-            for font in &family.fonts {
-                if font.1 == stretch && font.2 == weight && font.3 == style {
-                    return;
+            // Fonts designed for different optical sizes or CJK regions are
+            // not duplicates.
+            let duplicates = self.families[family_id.to_usize()]
+                .fonts
+                .iter()
+                .filter(|font| font.1 == stretch && font.2 == weight && font.3 == style)
+                .map(|font| font.0)
+                .filter(|id| {
+                    self.fonts
+                        .get(id.to_usize())
+                        .map(|font| (font.optical_size, font.cjk))
+                        == Some((font.optical_size, font.cjk))
+                })
+                .collect::<Vec<_>>();
+            let mut variant = 0;
+            let mut replaced = None;
+            if let Some(existing) = duplicates.first() {
+                match self.duplicate_policy {
+                    DuplicatePolicy::FirstWins => continue,
+                    DuplicatePolicy::KeepAll => variant = duplicates.len() as u16,
+                    policy => {
+                        if !self.prefers_source(policy, *existing, &source) {
+                            continue;
+                        }
+                        replaced = Some(*existing);
+                    }
                 }
             }
+            let family = Arc::make_mut(self.families.get_mut(family_id.to_usize()).unwrap());
+            if let Some(replaced) = replaced {
+                family.fonts.retain(|font| font.0 != replaced);
+            }
             if !added_source {
                 self.sources.push(source.clone());
                 // self.sources.push(SourceData {
@@ -166,10 +927,21 @@ impl CollectionData {
             if stretch != Stretch::NORMAL {
                 family.has_stretch = true;
             }
-            match family.fonts.binary_search_by(|probe| probe.2.cmp(&weight)) {
-                Ok(index) | Err(index) => family
+            family.capabilities.union(font.capabilities);
+            family.glyph_count = family.glyph_count.max(font.glyph_count);
+            family.file_size = family.file_size.max(font.file_size);
+            if variant != 0 {
+                // Duplicates follow the fonts they duplicate.
+                let index = family.fonts.partition_point(|probe| probe.2 <= weight);
+                family
                     .fonts
-                    .insert(index, (font_id, stretch, weight, style)),
+                    .insert(index, (font_id, stretch, weight, style));
+            } else {
+                match family.fonts.binary_search_by(|probe| probe.2.cmp(&weight)) {
+                    Ok(index) | Err(index) => family
+                        .fonts
+                        .insert(index, (font_id, stretch, weight, style)),
+                }
             }
             if let Some(reg) = reg.as_mut() {
                 if !reg.families.contains(&family_id) {
@@ -178,18 +950,31 @@ impl CollectionData {
                 reg.fonts.push(font_id);
             }
 
-            for (script, cjk) in &font.scripts {
-                if *script == Script::Han {
-                    let entry = &mut self.cjk_families[*cjk as usize];
-                    if !entry.contains(&family_id) {
-                        entry.push(family_id);
-                    }
+            for script in &font.verified_scripts {
+                self.verified_fallbacks.insert((family_id, *script));
+            }
+            // Shadowed families remain enumerable but are excluded from
+            // fallback, as are namespaced document fonts, which only resolve
+            // through their namespace.
+            let scripts = if !font.namespaced && self.family_map.get(name) == Some(&Some(family_id))
+            {
+                &font.scripts
+            } else {
+                &no_scripts
+            };
+            for (script, cjk) in scripts {
+                if self.fixed_fallbacks.contains(&(*script, *cjk)) {
+                    continue;
+                }
+                let entry = if *script == Script::Han {
+                    &mut self.cjk_families[*cjk as usize]
                 } else {
                     let tag = crate::script_tags::script_tag(*script);
-                    let entry = self.script_fallbacks.entry(tag).or_default();
-                    if !entry.contains(&family_id) {
-                        entry.push(family_id);
-                    }
+                    self.script_fallbacks.entry(tag).or_default()
+                };
+                if !entry.contains(&family_id) {
+                    entry.push(family_id);
+                    self.unranked_fallbacks.insert((*script, *cjk));
                 }
             }
 
@@ -199,27 +984,305 @@ impl CollectionData {
                 index: font.index,
                 attributes: font.attributes,
                 cache_key: font.cache_key,
+                metrics: font.metrics,
+                glyph_count: font.glyph_count,
+                file_size: font.file_size,
+                coverage: Default::default(),
+                instances: font.instances.clone(),
+                axes: font.axes.clone(),
+                variant,
+                optical_size: font.optical_size,
+                cjk: font.cjk,
+                color_format: font.color_format,
             });
             count += 1;
-        });
+        }
         Some(count)
     }
+
+    /// Ranks the fallback chains that were extended with discovered
+    /// families. This runs once after each scan or lookup rather than for
+    /// every added font.
+    pub fn rank_fallbacks(&mut self) {
+        for (script, cjk) in core::mem::take(&mut self.unranked_fallbacks) {
+            if self.fixed_fallbacks.contains(&(script, cjk)) {
+                continue;
+            }
+            let tag = crate::script_tags::script_tag(script);
+            let chain = if script == Script::Han {
+                &mut self.cjk_families[cjk as usize]
+            } else {
+                match self.script_fallbacks.get_mut(&tag) {
+                    Some(chain) => chain,
+                    None => continue,
+                }
+            };
+            let mut chain = core::mem::take(chain);
+            rank_chain(&mut chain, (script, cjk), self);
+            if script == Script::Han {
+                self.cjk_families[cjk as usize] = chain;
+            } else {
+                self.script_fallbacks.insert(tag, chain);
+            }
+        }
+    }
+
+    /// Returns true if a font from the new source should replace an
+    /// existing font with the same attributes under the specified policy.
+    fn prefers_source(&self, policy: DuplicatePolicy, existing: FontId, new: &SourceData) -> bool {
+        let path_of = |source: &SourceData| match &source.kind {
+            SourceDataKind::Path(path) => Some(path.clone()),
+            _ => None,
+        };
+        let existing = self
+            .fonts
+            .get(existing.to_usize())
+            .and_then(|font| self.sources.get(font.source.to_usize()))
+            .and_then(path_of);
+        let (existing, new) = match (existing, path_of(new)) {
+            (Some(existing), Some(new)) => (existing, new),
+            _ => return false,
+        };
+        match policy {
+            DuplicatePolicy::PreferHinted => is_hinted(&new) && !is_hinted(&existing),
+            DuplicatePolicy::PreferNewest => {
+                let modified = |path: &Path| self.file_system.metadata(path).ok()?.modified;
+                modified(&new) > modified(&existing)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if the path indicates a hinted build of a font, as used by
+/// distributions that package hinted and unhinted builds separately.
+fn is_hinted(path: &Path) -> bool {
+    path.components().any(|component| {
+        let component = component.as_os_str().to_string_lossy().to_lowercase();
+        component.contains("hinted") && !component.contains("unhinted")
+    })
+}
+
+/// Weight names recognized as family name suffixes. Compound names precede
+/// their components so that the longest suffix is matched.
+const WEIGHT_SUFFIXES: [(&str, Weight); 19] = [
+    ("extra light", Weight::EXTRA_LIGHT),
+    ("extralight", Weight::EXTRA_LIGHT),
+    ("ultra light", Weight::EXTRA_LIGHT),
+    ("ultralight", Weight::EXTRA_LIGHT),
+    ("semi bold", Weight::SEMI_BOLD),
+    ("semibold", Weight::SEMI_BOLD),
+    ("demi bold", Weight::SEMI_BOLD),
+    ("demibold", Weight::SEMI_BOLD),
+    ("extra bold", Weight::EXTRA_BOLD),
+    ("extrabold", Weight::EXTRA_BOLD),
+    ("ultra bold", Weight::EXTRA_BOLD),
+    ("ultrabold", Weight::EXTRA_BOLD),
+    ("hairline", Weight::THIN),
+    ("thin", Weight::THIN),
+    ("light", Weight::LIGHT),
+    ("medium", Weight::MEDIUM),
+    ("bold", Weight::BOLD),
+    ("black", Weight::BLACK),
+    ("heavy", Weight::BLACK),
+];
+
+/// Returns a copy of the font with a weight suffix removed from the family
+/// name and the weight set accordingly, or `None` if the name does not end
+/// with a weight.
+fn merge_weight_name(font: &ScannedFont) -> Option<ScannedFont> {
+    let name = font.name.as_str();
+    let (cut, weight) = WEIGHT_SUFFIXES.iter().find_map(|(suffix, weight)| {
+        let cut = name.len().checked_sub(suffix.len() + 1)?;
+        let rest = name.get(cut..)?;
+        if cut > 0 && rest.starts_with(' ') && rest[1..].eq_ignore_ascii_case(suffix) {
+            Some((cut, *weight))
+        } else {
+            None
+        }
+    })?;
+    let name = name[..cut].trim_end();
+    let (stretch, _, style) = font.attributes.parts();
+    let mut merged = font.clone();
+    merged.name = name.to_owned();
+    merged.lowercase_name = fold_name(name).collect();
+    merged.attributes = Attributes::new(stretch, weight, style);
+    merged.capabilities.insert_attributes(weight, style);
+    Some(merged)
+}
+
+/// Orders a fallback chain so that families preferred by the platform
+/// configuration come first, followed by the curated families in their
+/// listed order. Discovered families are ordered so that those with verified
+/// coverage come first, using glyph count and file size as tie-breakers to
+/// prefer more comprehensive fonts. The sort is stable so discovery order is
+/// otherwise preserved.
+fn rank_chain(chain: &mut [FamilyId], (script, cjk): (Script, Cjk), collection: &CollectionData) {
+    // Keys involve several lookups, so compute each one once.
+    chain.sort_by_cached_key(|id| {
+        let rank = collection
+            .fallback_ranks
+            .get(&(*id, script, cjk))
+            .copied()
+            .unwrap_or(usize::MAX);
+        if let Some(position) = collection.curated_fallbacks.iter().position(|c| c == id) {
+            return (rank, position, Reverse(false), Reverse(0), Reverse(0));
+        }
+        let (glyph_count, file_size) = collection
+            .families
+            .get(id.to_usize())
+            .map(|family| (family.glyph_count, family.file_size))
+            .unwrap_or_default();
+        (
+            rank,
+            usize::MAX,
+            Reverse(collection.verified_fallbacks.contains(&(*id, script))),
+            Reverse(glyph_count),
+            Reverse(file_size),
+        )
+    });
 }
 
 pub(crate) fn scan_path(
     path: impl AsRef<Path>,
     collection: &mut CollectionData,
 ) -> Result<(), io::Error> {
-    let path = std::fs::canonicalize(path)?;
-    if path.is_file() {
-        let data = crate::font::FontData::from_file(&path)?;
-        collection.add_fonts(data, SourceData::from_path(&path)?, None);
+    scan_paths(&[path.as_ref()], SourcePriority::System, collection)
+}
+
+/// Scans a set of files and directories, parsing all of the files
+/// concurrently before adding them to the collection.
+pub(crate) fn scan_paths(
+    paths: &[impl AsRef<Path>],
+    priority: SourcePriority,
+    collection: &mut CollectionData,
+) -> Result<(), io::Error> {
+    let file_system = collection.file_system.clone();
+    let mut files = Vec::new();
+    let mut unreadable = Vec::new();
+    for path in paths {
+        let path = file_system.canonicalize(path.as_ref())?;
+        collect_files(&*file_system, &path, &mut files, &mut unreadable)?;
+    }
+    collection.add_rejected_faces(unreadable);
+    let cache = collection.scan_cache.clone();
+    let scanners = collection.scanners.clone();
+    for (path, mut fonts, rejected) in scan_files(&*file_system, &scanners, files, cache.as_deref())
+    {
+        collection.add_rejected_faces(rejected);
+        for font in &mut fonts {
+            font.priority = priority;
+        }
+        let mut source = SourceData::from_path(&path)?;
+        source.trust = priority.into();
+        collection.add_scanned_fonts(&fonts, source, None);
+    }
+    collection.rank_fallbacks();
+    Ok(())
+}
+
+/// Collects the files below a path. Entries that cannot be read are recorded
+/// as unreadable and skipped so that the rest of the walk continues. Fails
+/// only if the path itself cannot be read.
+pub(crate) fn collect_files(
+    file_system: &dyn FileSystem,
+    path: &Path,
+    files: &mut Vec<PathBuf>,
+    unreadable: &mut Vec<RejectedFace>,
+) -> Result<(), io::Error> {
+    if !file_system.metadata(path)?.is_dir {
+        files.push(path.to_owned());
     } else {
-        for entry in fs::read_dir(&path)? {
-            let entry = entry?;
-            let path = entry.path();
-            scan_path(&path, collection)?;
+        for entry in file_system.read_dir(path)? {
+            let result = file_system
+                .canonicalize(&entry)
+                .and_then(|path| collect_files(file_system, &path, files, unreadable));
+            if let Err(error) = result {
+                unreadable.push(RejectedFace::unreadable(entry, &error));
+            }
         }
     }
     Ok(())
 }
+
+/// Scans the specified files, returning the fonts found in each file in
+/// the original order along with the faces that were rejected as malformed.
+///
+/// Files that are present in the cache are not read. The remaining files
+/// are read and parsed concurrently with scanners from the pool. Files that
+/// cannot be read are reported as rejected. Rejected faces are cached along
+/// with the fonts of their files, so malformed files are reported on every
+/// scan without being parsed again.
+pub(crate) fn scan_files(
+    file_system: &dyn FileSystem,
+    scanners: &ScannerPool,
+    files: Vec<PathBuf>,
+    cache: Option<&Mutex<ScanCache>>,
+) -> Vec<(PathBuf, Vec<ScannedFont>, Vec<RejectedFace>)> {
+    let mut results: Vec<Option<(Vec<ScannedFont>, Vec<RejectedFace>)>> = vec![None; files.len()];
+    let mut stamps = vec![None; files.len()];
+    if let Some(cache) = cache {
+        let cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        for (i, path) in files.iter().enumerate() {
+            stamps[i] = FileStamp::of(file_system, path);
+            if let Some(stamp) = stamps[i] {
+                results[i] = cache.get(path, stamp).map(|(fonts, rejected)| {
+                    let rejected = rejected
+                        .iter()
+                        .map(|(index, reason)| RejectedFace {
+                            path: Some(path.clone()),
+                            index: *index,
+                            reason: *reason,
+                        })
+                        .collect();
+                    (fonts.to_vec(), rejected)
+                });
+            }
+        }
+    }
+    let pending = (0..files.len())
+        .filter(|i| results[*i].is_none())
+        .collect::<Vec<_>>();
+    let scanned = scanners.map(&pending, |scanner, i| {
+        let path = &files[*i];
+        let data = match file_system.read(path) {
+            Ok(data) => data,
+            Err(error) => {
+                return Some((
+                    Vec::new(),
+                    vec![RejectedFace::unreadable(path.clone(), &error)],
+                ))
+            }
+        };
+        let fonts = scanner.scan_all(&data);
+        Some((fonts, scanner.rejected_faces(Some(path))))
+    });
+    for (i, result) in pending.into_iter().zip(scanned) {
+        results[i] = result.flatten();
+    }
+    if let Some(cache) = cache {
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        for (i, path) in files.iter().enumerate() {
+            if let (Some(stamp), Some((fonts, rejected))) = (stamps[i], &results[i]) {
+                let unreadable = rejected
+                    .iter()
+                    .any(|face| matches!(face.reason, FaceRejection::Unreadable(_)));
+                if !unreadable && cache.get(path, stamp).is_none() {
+                    let rejected = rejected
+                        .iter()
+                        .map(|face| (face.index, face.reason))
+                        .collect();
+                    cache.insert(path, stamp, fonts.clone(), rejected);
+                }
+            }
+        }
+    }
+    files
+        .into_iter()
+        .zip(results)
+        .filter_map(|(path, result)| {
+            let (fonts, rejected) = result?;
+            Some((path, fonts, rejected))
+        })
+        .collect()
+}
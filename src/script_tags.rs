@@ -1,3 +1,5 @@
+use swash::text::Script;
+
 #[cfg_attr(rustfmt, rustfmt_skip)]
 const SCRIPT_TAGS: [[u8; 4]; 157] = [
     *b"Adlm", *b"Aghb", *b"Ahom", *b"Arab", *b"Armi", *b"Armn", *b"Avst", *b"Bali", *b"Bamu", 
@@ -23,3 +25,134 @@ const SCRIPT_TAGS: [[u8; 4]; 157] = [
 pub fn script_tag(script: swash::text::Script) -> [u8; 4] {
     SCRIPT_TAGS[script as usize]
 }
+
+/// Representative character for each script, used to verify that a font
+/// declaring support for a script actually maps characters from it. Zero
+/// marks scripts without a meaningful sample.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SCRIPT_SAMPLES: [u32; 157] = [
+    0x1E922, 0x10530, 0x11700, 0x0627, 0x10840, 0x0561, 0x10B00, 0x1B05, 0xA6A0, 
+    0x16AD0, 0x1BC0, 0x0995, 0x11C00, 0x3105, 0x11013, 0x2801, 0x1A00, 0x1743, 
+    0x11103, 0x1401, 0x102A0, 0xAA00, 0x13A0, 0x10FB0, 0x2C80, 0x10800, 0x0430, 
+    0x0915, 0x11900, 0x11800, 0x10400, 0x1BC00, 0x13000, 0x10500, 0x10FE0, 0x1200, 
+    0x10D0, 0x2C00, 0x11D60, 0x11D00, 0x10330, 0x11305, 0x03B1, 0x0A95, 0x0A15, 
+    0xAC00, 0x4E2D, 0x1723, 0x108E0, 0x05D0, 0x3042, 0x14400, 0x16B00, 0x1E100, 
+    0x10C80, 0x10300, 0xA984, 0xA90A, 0x30A2, 0x10A10, 0x1780, 0x11200, 0x18B00, 
+    0x0C95, 0x1108D, 0x1A20, 0x0E81, 0x0061, 0x1C00, 0x1901, 0x10600, 0x10000, 
+    0xA4D0, 0x10280, 0x10920, 0x11150, 0x11EE0, 0x0840, 0x10AC0, 0x11C72, 0x16E40, 
+    0x1E800, 0x109A0, 0x10980, 0x0D15, 0x1160E, 0x1820, 0x16A40, 0xABC0, 0x11280, 
+    0x1000, 0x119A0, 0x10A80, 0x10880, 0x11400, 0x07CA, 0x1B170, 0x1681, 0x1C5A, 
+    0x10C00, 0x0B15, 0x104B0, 0x10480, 0x10860, 0x11AC0, 0x10350, 0xA840, 0x10B60, 
+    0x10B80, 0x10900, 0x16F00, 0x10B40, 0xA930, 0x10D00, 0x16A0, 0x0800, 0x10A60, 
+    0xA882, 0x1D800, 0x10450, 0x11183, 0x11580, 0x112B0, 0x0D9A, 0x10F30, 0x10F00, 
+    0x110D0, 0x11A50, 0x1B8A, 0xA800, 0x0710, 0x1763, 0x11680, 0x1950, 0x1980, 
+    0x0B95, 0x17000, 0xAA80, 0x0C15, 0x2D30, 0x1703, 0x0780, 0x0E01, 0x0F40, 
+    0x1148F, 0x10380, 0xA500, 0x118C0, 0x1E2C0, 0x103A0, 0x12000, 0x10E80, 0xA000, 
+    0x11A00, 0x0000, 0x0000, 0x0000, 
+];
+
+pub fn script_sample(script: swash::text::Script) -> Option<char> {
+    match SCRIPT_SAMPLES[script as usize] {
+        0 => None,
+        ch => char::from_u32(ch),
+    }
+}
+
+/// Freely available font families that cover each script, used to suggest
+/// fonts to install when a script has no coverage.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SCRIPT_SUGGESTIONS: [&str; 157] = [
+    "Noto Sans Adlam", "Noto Sans Caucasian Albanian", "Noto Serif Ahom", "Noto Sans Arabic",
+    "Noto Sans Imperial Aramaic", "Noto Sans Armenian", "Noto Sans Avestan", "Noto Sans Balinese",
+    "Noto Sans Bamum", "Noto Sans Bassa Vah", "Noto Sans Batak", "Noto Sans Bengali",
+    "Noto Sans Bhaiksuki", "Noto Sans CJK TC", "Noto Sans Brahmi", "Noto Sans Symbols 2",
+    "Noto Sans Buginese", "Noto Sans Buhid", "Noto Sans Chakma", "Noto Sans Canadian Aboriginal",
+    "Noto Sans Carian", "Noto Sans Cham", "Noto Sans Cherokee", "Noto Sans Chorasmian",
+    "Noto Sans Coptic", "Noto Sans Cypriot", "Noto Sans", "Noto Sans Devanagari",
+    "Noto Serif Dives Akuru", "Noto Serif Dogra", "Noto Sans Deseret", "Noto Sans Duployan",
+    "Noto Sans Egyptian Hieroglyphs", "Noto Sans Elbasan", "Noto Sans Elymaic", "Noto Sans Ethiopic",
+    "Noto Sans Georgian", "Noto Sans Glagolitic", "Noto Sans Gunjala Gondi", "Noto Sans Masaram Gondi",
+    "Noto Sans Gothic", "Noto Serif Grantha", "Noto Sans", "Noto Sans Gujarati",
+    "Noto Sans Gurmukhi", "Noto Sans CJK KR", "Noto Sans CJK SC", "Noto Sans Hanunoo",
+    "Noto Sans Hatran", "Noto Sans Hebrew", "Noto Sans CJK JP", "Noto Sans Anatolian Hieroglyphs",
+    "Noto Sans Pahawh Hmong", "Noto Serif Nyiakeng Puachue Hmong", "Noto Sans Old Hungarian", "Noto Sans Old Italic",
+    "Noto Sans Javanese", "Noto Sans Kayah Li", "Noto Sans CJK JP", "Noto Sans Kharoshthi",
+    "Noto Sans Khmer", "Noto Sans Khojki", "Noto Serif Khitan Small Script", "Noto Sans Kannada",
+    "Noto Sans Kaithi", "Noto Sans Tai Tham", "Noto Sans Lao", "Noto Sans",
+    "Noto Sans Lepcha", "Noto Sans Limbu", "Noto Sans Linear A", "Noto Sans Linear B",
+    "Noto Sans Lisu", "Noto Sans Lycian", "Noto Sans Lydian", "Noto Sans Mahajani",
+    "Noto Serif Makasar", "Noto Sans Mandaic", "Noto Sans Manichaean", "Noto Sans Marchen",
+    "Noto Sans Medefaidrin", "Noto Sans Mende Kikakui", "Noto Sans Meroitic", "Noto Sans Meroitic",
+    "Noto Sans Malayalam", "Noto Sans Modi", "Noto Sans Mongolian", "Noto Sans Mro",
+    "Noto Sans Meetei Mayek", "Noto Sans Multani", "Noto Sans Myanmar", "Noto Sans Nandinagari",
+    "Noto Sans Old North Arabian", "Noto Sans Nabataean", "Noto Sans Newa", "Noto Sans NKo",
+    "Noto Sans Nushu", "Noto Sans Ogham", "Noto Sans Ol Chiki", "Noto Sans Old Turkic",
+    "Noto Sans Oriya", "Noto Sans Osage", "Noto Sans Osmanya", "Noto Sans Palmyrene",
+    "Noto Sans Pau Cin Hau", "Noto Sans Old Permic", "Noto Sans Phags Pa", "Noto Sans Inscriptional Pahlavi",
+    "Noto Sans Psalter Pahlavi", "Noto Sans Phoenician", "Noto Sans Miao", "Noto Sans Inscriptional Parthian",
+    "Noto Sans Rejang", "Noto Sans Hanifi Rohingya", "Noto Sans Runic", "Noto Sans Samaritan",
+    "Noto Sans Old South Arabian", "Noto Sans Saurashtra", "Noto Sans SignWriting", "Noto Sans Shavian",
+    "Noto Sans Sharada", "Noto Sans Siddham", "Noto Sans Khudawadi", "Noto Sans Sinhala",
+    "Noto Sans Sogdian", "Noto Sans Old Sogdian", "Noto Sans Sora Sompeng", "Noto Sans Soyombo",
+    "Noto Sans Sundanese", "Noto Sans Syloti Nagri", "Noto Sans Syriac", "Noto Sans Tagbanwa",
+    "Noto Sans Takri", "Noto Sans Tai Le", "Noto Sans New Tai Lue", "Noto Sans Tamil",
+    "Noto Serif Tangut", "Noto Sans Tai Viet", "Noto Sans Telugu", "Noto Sans Tifinagh",
+    "Noto Sans Tagalog", "Noto Sans Thaana", "Noto Sans Thai", "Noto Serif Tibetan",
+    "Noto Sans Tirhuta", "Noto Sans Ugaritic", "Noto Sans Vai", "Noto Sans Warang Citi",
+    "Noto Sans Wancho", "Noto Sans Old Persian", "Noto Sans Cuneiform", "Noto Serif Yezidi",
+    "Noto Sans Yi", "Noto Sans Zanabazar Square", "", "",
+    "",
+];
+
+pub fn script_suggestion(script: swash::text::Script) -> Option<&'static str> {
+    match SCRIPT_SUGGESTIONS[script as usize] {
+        "" => None,
+        name => Some(name),
+    }
+}
+
+/// Every script in discriminant order, used to recover a script from its
+/// index when reading serialized data.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SCRIPTS: [Script; 157] = [
+    Script::Adlam, Script::CaucasianAlbanian, Script::Ahom, Script::Arabic, Script::ImperialAramaic, Script::Armenian,
+    Script::Avestan, Script::Balinese, Script::Bamum, Script::BassaVah, Script::Batak, Script::Bengali,
+    Script::Bhaiksuki, Script::Bopomofo, Script::Brahmi, Script::Braille, Script::Buginese, Script::Buhid,
+    Script::Chakma, Script::CanadianAboriginal, Script::Carian, Script::Cham, Script::Cherokee, Script::Chorasmian,
+    Script::Coptic, Script::Cypriot, Script::Cyrillic, Script::Devanagari, Script::DivesAkuru, Script::Dogra,
+    Script::Deseret, Script::Duployan, Script::EgyptianHieroglyphs, Script::Elbasan, Script::Elymaic, Script::Ethiopic,
+    Script::Georgian, Script::Glagolitic, Script::GunjalaGondi, Script::MasaramGondi, Script::Gothic, Script::Grantha,
+    Script::Greek, Script::Gujarati, Script::Gurmukhi, Script::Hangul, Script::Han, Script::Hanunoo,
+    Script::Hatran, Script::Hebrew, Script::Hiragana, Script::AnatolianHieroglyphs, Script::PahawhHmong, Script::NyiakengPuachueHmong,
+    Script::OldHungarian, Script::OldItalic, Script::Javanese, Script::KayahLi, Script::Katakana, Script::Kharoshthi,
+    Script::Khmer, Script::Khojki, Script::KhitanSmallScript, Script::Kannada, Script::Kaithi, Script::TaiTham,
+    Script::Lao, Script::Latin, Script::Lepcha, Script::Limbu, Script::LinearA, Script::LinearB,
+    Script::Lisu, Script::Lycian, Script::Lydian, Script::Mahajani, Script::Makasar, Script::Mandaic,
+    Script::Manichaean, Script::Marchen, Script::Medefaidrin, Script::MendeKikakui, Script::MeroiticCursive, Script::MeroiticHieroglyphs,
+    Script::Malayalam, Script::Modi, Script::Mongolian, Script::Mro, Script::MeeteiMayek, Script::Multani,
+    Script::Myanmar, Script::Nandinagari, Script::OldNorthArabian, Script::Nabataean, Script::Newa, Script::Nko,
+    Script::Nushu, Script::Ogham, Script::OlChiki, Script::OldTurkic, Script::Oriya, Script::Osage,
+    Script::Osmanya, Script::Palmyrene, Script::PauCinHau, Script::OldPermic, Script::PhagsPa, Script::InscriptionalPahlavi,
+    Script::PsalterPahlavi, Script::Phoenician, Script::Miao, Script::InscriptionalParthian, Script::Rejang, Script::HanifiRohingya,
+    Script::Runic, Script::Samaritan, Script::OldSouthArabian, Script::Saurashtra, Script::SignWriting, Script::Shavian,
+    Script::Sharada, Script::Siddham, Script::Khudawadi, Script::Sinhala, Script::Sogdian, Script::OldSogdian,
+    Script::SoraSompeng, Script::Soyombo, Script::Sundanese, Script::SylotiNagri, Script::Syriac, Script::Tagbanwa,
+    Script::Takri, Script::TaiLe, Script::NewTaiLue, Script::Tamil, Script::Tangut, Script::TaiViet,
+    Script::Telugu, Script::Tifinagh, Script::Tagalog, Script::Thaana, Script::Thai, Script::Tibetan,
+    Script::Tirhuta, Script::Ugaritic, Script::Vai, Script::WarangCiti, Script::Wancho, Script::OldPersian,
+    Script::Cuneiform, Script::Yezidi, Script::Yi, Script::ZanabazarSquare, Script::Inherited, Script::Common,
+    Script::Unknown,
+];
+
+pub fn script_from_index(index: usize) -> Option<Script> {
+    SCRIPTS.get(index).copied()
+}
+
+/// Returns the script with the specified ISO 15924 tag, such as "Arab", or
+/// name, such as "Arabic", ignoring case.
+pub fn script_from_name(name: &str) -> Option<Script> {
+    SCRIPTS.iter().copied().find(|script| {
+        SCRIPT_TAGS[*script as usize].eq_ignore_ascii_case(name.as_bytes())
+            || script.name().eq_ignore_ascii_case(name)
+    })
+}
@@ -8,6 +8,7 @@ pub enum Os {
     Ios,
     Windows,
     Unix,
+    Bsd,
     Android,
     Other,
 }
@@ -23,12 +24,338 @@ pub const OS: Os = Os::Windows;
 
 #[cfg(all(
     unix,
-    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    not(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "netbsd",
+        target_os = "dragonfly"
+    ))
 ))]
 pub const OS: Os = Os::Unix;
 
+#[cfg(any(
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+pub const OS: Os = Os::Bsd;
+
 #[cfg(target_os = "android")]
 pub const OS: Os = Os::Android;
 
 #[cfg(not(any(unix, windows)))]
 pub const OS: Os = Os::Other;
+
+/// Returns the directories that contain system and user fonts for the
+/// current platform. Directories that do not exist are included.
+pub fn font_dirs() -> Vec<std::path::PathBuf> {
+    use std::env::var_os;
+    use std::path::PathBuf;
+    let mut dirs = Vec::new();
+    match OS {
+        Os::Windows => {
+            if let Some(windir) = var_os("WINDIR") {
+                dirs.push(PathBuf::from(windir).join("Fonts"));
+            }
+        }
+        // Applications cannot enumerate the font directories on iOS.
+        Os::Ios => {}
+        Os::MacOs => {
+            dirs.push("/System/Library/Fonts".into());
+            dirs.push("/Library/Fonts".into());
+            dirs.push("/Network/Library/Fonts".into());
+        }
+        Os::Android => {
+            dirs.push("/system/fonts".into());
+        }
+        Os::Unix | Os::Other => {
+            dirs.push("/usr/share/fonts".into());
+            dirs.push("/usr/local/share/fonts".into());
+        }
+        // Packages install fonts below /usr/local (/usr/pkg with pkgsrc) and
+        // the base X11 fonts live in the X.Org tree of the release.
+        Os::Bsd => {
+            dirs.push("/usr/local/share/fonts".into());
+            dirs.push("/usr/pkg/share/fonts".into());
+            dirs.push("/usr/local/lib/X11/fonts".into());
+            dirs.push("/usr/X11R6/lib/X11/fonts".into());
+            dirs.push("/usr/X11R7/lib/X11/fonts".into());
+        }
+    }
+    dirs.extend(user_font_dirs());
+    dirs
+}
+
+/// Returns the subset of [`font_dirs`] that contains fonts installed for
+/// the current user.
+pub fn user_font_dirs() -> Vec<std::path::PathBuf> {
+    use std::env::var_os;
+    use std::path::PathBuf;
+    let home = var_os("HOME").map(PathBuf::from);
+    let mut dirs = Vec::new();
+    match OS {
+        Os::Windows => {
+            if let Some(local) = var_os("LOCALAPPDATA") {
+                dirs.push(PathBuf::from(local).join("Microsoft\\Windows\\Fonts"));
+            }
+        }
+        Os::Ios | Os::Android => {}
+        Os::MacOs => {
+            if let Some(home) = home {
+                dirs.push(home.join("Library/Fonts"));
+            }
+        }
+        Os::Unix | Os::Bsd | Os::Other => {
+            if let Some(home) = home {
+                dirs.push(home.join(".local/share/fonts"));
+                dirs.push(home.join(".fonts"));
+            }
+        }
+    }
+    dirs
+}
+
+/// Returns the priority of fonts found below a directory.
+pub fn dir_priority(path: &std::path::Path) -> super::SourcePriority {
+    if user_font_dirs().iter().any(|dir| path.starts_with(dir)) {
+        super::SourcePriority::User
+    } else {
+        super::SourcePriority::System
+    }
+}
+
+/// Returns additional directories to scan for fonts, taken from the
+/// `FOUNT_FONT_PATH` environment variable and, on Linux and the BSDs, the
+/// `fonts` subdirectory of each entry in `XDG_DATA_DIRS`. This lets
+/// portable builds such as AppImages point the library at bundled font
+/// folders. Directories returned by [`font_dirs`] are excluded.
+pub fn extra_font_dirs() -> Vec<std::path::PathBuf> {
+    use std::env::{split_paths, var_os};
+    let mut dirs = Vec::new();
+    if let Some(paths) = var_os("FOUNT_FONT_PATH") {
+        dirs.extend(split_paths(&paths).filter(|path| !path.as_os_str().is_empty()));
+    }
+    if matches!(OS, Os::Unix | Os::Bsd) {
+        if let Some(paths) = var_os("XDG_DATA_DIRS") {
+            dirs.extend(
+                split_paths(&paths)
+                    .filter(|path| path.is_absolute())
+                    .map(|path| path.join("fonts")),
+            );
+        }
+    }
+    let platform_dirs = font_dirs();
+    let mut extra_dirs = Vec::new();
+    for dir in dirs {
+        if !platform_dirs.contains(&dir) && !extra_dirs.contains(&dir) {
+            extra_dirs.push(dir);
+        }
+    }
+    extra_dirs
+}
+
+/// Interface and monospace fonts configured by the desktop environment.
+#[cfg(feature = "desktop-settings")]
+#[derive(Clone, Default, Debug)]
+pub struct DesktopFonts {
+    pub interface: Option<String>,
+    pub monospace: Option<String>,
+}
+
+/// Reads the font settings of the running GNOME or KDE desktop. Settings
+/// from the current desktop are preferred and missing values are filled
+/// from the other.
+#[cfg(feature = "desktop-settings")]
+pub fn desktop_fonts() -> DesktopFonts {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let (first, second) = if desktop.to_ascii_lowercase().contains("kde") {
+        (kde_fonts(), gnome_fonts())
+    } else {
+        (gnome_fonts(), kde_fonts())
+    };
+    DesktopFonts {
+        interface: first.interface.or(second.interface),
+        monospace: first.monospace.or(second.monospace),
+    }
+}
+
+#[cfg(feature = "desktop-settings")]
+fn gnome_fonts() -> DesktopFonts {
+    let get = |key: &str| {
+        let output = std::process::Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", key])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        // Values are Pango font descriptions such as 'Cantarell 11'.
+        let value = String::from_utf8(output.stdout).ok()?;
+        let value = value.trim().trim_matches('\'');
+        let name = match value.rsplit_once(' ') {
+            Some((name, size)) if size.parse::<f32>().is_ok() => name,
+            _ => value,
+        };
+        Some(name.trim().to_owned()).filter(|name| !name.is_empty())
+    };
+    DesktopFonts {
+        interface: get("font-name"),
+        monospace: get("monospace-font-name"),
+    }
+}
+
+#[cfg(feature = "desktop-settings")]
+fn kde_fonts() -> DesktopFonts {
+    use std::env::var_os;
+    use std::path::PathBuf;
+    let mut fonts = DesktopFonts::default();
+    let config = var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let contents = match config.and_then(|dir| std::fs::read_to_string(dir.join("kdeglobals")).ok())
+    {
+        Some(contents) => contents,
+        None => return fonts,
+    };
+    let mut in_general = false;
+    for line in contents.lines().map(|line| line.trim()) {
+        if line.starts_with('[') {
+            in_general = line == "[General]";
+            continue;
+        }
+        if !in_general {
+            continue;
+        }
+        // Values are Qt font descriptions such as `Noto Sans,10,-1,5,50,0,0,0,0,0`.
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        let name = value.split(',').next().unwrap_or_default().trim();
+        if name.is_empty() {
+            continue;
+        }
+        match key {
+            "font" => fonts.interface = Some(name.to_owned()),
+            "fixed" => fonts.monospace = Some(name.to_owned()),
+            _ => {}
+        }
+    }
+    fonts
+}
+
+/// Returns the families that fontconfig prefers for text in the specified
+/// language, in order of preference. Families that fontconfig does not
+/// consider to support the language are omitted.
+#[cfg(feature = "fontconfig")]
+pub fn fontconfig_families(lang: &str) -> Vec<String> {
+    let output = std::process::Command::new("fc-match")
+        .args(["--sort", "--format", "%{family[0]}\t%{lang}\n"])
+        .arg(format!(":lang={}", lang))
+        .output();
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => return Vec::new(),
+    };
+    let mut families = Vec::<String>::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let (family, langs) = match line.split_once('\t') {
+            Some(entry) => entry,
+            None => continue,
+        };
+        // Language sets contain tags such as "zh-cn" so a language without
+        // a region matches any region.
+        let supported = langs
+            .split('|')
+            .any(|tag| tag == lang || (!lang.contains('-') && tag.split('-').next() == Some(lang)));
+        let family = family.trim();
+        if supported && !family.is_empty() && !families.iter().any(|f| f == family) {
+            families.push(family.to_owned());
+        }
+    }
+    families
+}
+
+/// Returns the families in the Core Text cascade list of the system font for
+/// the specified language, in order of preference.
+#[cfg(all(feature = "coretext", target_os = "macos"))]
+pub fn coretext_cascade(lang: &str) -> Vec<String> {
+    use core_foundation::array::CFArray;
+    use core_foundation::string::CFString;
+    // Core Text expects script subtags for Chinese.
+    let lang = match lang {
+        "zh-cn" => "zh-Hans",
+        "zh-tw" => "zh-Hant",
+        lang => lang,
+    };
+    let font = match core_text::font::new_from_name("Helvetica", 12.) {
+        Ok(font) => font,
+        Err(_) => return Vec::new(),
+    };
+    let languages = CFArray::from_CFTypes(&[CFString::new(lang)]);
+    let cascade = core_text::font::cascade_list_for_languages(&font, &languages);
+    let mut families = Vec::<String>::new();
+    for descriptor in cascade.iter() {
+        let family = descriptor.family_name();
+        if !families.contains(&family) {
+            families.push(family);
+        }
+    }
+    families
+}
+
+/// Returns the family that Core Text selects to render the character.
+#[cfg(all(feature = "coretext", target_os = "macos"))]
+pub fn coretext_family_for_char(ch: char) -> Option<String> {
+    use core_foundation::base::{CFRange, TCFType};
+    use core_foundation::string::{CFString, CFStringRef};
+    use core_text::font::{CTFont, CTFontRef};
+    extern "C" {
+        fn CTFontCreateForString(
+            current_font: CTFontRef,
+            string: CFStringRef,
+            range: CFRange,
+        ) -> CTFontRef;
+    }
+    let base = core_text::font::new_from_name("Helvetica", 12.).ok()?;
+    let mut buf = [0u8; 4];
+    let string = CFString::new(ch.encode_utf8(&mut buf));
+    let range = CFRange::init(0, string.char_len());
+    let font = unsafe {
+        CTFontCreateForString(
+            base.as_concrete_TypeRef(),
+            string.as_concrete_TypeRef(),
+            range,
+        )
+    };
+    if font.is_null() {
+        return None;
+    }
+    let font = unsafe { CTFont::wrap_under_create_rule(font) };
+    // The last resort font draws placeholder glyphs for unsupported
+    // characters.
+    Some(font.family_name()).filter(|name| !name.trim_start_matches('.').eq("LastResort"))
+}
+
+/// Returns the paths of the font files for all fonts known to Core Text.
+#[cfg(target_os = "ios")]
+pub fn coretext_font_paths() -> Vec<std::path::PathBuf> {
+    let collection = core_text::font_collection::create_for_all_families();
+    let descriptors = match collection.get_descriptors() {
+        Some(descriptors) => descriptors,
+        None => return Vec::new(),
+    };
+    let mut paths = Vec::new();
+    for descriptor in descriptors.iter() {
+        if let Some(path) = descriptor.font_path() {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
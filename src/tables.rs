@@ -0,0 +1,256 @@
+//! Helpers for reading raw font table data.
+
+use super::FaceRejection;
+
+pub fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+pub fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    read_u16(data, offset).map(|value| value as i16)
+}
+
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Returns the design languages in a metadata (meta) table as a comma
+/// separated list of script and language tags.
+pub fn meta_design_languages(meta: &[u8]) -> Option<&str> {
+    let count = read_u32(meta, 12)? as usize;
+    for i in 0..count {
+        let base = 16 + i * 12;
+        if read_u32(meta, base)? == u32::from_be_bytes(*b"dlng") {
+            let offset = read_u32(meta, base + 4)? as usize;
+            let len = read_u32(meta, base + 8)? as usize;
+            return std::str::from_utf8(meta.get(offset..offset + len)?).ok();
+        }
+    }
+    None
+}
+
+/// Returns the axis tags and values of the axis value tables in a style
+/// attributes (STAT) table. Ranges contribute their nominal value.
+pub fn stat_axis_values(stat: &[u8]) -> Option<Vec<(u32, f32)>> {
+    let fixed = |offset: usize| read_u32(stat, offset).map(|value| value as i32 as f32 / 65536.);
+    let axis_size = read_u16(stat, 4)? as usize;
+    let axis_count = read_u16(stat, 6)? as usize;
+    let axes_offset = read_u32(stat, 8)? as usize;
+    let value_count = read_u16(stat, 12)? as usize;
+    let values_offset = read_u32(stat, 14)? as usize;
+    let axis_tag = |index: usize| {
+        if index >= axis_count {
+            return None;
+        }
+        read_u32(stat, axes_offset + index * axis_size)
+    };
+    let mut values = Vec::new();
+    for i in 0..value_count {
+        let base = values_offset + read_u16(stat, values_offset + i * 2)? as usize;
+        match read_u16(stat, base)? {
+            1..=3 => {
+                let tag = match axis_tag(read_u16(stat, base + 2)? as usize) {
+                    Some(tag) => tag,
+                    None => continue,
+                };
+                values.push((tag, fixed(base + 8)?));
+            }
+            4 => {
+                let count = read_u16(stat, base + 2)? as usize;
+                for j in 0..count {
+                    let record = base + 8 + j * 6;
+                    if let Some(tag) = axis_tag(read_u16(stat, record)? as usize) {
+                        values.push((tag, fixed(record + 2)?));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(values)
+}
+
+/// Copies a single face out of a font collection into a standalone font
+/// file. Tables shared between faces of the collection are duplicated.
+pub fn extract_face(data: &[u8], index: u32) -> Option<Vec<u8>> {
+    let base = swash::FontRef::from_index(data, index as usize)?.offset as usize;
+    let num_tables = read_u16(data, base + 4)? as usize;
+    let header_len = 12 + num_tables * 16;
+    let mut out = data.get(base..base + header_len)?.to_vec();
+    for i in 0..num_tables {
+        let record = 12 + i * 16;
+        let offset = read_u32(&out, record + 8)? as usize;
+        let len = read_u32(&out, record + 12)? as usize;
+        let table = data.get(offset..offset.checked_add(len)?)?;
+        let new_offset = out.len() as u32;
+        out[record + 8..record + 12].copy_from_slice(&new_offset.to_be_bytes());
+        out.extend_from_slice(table);
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+    }
+    Some(out)
+}
+
+/// Checks the structure of the face whose table directory starts at the
+/// specified offset so that malformed fonts are rejected before they are
+/// added to a collection.
+///
+/// This verifies that the directory and all tables lie within the data,
+/// that the tables required for layout are present, that the font has
+/// glyph data and that the head and cmap tables can be parsed.
+pub fn validate_face(data: &[u8], base: usize) -> Result<(), FaceRejection> {
+    const REQUIRED: [[u8; 4]; 5] = [*b"cmap", *b"head", *b"hhea", *b"hmtx", *b"maxp"];
+    // Outlines or bitmaps, any of which provides glyphs.
+    const GLYPHS: [&[[u8; 4]]; 6] = [
+        &[*b"glyf", *b"loca"],
+        &[*b"CFF "],
+        &[*b"CFF2"],
+        &[*b"CBDT", *b"CBLC"],
+        &[*b"EBDT", *b"EBLC"],
+        &[*b"sbix"],
+    ];
+    let num_tables = read_u16(data, base + 4).ok_or(FaceRejection::Truncated)? as usize;
+    if data.len() < base + 12 + num_tables * 16 {
+        return Err(FaceRejection::Truncated);
+    }
+    let mut tables = Vec::with_capacity(num_tables);
+    for i in 0..num_tables {
+        let record = base + 12 + i * 16;
+        let tag = [
+            data[record],
+            data[record + 1],
+            data[record + 2],
+            data[record + 3],
+        ];
+        let offset = read_u32(data, record + 8).unwrap_or_default() as usize;
+        let len = read_u32(data, record + 12).unwrap_or_default() as usize;
+        let table = offset
+            .checked_add(len)
+            .and_then(|end| data.get(offset..end))
+            .ok_or(FaceRejection::TableOutOfBounds(tag))?;
+        tables.push((tag, table));
+    }
+    let table = |tag: [u8; 4]| {
+        tables
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, table)| *table)
+    };
+    if let Some(tag) = REQUIRED.iter().find(|tag| table(**tag).is_none()) {
+        return Err(FaceRejection::MissingTable(*tag));
+    }
+    if !GLYPHS
+        .iter()
+        .any(|tags| tags.iter().all(|tag| table(*tag).is_some()))
+    {
+        return Err(FaceRejection::NoGlyphData);
+    }
+    let head = table(*b"head").unwrap_or_default();
+    let units_per_em = read_u16(head, 18).unwrap_or_default();
+    if head.len() < 54
+        || read_u32(head, 12) != Some(0x5F0F3CF5)
+        || !(16..=16384).contains(&units_per_em)
+    {
+        return Err(FaceRejection::InvalidHead);
+    }
+    if !has_valid_cmap(table(*b"cmap").unwrap_or_default()) {
+        return Err(FaceRejection::InvalidCmap);
+    }
+    Ok(())
+}
+
+/// Returns true if a character to glyph mapping (cmap) table contains a
+/// subtable in a known format that lies within the table. Variation
+/// sequence subtables do not count since they only refine other subtables.
+fn has_valid_cmap(cmap: &[u8]) -> bool {
+    let count = read_u16(cmap, 2).unwrap_or_default() as usize;
+    (0..count).any(|i| {
+        let offset = match read_u32(cmap, 4 + i * 8 + 4) {
+            Some(offset) => offset as usize,
+            None => return false,
+        };
+        let len = match read_u16(cmap, offset) {
+            Some(0 | 2 | 4 | 6) => read_u16(cmap, offset + 2).map(|len| len as usize),
+            Some(8 | 10 | 12 | 13) => read_u32(cmap, offset + 4).map(|len| len as usize),
+            _ => None,
+        };
+        match len {
+            Some(len) if len >= 4 => offset.checked_add(len).is_some_and(|end| end <= cmap.len()),
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a face with the specified tables. The head and cmap tables
+    /// are valid and the other tables are empty.
+    fn face(tags: &[&[u8; 4]]) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[12..16].copy_from_slice(&0x5F0F3CF5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        // A single format 4 subtable at offset 12.
+        let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12];
+        cmap.extend_from_slice(&[0, 4, 0, 16]);
+        cmap.resize(28, 0);
+        let mut data = vec![0, 1, 0, 0];
+        data.extend_from_slice(&(tags.len() as u16).to_be_bytes());
+        data.resize(12 + tags.len() * 16, 0);
+        for (i, tag) in tags.iter().enumerate() {
+            let table = match *tag {
+                b"head" => &head[..],
+                b"cmap" => &cmap[..],
+                _ => &[],
+            };
+            let record = 12 + i * 16;
+            let offset = data.len() as u32;
+            data[record..record + 4].copy_from_slice(*tag);
+            data[record + 8..record + 12].copy_from_slice(&offset.to_be_bytes());
+            data[record + 12..record + 16].copy_from_slice(&(table.len() as u32).to_be_bytes());
+            data.extend_from_slice(table);
+        }
+        data
+    }
+
+    const REQUIRED: [&[u8; 4]; 5] = [b"cmap", b"head", b"hhea", b"hmtx", b"maxp"];
+
+    fn with(tags: &[&'static [u8; 4]]) -> Vec<u8> {
+        face(&[&REQUIRED[..], tags].concat())
+    }
+
+    #[test]
+    fn accepts_glyph_formats() {
+        assert_eq!(validate_face(&with(&[b"glyf", b"loca"]), 0), Ok(()));
+        assert_eq!(validate_face(&with(&[b"CFF "]), 0), Ok(()));
+        assert_eq!(validate_face(&with(&[b"CBDT", b"CBLC"]), 0), Ok(()));
+        assert_eq!(validate_face(&with(&[b"EBDT", b"EBLC"]), 0), Ok(()));
+        assert_eq!(validate_face(&with(&[b"sbix"]), 0), Ok(()));
+    }
+
+    #[test]
+    fn rejects_malformed_faces() {
+        assert_eq!(
+            validate_face(&with(&[b"glyf"]), 0),
+            Err(FaceRejection::NoGlyphData)
+        );
+        assert_eq!(
+            validate_face(&face(&[b"cmap", b"head", b"hhea", b"glyf", b"loca"]), 0),
+            Err(FaceRejection::MissingTable(*b"hmtx"))
+        );
+        let mut data = with(&[b"CFF "]);
+        data.truncate(20);
+        assert_eq!(validate_face(&data, 0), Err(FaceRejection::Truncated));
+        let mut data = with(&[b"CFF "]);
+        // Point the head table past the end of the data.
+        data[12 + 16 + 8..12 + 16 + 12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(
+            validate_face(&data, 0),
+            Err(FaceRejection::TableOutOfBounds(*b"head"))
+        );
+    }
+}
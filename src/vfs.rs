@@ -0,0 +1,73 @@
+//! Abstraction over the file system used to locate and read fonts.
+
+use super::font::FontData;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// File system from which font files are scanned and loaded.
+///
+/// The library reads the files of path based sources through this trait,
+/// which allows scanning an in-memory tree in tests or the contents of an
+/// overlay or virtual file system when packaging. The default is
+/// [`OsFileSystem`].
+pub trait FileSystem: Send + Sync {
+    /// Returns the paths of the entries in the specified directory.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Reads the contents of the specified file.
+    fn read(&self, path: &Path) -> io::Result<FontData>;
+
+    /// Returns the metadata for the specified file or directory.
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+
+    /// Returns the canonical form of the specified path. Sources are keyed
+    /// by this path, so a file reachable through several paths is only
+    /// scanned once. The default implementation returns the path unchanged.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_owned())
+    }
+}
+
+/// Metadata for an entry in a [`FileSystem`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FileMetadata {
+    /// True if the entry is a directory.
+    pub is_dir: bool,
+    /// Size of the file in bytes.
+    pub len: u64,
+    /// Modification time of the file, if known. Files without a
+    /// modification time are not cached between runs.
+    pub modified: Option<SystemTime>,
+}
+
+/// File system of the operating system.
+///
+/// Files are memory mapped as described in [`FontData::from_file`].
+#[derive(Copy, Clone, Default, Debug)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<FontData> {
+        FontData::from_file(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(FileMetadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+}
@@ -0,0 +1,148 @@
+//! Monitoring of font directories for installed and removed fonts.
+
+use super::data::{SourceData, SourceDataKind, SourceDataStatus, SystemCollectionData};
+use super::library::Library;
+use super::scan::{collect_files, scan_files};
+use super::{Registration, RejectedFace};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Watches the system and user font directories and updates a library as
+/// fonts are installed, modified or removed.
+///
+/// Changes are applied from a background thread owned by the watcher and
+/// become visible to every [`FontContext`](super::FontContext) created for
+/// the library. Monitoring stops when the watcher is dropped.
+pub struct FontWatcher {
+    _watcher: RecommendedWatcher,
+    dirs: Vec<PathBuf>,
+}
+
+impl FontWatcher {
+    /// Returns the directories being monitored.
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+}
+
+impl Library {
+    /// Starts monitoring the system and user font directories for changes.
+    ///
+    /// Removed fonts are dropped from their families and families left
+    /// without fonts are removed from the name map and fallback lists.
+    /// Identifiers for removed fonts remain valid but loading them will fail.
+    ///
+    /// No directories are monitored if the library was built without
+    /// [system fonts](super::LibraryBuilder::with_system_fonts).
+    pub fn watch(&self) -> notify::Result<FontWatcher> {
+        let library = self.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    for path in &event.paths {
+                        update_path(&library, path);
+                    }
+                }
+            })?;
+        let system_fonts = match &*self.inner.system() {
+            SystemCollectionData::Scanned(data) => data.collection.system_fonts,
+            SystemCollectionData::Static(_) => true,
+        };
+        let mut dirs = Vec::new();
+        let candidates = if system_fonts {
+            let mut dirs = super::system::font_dirs();
+            dirs.extend(super::system::extra_font_dirs());
+            dirs
+        } else {
+            Vec::new()
+        };
+        for dir in candidates {
+            if dir.is_dir() {
+                watcher.watch(&dir, RecursiveMode::Recursive)?;
+                dirs.push(dir);
+            }
+        }
+        Ok(FontWatcher {
+            _watcher: watcher,
+            dirs,
+        })
+    }
+}
+
+/// Brings the library up to date with the current state of a changed path.
+///
+/// The path is read through the file system of the library.
+fn update_path(library: &Library, path: &Path) {
+    let (file_system, scanners, cache) = {
+        let system = library.inner.system();
+        (system.file_system(), system.scanners(), system.scan_cache())
+    };
+    let path = file_system
+        .canonicalize(path)
+        .unwrap_or_else(|_| path.to_owned());
+    let mut diff = match library.inner.system_mut() {
+        Some(mut system) => system.remove_path(&path),
+        None => return,
+    };
+    let mut files = Vec::new();
+    let mut unreadable: Vec<RejectedFace> = Vec::new();
+    // The path no longer exists if the fonts were removed.
+    let _ = collect_files(&*file_system, &path, &mut files, &mut unreadable);
+    files.retain(|file| is_font_file(file));
+    if !unreadable.is_empty() {
+        if let Some(mut system) = library.inner.system_mut() {
+            system.add_rejected_faces(&unreadable);
+        }
+    }
+    for (file, mut fonts, rejected) in scan_files(&*file_system, &scanners, files, cache.as_deref())
+    {
+        if !rejected.is_empty() {
+            if let Some(mut system) = library.inner.system_mut() {
+                system.add_rejected_faces(&rejected);
+            }
+        }
+        if fonts.is_empty() {
+            continue;
+        }
+        // Watched directories are the platform font directories.
+        let priority = super::system::dir_priority(&file);
+        for font in &mut fonts {
+            font.priority = priority;
+        }
+        let trust = priority.into();
+        let source = SourceData {
+            kind: SourceDataKind::Path(Arc::new(file)),
+            status: RwLock::new(SourceDataStatus::Vacant),
+            trust,
+        };
+        let mut system = match library.inner.system_mut() {
+            Some(system) => system,
+            None => return,
+        };
+        system.clear_missing(fonts.iter().map(|font| font.lowercase_name.clone()));
+        let family_count = system.family_count();
+        let mut reg = Registration::default();
+        system.add_scanned_fonts(&fonts, source, Some(&mut reg));
+        diff.added_families.extend(
+            reg.families
+                .into_iter()
+                .filter(|id| id.to_usize() >= family_count),
+        );
+        diff.added_fonts.extend(reg.fonts);
+    }
+    if !diff.is_empty() {
+        library.inner.bump_generation(diff);
+    }
+}
+
+fn is_font_file(path: &Path) -> bool {
+    #[cfg(not(feature = "woff"))]
+    const EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+    #[cfg(feature = "woff")]
+    const EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc", "woff", "woff2"];
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+        .unwrap_or(false)
+}
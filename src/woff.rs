@@ -0,0 +1,54 @@
+//! Decoding of WOFF and WOFF2 compressed fonts.
+
+const WOFF: [u8; 4] = *b"wOFF";
+const WOFF2: [u8; 4] = *b"wOF2";
+
+/// Decompresses WOFF or WOFF2 data to an OpenType font. Returns `None` if
+/// the data is not compressed or cannot be decoded.
+pub fn decode(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&WOFF) {
+        wuff::decompress_woff1(data).ok()
+    } else if data.starts_with(&WOFF2) {
+        wuff::decompress_woff2(data).ok()
+    } else {
+        None
+    }
+}
+
+/// Returns the size of the decompressed font declared in the header of WOFF
+/// or WOFF2 data, or `None` if the data is not compressed.
+pub fn decoded_len(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(&WOFF) && !data.starts_with(&WOFF2) {
+        return None;
+    }
+    let bytes = data.get(16..20)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FontData;
+
+    fn header(signature: [u8; 4], total_size: u32) -> Vec<u8> {
+        let mut data = vec![0; 44];
+        data[..4].copy_from_slice(&signature);
+        data[16..20].copy_from_slice(&total_size.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn reads_declared_size() {
+        assert_eq!(decoded_len(&header(WOFF, 1234)), Some(1234));
+        assert_eq!(decoded_len(&header(WOFF2, 5678)), Some(5678));
+        assert_eq!(decoded_len(&header(*b"OTTO", 1234)), None);
+        assert_eq!(decoded_len(&WOFF), None);
+    }
+
+    #[test]
+    fn rejects_oversized_decompression() {
+        let data = header(WOFF2, u32::MAX);
+        let error = FontData::from_reader(&data[..], 1024).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
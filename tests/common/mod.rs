@@ -0,0 +1,41 @@
+// Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::env;
+use std::path::PathBuf;
+
+/// Directory holding the DejaVu fonts on Debian based systems.
+pub const FONT_DIR: &str = "/usr/share/fonts/truetype/dejavu";
+
+/// Family name of the font returned by [`font_bytes`].
+pub const FONT_NAME: &str = "DejaVu Sans";
+
+/// Returns the directory of fonts used by the tests.
+///
+/// The directory may be overridden with `FOUNT_TEST_FONT_DIR`. When it does
+/// not exist, the calling test is reported as skipped and `None` is returned,
+/// unless `FOUNT_REQUIRE_TEST_FONTS` is set, in which case the test fails.
+pub fn font_dir(test: &str) -> Option<PathBuf> {
+    let dir = env::var_os("FOUNT_TEST_FONT_DIR").map_or_else(|| FONT_DIR.into(), PathBuf::from);
+    if dir.is_dir() {
+        return Some(dir);
+    }
+    if env::var_os("FOUNT_REQUIRE_TEST_FONTS").is_some() {
+        panic!("{}: test fonts not found in {}", test, dir.display());
+    }
+    eprintln!(
+        "skipping {}: test fonts not found in {}",
+        test,
+        dir.display()
+    );
+    None
+}
+
+/// Returns the family name and bytes of DejaVu Sans, or `None` if the test
+/// fonts are not available. See [`font_dir`].
+pub fn font_bytes(test: &str) -> Option<(String, Vec<u8>)> {
+    let path = font_dir(test)?.join("DejaVuSans.ttf");
+    let bytes = std::fs::read(&path)
+        .unwrap_or_else(|err| panic!("{}: cannot read {}: {}", test, path.display(), err));
+    Some((FONT_NAME.to_owned(), bytes))
+}
@@ -0,0 +1,45 @@
+use fount::{FontContext, Library};
+use std::thread;
+
+mod common;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn library_is_send_and_sync() {
+    assert_send_sync::<Library>();
+}
+
+#[test]
+fn concurrent_registration() {
+    let library = Library::default();
+    let (name, bytes) = match common::font_bytes("concurrent_registration") {
+        Some(font) => font,
+        None => return,
+    };
+    let threads = (0..8)
+        .map(|_| {
+            let library = library.clone();
+            let bytes = bytes.clone();
+            let name = name.clone();
+            thread::spawn(move || {
+                let fcx = FontContext::new(&library);
+                for _ in 0..16 {
+                    fcx.register_fonts(bytes.clone());
+                    let family = fcx.family_by_name(&name).expect("family should resolve");
+                    assert!(family.fonts().next().is_some());
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let fcx = FontContext::new(&library);
+    let lowercase_name = name.to_lowercase();
+    let count = fcx
+        .families()
+        .filter(|family| family.name().to_lowercase() == lowercase_name)
+        .count();
+    assert_eq!(count, 1);
+}
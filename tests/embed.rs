@@ -0,0 +1,143 @@
+#![cfg(feature = "embed")]
+
+use fount::embed::{
+    collection_source, StaticCollectionData, StaticFamilyData, StaticFontData, StaticSourceData,
+};
+use fount::{FamilyId, FontContext, FontId, FontScanner, Library, ScannedFont, SourceId};
+use swash::text::Script;
+
+mod common;
+
+fn leak<T>(items: Vec<T>) -> &'static [T] {
+    Box::leak(items.into_boxed_slice())
+}
+
+#[test]
+fn generates_source() {
+    let (name, bytes) = match common::font_bytes("generates_source") {
+        Some(font) => font,
+        None => return,
+    };
+    let dir = std::env::temp_dir().join(format!("fount-embed-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("font.ttf");
+    std::fs::write(&path, &bytes).unwrap();
+    let source = collection_source(&[&path]);
+    let missing = collection_source(&[dir.join("missing.ttf")]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let source = source.unwrap();
+    assert_eq!(missing.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    assert!(source.contains(&format!("name: {:?}", name)));
+    assert!(source.contains(&format!("include_bytes!({:?})", path.to_str().unwrap())));
+    assert!(source.contains("color_format: "));
+    assert!(source.trim_end().ends_with("&COLLECTION\n}"));
+}
+
+/// Builds a collection for the faces of a font in the same shape as the
+/// generated source, with a single source described by `source`.
+fn collection(
+    name: &str,
+    scanned: &[ScannedFont],
+    source: StaticSourceData,
+) -> &'static StaticCollectionData {
+    let family = FamilyId::from_raw(0);
+    let fonts = scanned
+        .iter()
+        .map(|font| StaticFontData {
+            family,
+            attributes: font.attributes(),
+            source: SourceId::from_raw(0),
+            index: font.index(),
+            color_format: font.color_format(),
+        })
+        .collect::<Vec<_>>();
+    let font_ids = (0..fonts.len() as u32).map(FontId::from_raw).collect();
+    Box::leak(Box::new(StaticCollectionData {
+        search_paths: &[],
+        families: leak(vec![StaticFamilyData {
+            name: Box::leak(name.into()),
+            lowercase_name: Box::leak(name.to_lowercase().into()),
+            has_stretch: false,
+            capabilities: 0,
+            fonts: leak(font_ids),
+        }]),
+        fonts: leak(fonts),
+        sources: leak(vec![source]),
+        default_families: leak(vec![family]),
+        script_fallbacks: &[],
+        generic_families: Default::default(),
+        cjk_families: Default::default(),
+    }))
+}
+
+/// Builds a collection for a font and checks that a library created from it
+/// resolves and loads the font.
+#[test]
+fn builds_library_from_collection() {
+    let (name, bytes) = match common::font_bytes("builds_library_from_collection") {
+        Some(font) => font,
+        None => return,
+    };
+    let scanned = FontScanner::new()
+        .scan_all(&bytes)
+        .into_iter()
+        .filter(|font| font.name() == name)
+        .collect::<Vec<_>>();
+    let source = StaticSourceData {
+        file_name: "font.ttf",
+        data: Some(leak(bytes.clone())),
+    };
+    let library = Library::from_embedded(collection(&name, &scanned, source));
+    let family = FamilyId::from_raw(0);
+    let fcx = FontContext::new(&library);
+    assert_eq!(fcx.default_families(), [family]);
+    let entry = fcx.family_by_name(&name).expect("family should resolve");
+    assert_eq!(entry.id(), family);
+    assert_eq!(entry.fonts().count(), scanned.len());
+    let font = fcx.font(entry.fonts().next().unwrap()).unwrap();
+    assert_eq!(font.color_format(), scanned[0].color_format());
+    let data = fcx.load(font.source()).expect("font should load");
+    assert_eq!(data.as_ref(), &bytes[..]);
+    // Registering the embedded data again reconciles with the collection.
+    let reg = fcx.register_fonts(bytes).expect("font should register");
+    assert_eq!(reg.families, [family]);
+    assert_eq!(reg.fonts, entry.fonts().collect::<Vec<_>>());
+    assert_eq!(fcx.families().count(), 1);
+}
+
+/// Registered fonts overlay a static collection, except for faces loaded
+/// from the same file as a source of the collection.
+#[test]
+fn overlays_registered_fonts() {
+    let dir = match common::font_dir("overlays_registered_fonts") {
+        Some(dir) => dir,
+        None => return,
+    };
+    let sans = dir.join("DejaVuSans.ttf");
+    let serif = dir.join("DejaVuSerif.ttf");
+    let scanned = FontScanner::new().scan_all(&std::fs::read(&sans).unwrap());
+    let source = StaticSourceData {
+        file_name: Box::leak(sans.to_str().unwrap().into()),
+        data: None,
+    };
+    let library = Library::from_embedded(collection(common::FONT_NAME, &scanned, source));
+    let fcx = FontContext::new(&library);
+    let family = FamilyId::from_raw(0);
+    let reg = fcx.register_font_file(&sans).expect("font should register");
+    assert_eq!(reg.families, [family]);
+    assert_eq!(fcx.families().count(), 1);
+    let reg = fcx
+        .register_font_file(&serif)
+        .expect("font should register");
+    assert_eq!(reg.families.len(), 1);
+    let added = reg.families[0];
+    assert_ne!(added, family);
+    assert_eq!(fcx.family_by_name("DejaVu Serif").unwrap().id(), added);
+    assert_eq!(fcx.families().count(), 2);
+    assert!(fcx.fallback_families(Script::Latin, None).contains(&added));
+    let font = fcx.font(reg.fonts[0]).unwrap();
+    assert!(fcx.load(font.source()).is_some());
+    assert!(fcx.unregister(&reg));
+    assert!(fcx.family_by_name("DejaVu Serif").is_none());
+    assert!(!fcx.fallback_families(Script::Latin, None).contains(&added));
+}
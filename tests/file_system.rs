@@ -0,0 +1,78 @@
+use fount::{FileMetadata, FileSystem, FontContext, FontData, LibraryBuilder};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod common;
+
+/// File system holding files in memory.
+#[derive(Default)]
+struct MemoryFileSystem {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFileSystem {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.keys().any(|file| file.parent() == Some(path))
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(self
+            .files
+            .keys()
+            .filter(|file| file.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<FontData> {
+        match self.files.get(path) {
+            Some(data) => Ok(FontData::new(data.clone())),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let len = match self.files.get(path) {
+            Some(data) => data.len() as u64,
+            None if self.is_dir(path) => 0,
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+        Ok(FileMetadata {
+            is_dir: self.is_dir(path),
+            len,
+            modified: None,
+        })
+    }
+}
+
+#[test]
+fn scans_and_loads_through_file_system() {
+    let (name, bytes) = match common::font_bytes("scans_and_loads_through_file_system") {
+        Some(font) => font,
+        None => return,
+    };
+    let mut file_system = MemoryFileSystem::default();
+    file_system
+        .files
+        .insert("/fonts/font.ttf".into(), bytes.clone());
+    file_system
+        .files
+        .insert("/fonts/readme.txt".into(), b"not a font".to_vec());
+    let mut builder = LibraryBuilder::headless();
+    builder.file_system(Arc::new(file_system));
+    builder.add_font_dir("/fonts").unwrap();
+    assert!(builder.add_font_dir("/missing").is_err());
+    let library = builder.build();
+    let fcx = FontContext::new(&library);
+    let family = fcx.family_by_name(&name).expect("family should resolve");
+    let font = fcx.font(family.fonts().next().unwrap()).unwrap();
+    let data = fcx.load(font.source()).expect("font should load");
+    assert_eq!(data.as_ref(), &bytes[..]);
+}
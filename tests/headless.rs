@@ -0,0 +1,34 @@
+use fount::{FontContext, GenericFamily, LibraryBuilder, SetupStatus};
+
+mod common;
+
+/// Generic families of a headless library are synthesized from the fonts
+/// added to the builder.
+#[test]
+fn synthesizes_generics_from_added_fonts() {
+    let dir = match common::font_dir("synthesizes_generics_from_added_fonts") {
+        Some(dir) => dir,
+        None => return,
+    };
+    let mut builder = LibraryBuilder::headless();
+    builder.add_font_dir(dir).unwrap();
+    let library = builder.build();
+    let fcx = FontContext::new(&library);
+    // The directory holds condensed variants of the serif and sans serif
+    // families, either of which may be chosen.
+    for (generic, css_name, prefix) in [
+        (GenericFamily::Serif, "serif", "DejaVu Serif"),
+        (GenericFamily::SansSerif, "sans-serif", "DejaVu Sans"),
+        (GenericFamily::Monospace, "monospace", "DejaVu Sans Mono"),
+    ] {
+        assert_ne!(library.generic_status(generic), SetupStatus::Missing);
+        let family = fcx
+            .family_by_name(css_name)
+            .expect("generic family should resolve");
+        assert!(family.name().starts_with(prefix), "{}", family.name());
+        assert_eq!(
+            family.name().contains("Mono"),
+            generic == GenericFamily::Monospace
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use fount::{FontContext, LibraryBuilder};
+
+mod common;
+
+/// Returns the font with a table inserted into its table directory, which
+/// is kept sorted by tag.
+fn with_table(font: &[u8], tag: &[u8; 4], table: &[u8]) -> Vec<u8> {
+    let read_u32 = |offset: usize| {
+        u32::from_be_bytes([
+            font[offset],
+            font[offset + 1],
+            font[offset + 2],
+            font[offset + 3],
+        ])
+    };
+    let count = u16::from_be_bytes([font[4], font[5]]) as usize;
+    let mut records = (0..count)
+        .map(|i| {
+            let record = 12 + i * 16;
+            let mut tag = [0; 4];
+            tag.copy_from_slice(&font[record..record + 4]);
+            (
+                tag,
+                read_u32(record + 8) as usize,
+                read_u32(record + 12) as usize,
+            )
+        })
+        .collect::<Vec<_>>();
+    records.push((*tag, 0, table.len()));
+    records.sort_by_key(|(tag, ..)| *tag);
+    let mut data = font[..12].to_vec();
+    data[4..6].copy_from_slice(&(records.len() as u16).to_be_bytes());
+    data.resize(12 + records.len() * 16, 0);
+    for (i, (record_tag, offset, len)) in records.iter().enumerate() {
+        let bytes = if record_tag == tag {
+            table
+        } else {
+            &font[*offset..*offset + *len]
+        };
+        let record = 12 + i * 16;
+        let offset = data.len() as u32;
+        data[record..record + 4].copy_from_slice(record_tag);
+        data[record + 8..record + 12].copy_from_slice(&offset.to_be_bytes());
+        data[record + 12..record + 16].copy_from_slice(&(bytes.len() as u32).to_be_bytes());
+        data.extend_from_slice(bytes);
+        data.resize((data.len() + 3) & !3, 0);
+    }
+    data
+}
+
+/// Builds a font variations table with a single optical size axis.
+fn opsz_fvar(min: i32, default: i32, max: i32) -> Vec<u8> {
+    // Header with the axes at offset 16 and no named instances.
+    let mut fvar = [1u16, 0, 16, 2, 1, 20, 0, 8]
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect::<Vec<_>>();
+    fvar.extend_from_slice(b"opsz");
+    for value in [min, default, max] {
+        // Values are 16.16 fixed point numbers.
+        fvar.extend_from_slice(&(value << 16).to_be_bytes());
+    }
+    fvar.extend_from_slice(&[0, 0, 1, 0]);
+    fvar
+}
+
+/// The maximum of an optical size axis is part of the range that the font
+/// is designed for.
+#[test]
+fn includes_axis_maximum() {
+    let (_, bytes) = match common::font_bytes("includes_axis_maximum") {
+        Some(font) => font,
+        None => return,
+    };
+    let bytes = with_table(&bytes, b"fvar", &opsz_fvar(8, 12, 144));
+    let library = LibraryBuilder::headless().build();
+    let fcx = FontContext::new(&library);
+    let family = fcx.register_fonts(bytes).unwrap().families[0];
+    let family = fcx.family(family).unwrap();
+    let font = fcx.font(family.fonts().next().unwrap()).unwrap();
+    assert_eq!(font.optical_size_range(), Some((8., 144.)));
+    for (size, supported) in [(6., false), (8., true), (144., true), (145., false)] {
+        assert_eq!(font.supports_optical_size(size), supported, "{}", size);
+        assert_eq!(
+            font.optical_size_distance(size) == 0.,
+            supported,
+            "{}",
+            size
+        );
+    }
+}
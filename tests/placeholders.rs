@@ -0,0 +1,55 @@
+use fount::{FontContext, LibraryBuilder, MissingFont};
+use swash::{Attributes, Stretch, Style, Weight};
+
+mod common;
+
+#[test]
+fn records_requested_fonts() {
+    let library = LibraryBuilder::headless().build();
+    let bold = Attributes::new(Stretch::NORMAL, Weight::BOLD, Style::Normal);
+    let id = library.add_placeholder("Missing Sans", Attributes::default());
+    assert!(id.is_placeholder());
+    assert_eq!(
+        library.add_placeholder("MISSING SANS", Attributes::default()),
+        id
+    );
+    let bold_id = library.add_placeholder("Missing Sans", bold);
+    assert_ne!(bold_id, id);
+    assert_eq!(
+        library.missing_font(id),
+        Some(MissingFont {
+            name: "Missing Sans".into(),
+            attributes: Attributes::default(),
+        })
+    );
+    assert_eq!(library.missing_font(bold_id).unwrap().attributes, bold);
+    let placeholders = library.placeholders();
+    assert_eq!(
+        placeholders.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        [id, bold_id]
+    );
+    // Without any fonts there is nothing to substitute.
+    let fcx = FontContext::new(&library);
+    assert!(fcx.family(id).is_none());
+}
+
+#[test]
+fn resolves_to_substitute() {
+    let (name, bytes) = match common::font_bytes("resolves_to_substitute") {
+        Some(font) => font,
+        None => return,
+    };
+    let library = LibraryBuilder::headless().build();
+    let fcx = FontContext::new(&library);
+    let registered = fcx.register_fonts(bytes).unwrap().families[0];
+    assert!(library.missing_font(registered).is_none());
+    // A placeholder resolves to the family it names once it is available.
+    let id = library.add_placeholder(&name, Attributes::default());
+    let family = fcx.family(id).expect("placeholder should resolve");
+    assert_eq!(family.id(), registered);
+    assert_eq!(
+        fcx.with_family(id, |family| family.name().to_owned()),
+        Some(name.clone())
+    );
+    assert_eq!(library.missing_font(id).unwrap().name, name);
+}